@@ -0,0 +1,35 @@
+#!/usr/bin/sh
+# Sends a Twitch EventSub message signed with the secret to the web server, to test the receiver without Twitch.
+# The streams are still checked on Helix after the event, use twitch_api_url to point it to a mock.
+#
+# Usage: ./eventsub_test.sh <twitch_eventsub_secret> <streamer login> [online|offline|challenge] [url]
+# Ex: ./eventsub_test.sh "a long random string" bobross online http://127.0.0.1:54424/twitch/eventsub
+
+secret=$1
+login=$2
+kind=${3:-online}
+url=${4:-http://127.0.0.1:54424/twitch/eventsub}
+
+message_id=$(date +%s%N)
+timestamp=$(date -u +%Y-%m-%dT%H:%M:%SZ)
+
+subscription='{"id":"local-test","status":"enabled","type":"stream.'"$kind"'","version":"1","condition":{"broadcaster_user_id":"0"},"transport":{"method":"webhook","callback":"'"$url"'"}}'
+
+if [ "$kind" = "challenge" ]; then
+    message_type=webhook_callback_verification
+    subscription=$(echo "$subscription" | sed 's/stream.challenge/stream.online/; s/"enabled"/"webhook_callback_verification_pending"/')
+    body='{"challenge":"local-test-challenge","subscription":'"$subscription"'}'
+else
+    message_type=notification
+    body='{"subscription":'"$subscription"',"event":{"broadcaster_user_id":"0","broadcaster_user_login":"'"$login"'","broadcaster_user_name":"'"$login"'"}}'
+fi
+
+signature=$(printf '%s%s%s' "$message_id" "$timestamp" "$body" | openssl dgst -sha256 -hmac "$secret" | awk '{print $NF}')
+
+curl -i -X POST "$url" \
+    -H "Content-Type: application/json" \
+    -H "Twitch-Eventsub-Message-Id: $message_id" \
+    -H "Twitch-Eventsub-Message-Timestamp: $timestamp" \
+    -H "Twitch-Eventsub-Message-Signature: sha256=$signature" \
+    -H "Twitch-Eventsub-Message-Type: $message_type" \
+    -d "$body"
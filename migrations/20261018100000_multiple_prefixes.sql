@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE prefixes ADD COLUMN additional_prefixes text[];
+
+CREATE TABLE user_prefixes (
+    user_id bigint PRIMARY KEY NOT NULL,
+    prefix text NOT NULL
+);
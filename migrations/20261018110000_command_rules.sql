@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE command_rules (
+    id serial NOT NULL PRIMARY KEY,
+    guild_id bigint NOT NULL,
+    target text NOT NULL,
+    is_group bool NOT NULL,
+    channel_id bigint,
+    role_id bigint,
+    allow bool NOT NULL
+);
+
+CREATE INDEX command_rules_guild_id_idx ON command_rules (guild_id);
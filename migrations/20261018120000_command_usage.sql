@@ -0,0 +1,28 @@
+-- Add migration script here
+-- guild_id is 0 for commands ran on DMs.
+CREATE TABLE command_usage (
+    id bigserial NOT NULL PRIMARY KEY,
+    command text NOT NULL,
+    guild_id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    duration_ms integer NOT NULL,
+    success bool NOT NULL,
+    error_kind text,
+    invoked_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX command_usage_invoked_at_idx ON command_usage (invoked_at);
+
+-- Daily rollup of command_usage, kept after the individual invocations are pruned.
+CREATE TABLE command_usage_daily (
+    day date NOT NULL,
+    command text NOT NULL,
+    guild_id bigint NOT NULL,
+    uses bigint NOT NULL DEFAULT 0,
+    failures bigint NOT NULL DEFAULT 0,
+    internal_errors bigint NOT NULL DEFAULT 0,
+    total_duration_ms bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, command, guild_id)
+);
+
+CREATE INDEX command_usage_daily_guild_id_idx ON command_usage_daily (guild_id, day);
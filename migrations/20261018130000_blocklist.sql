@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE blocklist (
+    id serial NOT NULL PRIMARY KEY,
+    target_id bigint NOT NULL,
+    is_guild bool NOT NULL,
+    reason text,
+    expires_at timestamptz,
+    blocked_by bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    UNIQUE (target_id, is_guild)
+);
+
+-- This user used to be blocked on the framework configuration.
+INSERT INTO blocklist (target_id, is_guild, reason, blocked_by) VALUES (135423120268984330, false, 'Blocked before the blocklist existed.', 0);
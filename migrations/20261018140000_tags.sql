@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE tags (
+    guild_id bigint NOT NULL,
+    name text NOT NULL,
+    content text NOT NULL,
+    created_by bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, name)
+);
@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE command_aliases (
+    guild_id bigint NOT NULL,
+    name text NOT NULL,
+    command text NOT NULL,
+    arguments text NOT NULL DEFAULT '',
+    created_by bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, name)
+);
@@ -0,0 +1,19 @@
+-- Add migration script here
+-- The posts already sent by every booru subscription.
+-- last_seen_at is updated every time the post is still on the latest posts of the booru,
+-- so only the posts that can't be sent again get pruned.
+CREATE TABLE sent_posts (
+    subscription_id integer NOT NULL REFERENCES new_posts (id) ON DELETE CASCADE,
+    post_hash text NOT NULL,
+    sent_at timestamptz NOT NULL DEFAULT now(),
+    last_seen_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (subscription_id, post_hash)
+);
+
+CREATE INDEX sent_posts_last_seen_at_idx ON sent_posts (last_seen_at);
+
+INSERT INTO sent_posts (subscription_id, post_hash)
+SELECT DISTINCT id, unnest(sent_md5) FROM new_posts WHERE sent_md5 IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE new_posts DROP COLUMN sent_md5;
@@ -0,0 +1,27 @@
+-- Add migration script here
+-- RSS and Atom feeds that are sent to a channel, with the bot or a webhook.
+-- The items are only sent when their title or summary contain the filter, if there's one.
+CREATE TABLE feed_subscriptions (
+    id serial PRIMARY KEY,
+    guild_id bigint NOT NULL,
+    channel_id bigint NOT NULL,
+    webhook text,
+    url text NOT NULL,
+    filter text,
+    created_by bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX feed_subscriptions_guild_id_idx ON feed_subscriptions (guild_id);
+
+-- The items already sent by every feed subscription, by their guid.
+-- Pruned the same way as sent_posts.
+CREATE TABLE sent_feed_items (
+    subscription_id integer NOT NULL REFERENCES feed_subscriptions (id) ON DELETE CASCADE,
+    guid text NOT NULL,
+    sent_at timestamptz NOT NULL DEFAULT now(),
+    last_seen_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (subscription_id, guid)
+);
+
+CREATE INDEX sent_feed_items_last_seen_at_idx ON sent_feed_items (last_seen_at);
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- The scheduler only loads the reminders and mutes that are due soon.
+CREATE INDEX reminders_date_idx ON reminders (date);
+CREATE INDEX muted_members_date_idx ON muted_members (date);
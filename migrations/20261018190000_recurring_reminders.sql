@@ -0,0 +1,4 @@
+-- Add migration script here
+-- How the reminder repeats, null for the ones that fire once.
+ALTER TABLE reminders ADD COLUMN recurrence text;
+CREATE INDEX reminders_user_id_idx ON reminders (user_id);
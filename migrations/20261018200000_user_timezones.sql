@@ -0,0 +1,5 @@
+-- Add migration script here
+CREATE TABLE user_timezones (
+    user_id bigint PRIMARY KEY NOT NULL,
+    timezone text NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- The reminders and unmutes that failed are retried a few times, with a backoff.
+ALTER TABLE reminders ADD COLUMN failed_attempts integer NOT NULL DEFAULT 0;
+ALTER TABLE reminders ADD COLUMN retry_at timestamptz;
+ALTER TABLE muted_members ADD COLUMN failed_attempts integer NOT NULL DEFAULT 0;
+ALTER TABLE muted_members ADD COLUMN retry_at timestamptz;
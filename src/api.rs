@@ -0,0 +1,588 @@
+//! JSON API to read and update the settings of a guild, served on the web server.
+//!
+//! Every request needs the `api_token` of the `web_server` section as a bearer token.
+//! The updates go through the same validation as the configuration commands.
+use crate::eventsub;
+use crate::global_data::{BooruCommands, DatabasePool, Tokens};
+use crate::utils::guild_settings::{
+    get_guild_settings, invalidate_guild_settings, resolve_disableable_command,
+    save_disabled_commands, save_prefix, set_anti_spam, set_mute_role, validate_prefix,
+};
+use crate::utils::logging::{set_logging_events, LoggingEvents};
+
+use std::{convert::Infallible, sync::Arc};
+
+use serenity::{
+    model::id::{GuildId, RoleId},
+    prelude::Context,
+};
+use warp::{
+    http::StatusCode,
+    reply::{json, Response},
+    Filter, Rejection, Reply,
+};
+
+// Limit of the size of a request body, settings are tiny.
+const MAX_BODY_SIZE: u64 = 16 * 1024;
+
+#[derive(Serialize)]
+struct ApiError {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct Subscription {
+    site: String,
+    target: String,
+    channel_id: Option<String>,
+    webhook: bool,
+}
+
+#[derive(Serialize)]
+struct GuildSettingsResponse {
+    prefix: String,
+    additional_prefixes: Vec<String>,
+    disabled_commands: Vec<String>,
+    logging: Option<u64>,
+    anti_spam: bool,
+    mute_role: Option<String>,
+    subscriptions: Vec<Subscription>,
+}
+
+/// The settings to change, the missing ones are left as they are.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GuildSettingsUpdate {
+    prefix: Option<String>,
+    disabled_commands: Option<Vec<String>>,
+    logging: Option<u64>,
+    anti_spam: Option<bool>,
+    mute_role: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BooruSubscriptionQuery {
+    booru_url: String,
+    tags: String,
+}
+
+fn error_reply(status: StatusCode, message: impl Into<String>) -> Response {
+    warp::reply::with_status(
+        json(&ApiError {
+            error: message.into(),
+        }),
+        status,
+    )
+    .into_response()
+}
+
+// Compares every byte, so the time it takes doesn't tell how much of the token was right.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Checks the token of a request, and that the bot is on the guild.
+async fn authorize(
+    ctx: &Context,
+    guild_id: u64,
+    authorization: Option<String>,
+) -> Result<GuildId, Response> {
+    let token = {
+        let data_read = ctx.data.read().await;
+        let config = data_read.get::<Tokens>().unwrap();
+        config.web_server.as_ref().and_then(|w| w.api_token.clone())
+    };
+
+    let token = if let Some(x) = token {
+        x
+    } else {
+        return Err(error_reply(
+            StatusCode::NOT_FOUND,
+            "The settings API is not enabled.",
+        ));
+    };
+
+    let given = authorization.unwrap_or_default();
+    let given = given.strip_prefix("Bearer ").unwrap_or_default();
+
+    if !constant_time_eq(given.as_bytes(), token.as_bytes()) {
+        return Err(error_reply(StatusCode::UNAUTHORIZED, "Invalid token."));
+    }
+
+    let guild_id = GuildId(guild_id);
+
+    if ctx.cache.guild_field(guild_id, |g| g.id).is_none() {
+        return Err(error_reply(
+            StatusCode::NOT_FOUND,
+            "The bot is not on that guild.",
+        ));
+    }
+
+    Ok(guild_id)
+}
+
+// Obtains the channels and the bot webhooks of a guild, which is what subscriptions are stored by.
+async fn guild_targets(ctx: &Context, guild_id: GuildId) -> (Vec<i64>, Vec<String>) {
+    let channels = ctx
+        .cache
+        .guild_field(guild_id, |g| {
+            g.channels.keys().map(|c| c.0 as i64).collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let bot_id = ctx.cache.current_user_id();
+    let webhooks = match guild_id.webhooks(ctx).await {
+        Ok(x) => x
+            .into_iter()
+            .filter(|w| w.user.as_ref().map_or(false, |u| u.id == bot_id))
+            .filter_map(|w| w.url().ok())
+            .collect(),
+        Err(why) => {
+            warn!(
+                "Could not obtain the webhooks of guild {}: {}",
+                guild_id, why
+            );
+            Vec::new()
+        }
+    };
+
+    (channels, webhooks)
+}
+
+async fn guild_subscriptions(
+    ctx: &Context,
+    guild_id: GuildId,
+) -> Result<Vec<Subscription>, sqlx::Error> {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let (channels, webhooks) = guild_targets(ctx, guild_id).await;
+    let mut subscriptions = Vec::new();
+
+    let posts = sqlx::query!(
+        "SELECT booru_url, tags, channel_id, webhook FROM new_posts WHERE channel_id && $1 OR webhook && $2",
+        &channels,
+        &webhooks
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    for row in posts {
+        for channel_id in row.channel_id.unwrap_or_default() {
+            if channels.contains(&channel_id) {
+                subscriptions.push(Subscription {
+                    site: row.booru_url.to_string(),
+                    target: row.tags.to_string(),
+                    channel_id: Some(channel_id.to_string()),
+                    webhook: false,
+                });
+            }
+        }
+
+        for webhook in row.webhook.unwrap_or_default() {
+            if webhooks.contains(&webhook) {
+                subscriptions.push(Subscription {
+                    site: row.booru_url.to_string(),
+                    target: row.tags.to_string(),
+                    channel_id: None,
+                    webhook: true,
+                });
+            }
+        }
+    }
+
+    let streamers = sqlx::query!(
+        "SELECT streamer, channel_id FROM streamer_notification_channel WHERE channel_id = ANY($1)",
+        &channels
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    for row in streamers {
+        subscriptions.push(Subscription {
+            site: "twitch".to_string(),
+            target: row.streamer,
+            channel_id: row.channel_id.map(|c| c.to_string()),
+            webhook: false,
+        });
+    }
+
+    let streamers = sqlx::query!(
+        "SELECT streamer FROM streamer_notification_webhook WHERE webhook = ANY($1)",
+        &webhooks
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    for row in streamers {
+        subscriptions.push(Subscription {
+            site: "twitch".to_string(),
+            target: row.streamer,
+            channel_id: None,
+            webhook: true,
+        });
+    }
+
+    Ok(subscriptions)
+}
+
+async fn settings_response(ctx: &Context, guild_id: GuildId) -> Result<Response, sqlx::Error> {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let settings = get_guild_settings(ctx, guild_id).await;
+
+    let logging = sqlx::query!(
+        "SELECT bitwise FROM logging_channels WHERE guild_id = $1",
+        guild_id.0 as i64
+    )
+    .fetch_optional(&pool)
+    .await?;
+
+    let anti_spam = sqlx::query!(
+        "SELECT enabled FROM anti_spam WHERE guild_id = $1",
+        guild_id.0 as i64
+    )
+    .fetch_optional(&pool)
+    .await?;
+
+    let mute_role = sqlx::query!(
+        "SELECT role_id FROM muted_roles WHERE guild_id = $1",
+        guild_id.0 as i64
+    )
+    .fetch_optional(&pool)
+    .await?;
+
+    let response = GuildSettingsResponse {
+        prefix: settings.prefix(),
+        additional_prefixes: settings.additional_prefixes,
+        disabled_commands: settings.disallowed_commands,
+        logging: logging.map(|r| r.bitwise as u64),
+        anti_spam: anti_spam.map_or(false, |r| r.enabled),
+        mute_role: mute_role.map(|r| r.role_id.to_string()),
+        subscriptions: guild_subscriptions(ctx, guild_id).await?,
+    };
+
+    Ok(json(&response).into_response())
+}
+
+fn internal_error(why: sqlx::Error) -> Response {
+    error!("Settings API database error: {}", why);
+    error_reply(StatusCode::INTERNAL_SERVER_ERROR, "Internal error.")
+}
+
+async fn get_settings(
+    guild_id: u64,
+    authorization: Option<String>,
+    ctx: Arc<Context>,
+) -> Result<Response, Infallible> {
+    let guild_id = match authorize(&ctx, guild_id, authorization).await {
+        Ok(x) => x,
+        Err(response) => return Ok(response),
+    };
+
+    Ok(settings_response(&ctx, guild_id)
+        .await
+        .unwrap_or_else(internal_error))
+}
+
+async fn update_settings(
+    guild_id: u64,
+    authorization: Option<String>,
+    update: GuildSettingsUpdate,
+    ctx: Arc<Context>,
+) -> Result<Response, Infallible> {
+    let guild_id = match authorize(&ctx, guild_id, authorization).await {
+        Ok(x) => x,
+        Err(response) => return Ok(response),
+    };
+
+    let (pool, booru_commands) = {
+        let data_read = ctx.data.read().await;
+        let pool = data_read.get::<DatabasePool>().unwrap();
+        let booru_commands = data_read.get::<BooruCommands>().unwrap();
+
+        (pool.clone(), booru_commands.clone())
+    };
+
+    // Everything is validated before anything is changed, so a bad request changes nothing.
+    if let Some(prefix) = &update.prefix {
+        if let Err(why) = validate_prefix(prefix) {
+            return Ok(error_reply(StatusCode::BAD_REQUEST, why));
+        }
+    }
+
+    let disabled_commands = if let Some(commands) = &update.disabled_commands {
+        let mut resolved = Vec::new();
+
+        for command in commands {
+            match resolve_disableable_command(command, &booru_commands) {
+                Some(x) if !resolved.iter().any(|c| c == x) => resolved.push(x.to_string()),
+                Some(_) => (),
+                None => {
+                    return Ok(error_reply(
+                        StatusCode::BAD_REQUEST,
+                        format!("Command `{}` not found.", command),
+                    ))
+                }
+            }
+        }
+
+        Some(resolved)
+    } else {
+        None
+    };
+
+    let logging = if let Some(bits) = update.logging {
+        if let Some(x) = LoggingEvents::from_bits(bits) {
+            Some(x)
+        } else {
+            return Ok(error_reply(
+                StatusCode::BAD_REQUEST,
+                "The logging bitmask contains unknown events.",
+            ));
+        }
+    } else {
+        None
+    };
+
+    let mute_role = if let Some(role) = &update.mute_role {
+        let role_id = role.parse::<u64>().map(RoleId).ok();
+        let exists = role_id.and_then(|r| {
+            ctx.cache
+                .guild_field(guild_id, |g| g.roles.contains_key(&r))
+        });
+
+        if exists != Some(true) {
+            return Ok(error_reply(
+                StatusCode::BAD_REQUEST,
+                "The mute role doesn't exist on this guild.",
+            ));
+        }
+
+        role_id
+    } else {
+        None
+    };
+
+    // Everything is written on a single transaction, so a failure changes nothing.
+    // Returning before the commit rolls it back.
+    let result = async {
+        let mut tx = pool.begin().await?;
+
+        if let Some(events) = logging {
+            if !set_logging_events(&mut tx, guild_id, events).await? {
+                return Ok(Some(error_reply(
+                    StatusCode::CONFLICT,
+                    "This guild doesn't have a logging channel, configure one with `configure channel logging` first.",
+                )));
+            }
+        }
+
+        if let Some(prefix) = &update.prefix {
+            save_prefix(&mut tx, guild_id, prefix).await?;
+        }
+
+        if let Some(commands) = &disabled_commands {
+            save_disabled_commands(&mut tx, guild_id, commands).await?;
+        }
+
+        if let Some(enabled) = update.anti_spam {
+            set_anti_spam(&mut tx, guild_id, enabled).await?;
+        }
+
+        if let Some(role_id) = mute_role {
+            set_mute_role(&mut tx, guild_id, role_id).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok::<_, sqlx::Error>(None)
+    }
+    .await;
+
+    if let Ok(None) = result {
+        invalidate_guild_settings(&ctx, guild_id).await;
+    }
+
+    match result {
+        Ok(Some(response)) => Ok(response),
+        Ok(None) => Ok(settings_response(&ctx, guild_id)
+            .await
+            .unwrap_or_else(internal_error)),
+        Err(why) => Ok(internal_error(why)),
+    }
+}
+
+async fn remove_twitch_subscription(
+    guild_id: u64,
+    streamer: String,
+    authorization: Option<String>,
+    ctx: Arc<Context>,
+) -> Result<Response, Infallible> {
+    let guild_id = match authorize(&ctx, guild_id, authorization).await {
+        Ok(x) => x,
+        Err(response) => return Ok(response),
+    };
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let (channels, webhooks) = guild_targets(&ctx, guild_id).await;
+
+    let result = async {
+        let channel_result = sqlx::query!(
+            "DELETE FROM streamer_notification_channel WHERE streamer = $1 AND channel_id = ANY($2)",
+            &streamer,
+            &channels
+        )
+        .execute(&pool)
+        .await?;
+
+        let webhook_result = sqlx::query!(
+            "DELETE FROM streamer_notification_webhook WHERE streamer = $1 AND webhook = ANY($2)",
+            &streamer,
+            &webhooks
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok::<_, sqlx::Error>(channel_result.rows_affected() + webhook_result.rows_affected())
+    }
+    .await;
+
+    match result {
+        Ok(0) => Ok(error_reply(
+            StatusCode::NOT_FOUND,
+            "This guild is not subscribed to that streamer.",
+        )),
+        Ok(_) => {
+            eventsub::spawn_sync(&ctx);
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        Err(why) => Ok(internal_error(why)),
+    }
+}
+
+async fn remove_booru_subscription(
+    guild_id: u64,
+    query: BooruSubscriptionQuery,
+    authorization: Option<String>,
+    ctx: Arc<Context>,
+) -> Result<Response, Infallible> {
+    let guild_id = match authorize(&ctx, guild_id, authorization).await {
+        Ok(x) => x,
+        Err(response) => return Ok(response),
+    };
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let (channels, webhooks) = guild_targets(&ctx, guild_id).await;
+
+    let result = async {
+        let row = sqlx::query!(
+            "SELECT channel_id, webhook FROM new_posts WHERE booru_url = $1 AND tags = $2",
+            &query.booru_url,
+            &query.tags
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        let row = if let Some(x) = row {
+            x
+        } else {
+            return Ok(false);
+        };
+
+        let old_channels = row.channel_id.unwrap_or_default();
+        let old_webhooks = row.webhook.unwrap_or_default();
+
+        let new_channels = old_channels
+            .iter()
+            .filter(|c| !channels.contains(*c))
+            .copied()
+            .collect::<Vec<_>>();
+        let new_webhooks = old_webhooks
+            .iter()
+            .filter(|w| !webhooks.contains(*w))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if new_channels.len() == old_channels.len() && new_webhooks.len() == old_webhooks.len() {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            "UPDATE new_posts SET channel_id = $3, webhook = $4 WHERE booru_url = $1 AND tags = $2",
+            &query.booru_url,
+            &query.tags,
+            &new_channels,
+            &new_webhooks
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok::<_, sqlx::Error>(true)
+    }
+    .await;
+
+    match result {
+        Ok(false) => Ok(error_reply(
+            StatusCode::NOT_FOUND,
+            "This guild is not subscribed to those tags.",
+        )),
+        Ok(true) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(why) => Ok(internal_error(why)),
+    }
+}
+
+/// The routes of the settings API.
+pub fn routes(ctx: Arc<Context>) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    let with_ctx = warp::any().map(move || ctx.clone());
+    let authorization = warp::header::optional::<String>("authorization");
+
+    let get_settings_route = warp::path!("api" / "guilds" / u64 / "settings")
+        .and(warp::get())
+        .and(authorization)
+        .and(with_ctx.clone())
+        .and_then(get_settings);
+
+    let update_settings_route = warp::path!("api" / "guilds" / u64 / "settings")
+        .and(warp::patch())
+        .and(authorization)
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_ctx.clone())
+        .and_then(update_settings);
+
+    let remove_twitch_route =
+        warp::path!("api" / "guilds" / u64 / "subscriptions" / "twitch" / String)
+            .and(warp::delete())
+            .and(authorization)
+            .and(with_ctx.clone())
+            .and_then(remove_twitch_subscription);
+
+    let remove_booru_route = warp::path!("api" / "guilds" / u64 / "subscriptions" / "boorus")
+        .and(warp::delete())
+        .and(warp::query::<BooruSubscriptionQuery>())
+        .and(authorization)
+        .and(with_ctx)
+        .and_then(remove_booru_subscription);
+
+    get_settings_route
+        .or(update_settings_route)
+        .unify()
+        .or(remove_twitch_route)
+        .unify()
+        .or(remove_booru_route)
+        .unify()
+}
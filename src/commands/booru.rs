@@ -5,11 +5,7 @@ use crate::{
     commands::sankaku::{chan, idol},
     global_data::*,
     // import the utils::booru module for all the argument and tags blacklisting.
-    utils::{
-        basic_functions::capitalize_first,
-        booru,
-        booru::{SAFE_BANLIST, UNSAFE_BANLIST},
-    },
+    utils::{basic_functions::capitalize_first, booru},
     Booru,
 };
 
@@ -28,9 +24,7 @@ use rand::Rng;
 
 // reqwest is a crate used to do http requests.
 // used to request posts matching the specified tags on the selected site.
-use reqwest::{header::*, Client as ReqwestClient, Url as ReqUrl};
-// quick_xml is an xml sedrde library.
-// used to deserialize the xml data into structs.
+use reqwest::{Client as ReqwestClient, Url as ReqUrl};
 // serde or SerializerDeserializer, is a library to srialize data structures into structs.
 use serde::{Deserialize, Serialize};
 use serde_aux::prelude::*;
@@ -76,66 +70,6 @@ struct NHentaiSearch {
     result: Vec<NHentaiSearchResult>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-struct ScoreData {
-    total: i64,
-}
-
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-struct Url {
-    url: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-struct Tags {
-    general: Option<Vec<Option<String>>>,
-}
-
-// defining the Post type to be used for the xml deserialized on the Posts vector.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-struct Post {
-    #[serde(deserialize_with = "deserialize_number_from_string")]
-    id: u64,
-    score: Option<String>,
-    actual_score: Option<String>,
-    source: Option<String>,
-    sources: Option<Vec<String>>,
-    rating: Option<String>,
-    sample_url: Option<String>,
-    file_url: Option<String>,
-    sample: Option<Url>,
-    file: Option<Url>,
-    tags: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-struct PostE621 {
-    #[serde(deserialize_with = "deserialize_number_from_string")]
-    id: u64,
-    score: Option<ScoreData>,
-    actual_score: Option<String>,
-    source: Option<String>,
-    sources: Option<Vec<String>>,
-    rating: Option<String>,
-    sample_url: Option<String>,
-    file_url: Option<String>,
-    sample: Option<Url>,
-    file: Option<Url>,
-    tags: Option<Tags>,
-    string_tags: Option<String>,
-}
-
-// defining the Posts vector to Deserialize the requested xml list.
-#[derive(Debug, Deserialize, PartialEq, Clone)]
-struct Posts {
-    post: Vec<Post>,
-}
-
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-struct PostsE621 {
-    posts: Vec<PostE621>,
-}
-
 // Function to get the booru data and send it.
 pub async fn get_booru(
     ctx: &Context,
@@ -160,167 +94,31 @@ pub async fn get_booru(
         }
     };
 
-    // TODO: replace this with Url::parse_with_params
-    let mut tags = raw_tags
-        .iter()
-        .map(|x| format!("{}+", x))
-        .collect::<String>();
-    tags.pop();
-
     let reqwest = ReqwestClient::new();
+    let headers = booru::request_headers(&format!("http://{}/", booru.url));
 
-    let page: usize = 0;
-
-    let url = if booru.typ == 1 {
-        format!(
-            "https://{}/index.php?page=dapi&s=post&q=index&tags={}&pid={}&limit=50",
-            booru.url, tags, page
-        )
-    } else if booru.typ == 2 {
-        format!(
-            "https://{}/post/index.xml?tags={}&page={}&limit=50",
-            booru.url, tags, page
-        )
-    } else if booru.typ == 3 {
-        format!(
-            "http://{}/post/index.xml?tags={}&page={}&limit=50",
-            booru.url, tags, page
-        )
-    } else if booru.typ == 4 {
-        format!(
-            "http://{}/posts.json?tags={}&page={}&limit=50",
-            booru.url, tags, page
-        )
-    } else {
-        "https://safebooru.org/index.php?page=dapi&s=post&q=index".to_string()
-    };
+    let posts = booru::fetch_posts(booru, &raw_tags.join(" ")).await?;
 
-    let mut headers = HeaderMap::new();
-
-    headers.insert(
-        ACCEPT,
-        "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8"
-            .parse()
-            .unwrap(),
-    );
-    headers.insert(
-        USER_AGENT,
-        "Mozilla/5.0 (X11; Linux x86_64; rv:99.0) Gecko/20100101 Firefox/99.0"
-            .parse()
-            .unwrap(),
-    );
-    headers.insert("Referer", url.parse().unwrap());
-
-    // Send a request with the parsed url, and return the output text.
-    let resp = reqwest
-        .get(&url)
-        .headers(headers.clone())
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    // deserialize the request XML into the Posts struct.
-    let xml = if booru.typ == 4 {
-        let mut posts: PostsE621 = serde_json::from_str(resp.as_str())?;
-        for post in posts.posts.iter_mut() {
-            post.actual_score = Some(if let Some(score_data) = post.score.clone() {
-                score_data.total.to_string()
-            } else {
-                "0".to_string()
-            });
-            post.score = None;
-            post.source = if let Some(sources) = post.sources.clone() {
-                if !sources.is_empty() {
-                    Some(sources[0].clone())
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-            post.sample_url = Some(post.sample.clone().unwrap().url.unwrap_or_default());
-            post.file_url = Some(post.file.clone().unwrap().url.unwrap_or_default());
-            post.string_tags = Some({
-                if let Some(t) = post.tags.clone() {
-                    t.general
-                        .unwrap_or_else(|| vec![Some("gore".to_string())])
-                        .iter()
-                        .map(|tag| format!("{} ", tag.as_ref().unwrap_or(&"gore".to_string())))
-                        .collect::<String>()
-                } else {
-                    "gore".to_string()
-                }
-            });
-        }
-
-        let mut new_raw_posts = serde_json::to_string(&posts)?;
-        new_raw_posts = new_raw_posts.replace("\"posts\"", "\"post\"");
-        new_raw_posts = new_raw_posts.replace("\"score\"", "\"__\"");
-        new_raw_posts = new_raw_posts.replace("\"tags\"", "\"___\"");
-        new_raw_posts = new_raw_posts.replace("\"string_tags\"", "\"tags\"");
-        new_raw_posts = new_raw_posts.replace("\"actual_score\"", "\"score\"");
-        let new_posts: Posts = serde_json::from_str(&new_raw_posts)?;
-
-        new_posts
-    } else {
-        let posts_result = quick_xml::de::from_str::<Posts>(resp.as_str());
-        if let Err(why) = posts_result {
-            error!("Error with booru XML deser: {}", why);
-            msg.reply(ctx, "There are no posts containing the requested tags.")
-                .await?;
-            return Ok(());
-        }
-
-        let mut posts = posts_result.unwrap();
-        for ref mut post in posts.post.iter_mut() {
-            post.actual_score = post.score.clone();
-        }
-        posts
-    };
+    if posts.is_empty() {
+        msg.reply(ctx, "There are no posts containing the requested tags.")
+            .await?;
+        return Ok(());
+    }
 
     // gets a random post from the vector.
     let choice;
     {
         let mut y = 1;
         loop {
-            let r = rand::thread_rng().gen_range(0..xml.post.len());
-            let x = xml.post[r].clone();
-            let mut is_unsafe = false;
+            let r = rand::thread_rng().gen_range(0..posts.len());
+            let x = posts[r].clone();
             y += 1;
 
-            if channel.is_nsfw() || dm_channel {
-                for tag in x
-                    .clone()
-                    .tags
-                    .unwrap_or_else(|| "gore".to_string())
-                    .split(' ')
-                    .into_iter()
-                {
-                    if UNSAFE_BANLIST.contains(&tag) {
-                        is_unsafe = true;
-                    }
-                }
-            } else {
-                for tag in x
-                    .clone()
-                    .tags
-                    .unwrap_or_else(|| "gore".to_string())
-                    .split(' ')
-                    .into_iter()
-                {
-                    if SAFE_BANLIST.contains(&tag)
-                        || x.clone().rating.unwrap_or_else(|| "x".to_string()) != "s"
-                    {
-                        is_unsafe = true;
-                    }
-                }
-            }
-            if !is_unsafe {
+            if x.is_allowed(channel.is_nsfw() || dm_channel) {
                 choice = x;
                 break;
             }
-            if y > (&resp.len() * 2) {
+            if y > (posts.len() * 2) {
                 msg.channel_id.say(ctx, "All the content matching the requested tags is either too large, unsafe or illegal to be sent.").await?;
                 return Ok(());
             }
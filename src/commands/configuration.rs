@@ -1,6 +1,19 @@
 use crate::{
-    global_data::*, notifications::Post, utils::booru, utils::checks::*,
-    utils::logging::LoggingEvents, MASTER_GROUP,
+    eventsub,
+    global_data::*,
+    utils::aliases::{format_aliases, MAX_ALIASES_PER_GUILD, MAX_ALIAS_ARGUMENTS_LENGTH},
+    utils::booru,
+    utils::checks::*,
+    utils::command_rules::{is_command_name, resolve_rule_target},
+    utils::feeds::{self, fetch_feed, validate_feed_url, MAX_FEEDS_PER_GUILD, MAX_FILTER_LENGTH},
+    utils::guild_settings::{
+        get_guild_settings, invalidate_guild_settings, resolve_disableable_command, set_anti_spam,
+        set_disabled_commands, set_mute_role, set_prefix, validate_prefix, MAX_ADDITIONAL_PREFIXES,
+    },
+    utils::logging::LoggingEvents,
+    utils::sent_posts::mark_as_sent,
+    utils::tags::{validate_custom_command_name, MAX_TAGS_PER_GUILD, MAX_TAG_CONTENT_LENGTH},
+    utils::time_parser,
 };
 
 use std::time::Duration;
@@ -8,8 +21,7 @@ use std::time::Duration;
 use futures::stream::StreamExt;
 use futures::TryStreamExt;
 
-use reqwest::Url;
-
+use chrono_tz::Tz;
 use regex::Regex;
 
 use serenity::{
@@ -127,9 +139,11 @@ async fn set_best_tags(
 /// `best_boy`: Sets your best boy to the given tags.
 /// `booru`: Sets the booru to be used for the best_X commands ~~and `picture`~~
 /// `streamrole`: Gives you the configured streamrole of a streamer the guild gets notifications on.
+/// `prefix`: Sets a personal prefix that works everywhere, along with the prefixes of the guild.
+/// `timezone`: Sets the time zone of the dates and times you give to the commands.
 #[command]
 #[aliases("self", "me")]
-#[sub_commands(best_boy, best_girl, booru, streamrole)]
+#[sub_commands(best_boy, best_girl, booru, streamrole, user_prefix, user_timezone)]
 async fn user(_ctx: &Context, _msg: &Message, _args: Args) -> CommandResult {
     Ok(())
 }
@@ -294,6 +308,137 @@ async fn booru(ctx: &Context, msg: &Message, raw_args: Args) -> CommandResult {
     Ok(())
 }
 
+/// Sets your personal prefix, which works on every guild and on DMs.
+/// Run it without a prefix to remove it.
+///
+/// Usage:
+/// `config user prefix a!`
+/// `config user prefix`
+#[command("prefix")]
+async fn user_prefix(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (pool, user_prefixes) = {
+        let data_read = ctx.data.read().await;
+        let pool = data_read.get::<DatabasePool>().unwrap();
+        let user_prefixes = data_read.get::<UserPrefixes>().unwrap();
+
+        (pool.clone(), user_prefixes.clone())
+    };
+
+    let user_id = msg.author.id.0 as i64;
+
+    if let Ok(prefix) = args.single_quoted::<String>() {
+        if let Err(why) = validate_prefix(&prefix) {
+            msg.reply(ctx, why).await?;
+            return Ok(());
+        }
+
+        sqlx::query!(
+            "INSERT INTO user_prefixes (user_id, prefix) VALUES ($1, $2) ON CONFLICT (user_id) DO UPDATE SET prefix = $2",
+            user_id,
+            &prefix
+        )
+        .execute(&pool)
+        .await?;
+
+        user_prefixes
+            .write()
+            .await
+            .insert(msg.author.id.0, prefix.to_string());
+
+        let content_safe_options = ContentSafeOptions::default();
+        let bad_success_message = format!("Successfully set your personal prefix to `{}`", prefix);
+        let success_message = content_safe(ctx, bad_success_message, &content_safe_options, &[]);
+        msg.reply(ctx, success_message).await?;
+    } else {
+        sqlx::query!("DELETE FROM user_prefixes WHERE user_id = $1", user_id)
+            .execute(&pool)
+            .await?;
+
+        user_prefixes.write().await.remove(&msg.author.id.0);
+
+        msg.reply(ctx, "Successfully removed your personal prefix.")
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Sets your time zone, used for the dates and times given to `remind_me`, `reminders every`
+/// and `temporal_mute`. It takes the names of the tz database, like `Europe/Madrid`.
+/// Run it without a time zone to see yours, or with `reset` to go back to UTC.
+///
+/// Usage:
+/// `config user timezone America/New_York`
+/// `config user timezone`
+/// `config user timezone reset`
+#[command("timezone")]
+#[aliases(tz, time_zone)]
+async fn user_timezone(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let user_id = msg.author.id.0 as i64;
+
+    let timezone = if let Ok(x) = args.single::<String>() {
+        x
+    } else {
+        let tz = time_parser::user_timezone(&pool, msg.author.id.0).await?;
+        let now = chrono::Utc::now().with_timezone(&tz);
+
+        msg.reply(
+            ctx,
+            format!(
+                "Your time zone is `{}`, where it's {}.",
+                tz.name(),
+                now.format("%Y-%m-%d %H:%M")
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if ["reset", "utc"].contains(&timezone.to_lowercase().as_str()) {
+        sqlx::query!("DELETE FROM user_timezones WHERE user_id = $1", user_id)
+            .execute(&pool)
+            .await?;
+
+        msg.reply(ctx, "Successfully reset your time zone to `UTC`.")
+            .await?;
+        return Ok(());
+    }
+
+    let tz = if let Ok(x) = timezone.parse::<Tz>() {
+        x
+    } else {
+        msg.reply(ctx, "That's not a valid time zone, use a name from the tz database like `Europe/Madrid` or `America/New_York`.\n<https://en.wikipedia.org/wiki/List_of_tz_database_time_zones>").await?;
+        return Ok(());
+    };
+
+    sqlx::query!(
+        "INSERT INTO user_timezones (user_id, timezone) VALUES ($1, $2) ON CONFLICT (user_id) DO UPDATE SET timezone = $2",
+        user_id,
+        tz.name()
+    )
+    .execute(&pool)
+    .await?;
+
+    let now = chrono::Utc::now().with_timezone(&tz);
+
+    msg.reply(
+        ctx,
+        format!(
+            "Successfully set your time zone to `{}`, where it's {}.",
+            tz.name(),
+            now.format("%Y-%m-%d %H:%M")
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
 /// Configures the bot for the channel it was invoked on.
 ///
 /// Configurable aspects:
@@ -327,7 +472,7 @@ async fn notifications(ctx: &Context, message: &Message, _args: Args) -> Command
 
     let mut is_hook = true;
     let mut is_create = true;
-    let mut site = "yandere";
+    let mut site = "booru";
 
     if let Some(reaction) = message
         .author
@@ -371,7 +516,7 @@ async fn notifications(ctx: &Context, message: &Message, _args: Args) -> Command
                 m.embed(|e| {
                     e.title("Select the number of option that you want");
                     e.description(
-                        "Select the site to configure notifications on:\n\n1: YandeRe\n2: Twitch",
+                        "Select the site to configure notifications on:\n\n1: Boorus\n2: Twitch",
                     )
                 })
             })
@@ -387,7 +532,7 @@ async fn notifications(ctx: &Context, message: &Message, _args: Args) -> Command
                 let emoji = &reaction.as_inner_ref().emoji;
 
                 match emoji.as_data().as_str() {
-                    "1\u{fe0f}\u{20e3}" => site = "yandere",
+                    "1\u{fe0f}\u{20e3}" => site = "booru",
                     "2\u{fe0f}\u{20e3}" => site = "twitch",
                     _ => (),
                 }
@@ -405,8 +550,8 @@ async fn notifications(ctx: &Context, message: &Message, _args: Args) -> Command
     }
 
     match site {
-        "yandere" => {
-            configure_yandere(ctx, &mut msg, message, is_create, is_hook).await?;
+        "booru" => {
+            configure_booru(ctx, &mut msg, message, is_create, is_hook).await?;
         }
         "twitch" => {
             configure_twitch(ctx, &mut msg, message, is_create, is_hook).await?;
@@ -438,12 +583,24 @@ trait Hook {
 }
 
 #[derive(Debug, Default)]
-struct YandeRe {
+struct BooruSubscription {
+    booru_url: String,
     tags: String,
     hook: Option<Webhook>,
 }
 
-impl Hook for YandeRe {
+impl Hook for BooruSubscription {
+    fn swap_hook(&mut self, data: Webhook) {
+        self.hook = Some(data);
+    }
+}
+
+#[derive(Debug, Default)]
+struct FeedSubscription {
+    hook: Option<Webhook>,
+}
+
+impl Hook for FeedSubscription {
     fn swap_hook(&mut self, data: Webhook) {
         self.hook = Some(data);
     }
@@ -482,27 +639,30 @@ async fn check_hook(ctx: &Context, msg: &Message, embed: &mut impl Hook) {
     }
 }
 
-async fn configure_yandere(
+async fn configure_booru(
     ctx: &Context,
     msg: &mut Message,
     og_message: &Message,
     is_create: bool,
     is_hook: bool,
 ) -> CommandResult {
-    let pool = {
+    let (pool, boorus) = {
         let data_read = ctx.data.read().await;
-        data_read.get::<DatabasePool>().unwrap().clone()
+        (
+            data_read.get::<DatabasePool>().unwrap().clone(),
+            data_read.get::<BooruList>().unwrap().clone(),
+        )
     };
 
-    let mut config = YandeRe::default();
+    let mut config = BooruSubscription::default();
     let author = &og_message.author;
 
     if is_create {
         msg.edit(ctx, |m| {
             m.content(format!("<@{}>", author.id));
             m.embed(|e| {
-                e.title("Say the tags you would like to get notified about");
-                e.description("This supports the same flags as the `yandere` command.\nEx: `uruha_rushia -x yuri`")
+                e.title("Say the booru and the tags you would like to get notified about");
+                e.description("This supports the same flags as the booru commands.\nEx: `yandere uruha_rushia -x yuri`")
             })
         }).await?;
 
@@ -511,7 +671,24 @@ async fn configure_yandere(
             .timeout(Duration::from_secs(120))
             .await
         {
-            let args = Args::new(&reply.content, &[Delimiter::Single(' ')]);
+            let mut args = Args::new(&reply.content, &[Delimiter::Single(' ')]);
+            let booru_name = args.single::<String>().unwrap_or_default().to_lowercase();
+
+            // Behoimi images can't be embedded, they need to be uploaded with the right referer.
+            let booru = if let Some(x) = boorus
+                .iter()
+                .find(|b| b.names.contains(&booru_name) && b.typ != 3)
+            {
+                x
+            } else {
+                og_message
+                    .reply(ctx, "That booru is not available for notifications.")
+                    .await?;
+                timeout(ctx, msg, og_message).await?;
+                return Ok(());
+            };
+
+            let args = Args::new(args.rest(), &[Delimiter::Single(' ')]);
 
             let channel = ctx.http.get_channel(msg.channel_id.0).await?;
 
@@ -533,16 +710,24 @@ async fn configure_yandere(
 
             tags.sort();
             config.tags = tags.join(" ");
+            config.booru_url = booru.url.to_string();
 
             msg.edit(ctx, |m| {
                 m.content(format!("<@{}>", author.id));
                 m.embed(|e| {
                     e.title("You selected the following tags");
-                    e.description(format!("`{}`", config.tags))
+                    e.description(format!("`{}` on `{}`", config.tags, config.booru_url))
                 })
             })
             .await?;
 
+            // The posts that already exist are marked as sent, so only the new ones get notified.
+            let post_hashes = booru::fetch_posts(booru, &config.tags)
+                .await?
+                .iter()
+                .map(|post| post.key())
+                .collect::<Vec<String>>();
+
             if is_hook {
                 check_hook(ctx, msg, &mut config).await;
                 if config.hook.is_none() {
@@ -560,7 +745,8 @@ async fn configure_yandere(
                     );
 
                     let query = sqlx::query!(
-                        "SELECT webhook FROM new_posts WHERE booru_url = 'yande.re' AND tags = $1",
+                        "SELECT webhook FROM new_posts WHERE booru_url = $1 AND tags = $2",
+                        &config.booru_url,
                         &config.tags
                     )
                     .fetch_optional(&pool)
@@ -571,28 +757,22 @@ async fn configure_yandere(
                         hooks.push(hook_url);
                         hooks.dedup();
 
-                        sqlx::query!("UPDATE new_posts SET webhook = $2 WHERE booru_url = 'yande.re' AND tags = $1", &config.tags, &hooks)
-                            .execute(&pool)
-                            .await?;
+                        sqlx::query!(
+                            "UPDATE new_posts SET webhook = $3 WHERE booru_url = $1 AND tags = $2",
+                            &config.booru_url,
+                            &config.tags,
+                            &hooks
+                        )
+                        .execute(&pool)
+                        .await?;
                     } else {
                         let hooks = vec![hook_url];
 
-                        let md5s = {
-                            let url = Url::parse_with_params(
-                                "https://yande.re/post/index.json",
-                                &[("tags", &config.tags), ("limit", &"100".to_string())],
-                            )?;
-
-                            let resp = reqwest::get(url).await?.json::<Vec<Post>>().await?;
-
-                            resp.iter()
-                                .map(|post| post.md5.clone())
-                                .collect::<Vec<String>>()
-                        };
-
-                        sqlx::query!("INSERT INTO new_posts (booru_url, tags, webhook, sent_md5) VALUES ('yande.re', $1, $2, $3)", &config.tags, &hooks, &md5s)
-                            .execute(&pool)
+                        let row = sqlx::query!("INSERT INTO new_posts (booru_url, tags, webhook) VALUES ($1, $2, $3) RETURNING id", &config.booru_url, &config.tags, &hooks)
+                            .fetch_one(&pool)
                             .await?;
+
+                        mark_as_sent(&pool, row.id, &post_hashes).await?;
                     }
                 } else {
                     og_message.reply(ctx, "There was an error obtaining a webhook. Make sure i have the permission to manage webhooks.").await?;
@@ -601,7 +781,8 @@ async fn configure_yandere(
                 }
             } else {
                 let query = sqlx::query!(
-                    "SELECT channel_id FROM new_posts WHERE booru_url = 'yande.re' AND tags = $1",
+                    "SELECT channel_id FROM new_posts WHERE booru_url = $1 AND tags = $2",
+                    &config.booru_url,
                     &config.tags
                 )
                 .fetch_optional(&pool)
@@ -612,28 +793,22 @@ async fn configure_yandere(
                     channels.push(msg.channel_id.0 as i64);
                     channels.dedup();
 
-                    sqlx::query!("UPDATE new_posts SET channel_id = $2 WHERE booru_url = 'yande.re' AND tags = $1", &config.tags, &channels)
-                        .execute(&pool)
-                        .await?;
+                    sqlx::query!(
+                        "UPDATE new_posts SET channel_id = $3 WHERE booru_url = $1 AND tags = $2",
+                        &config.booru_url,
+                        &config.tags,
+                        &channels
+                    )
+                    .execute(&pool)
+                    .await?;
                 } else {
                     let channels = vec![msg.channel_id.0 as i64];
 
-                    let md5s = {
-                        let url = Url::parse_with_params(
-                            "https://yande.re/post/index.json",
-                            &[("tags", &config.tags), ("limit", &"100".to_string())],
-                        )?;
-
-                        let resp = reqwest::get(url).await?.json::<Vec<Post>>().await?;
-
-                        resp.iter()
-                            .map(|post| post.md5.clone())
-                            .collect::<Vec<String>>()
-                    };
-
-                    sqlx::query!("INSERT INTO new_posts (booru_url, tags, channel_id, sent_md5) VALUES ('yande.re', $1, $2, $3)", &config.tags, &channels, &md5s)
-                        .execute(&pool)
+                    let row = sqlx::query!("INSERT INTO new_posts (booru_url, tags, channel_id) VALUES ($1, $2, $3) RETURNING id", &config.booru_url, &config.tags, &channels)
+                        .fetch_one(&pool)
                         .await?;
+
+                    mark_as_sent(&pool, row.id, &post_hashes).await?;
                 }
             }
         } else {
@@ -966,6 +1141,9 @@ async fn configure_twitch(
         .await?;
     }
 
+    // The streamers that got their first or lost their last notification get (un)subscribed.
+    eventsub::spawn_sync(ctx);
+
     Ok(())
 }
 
@@ -1051,15 +1229,33 @@ async fn toggle_annoy(ctx: &Context, msg: &Message, _args: Args) -> CommandResul
 ///
 /// Configurable aspects:
 /// `prefix`: Changes the bot prefix.
+/// `add_prefix`: Adds an additional prefix the bot will respond to.
+/// `remove_prefix`: Removes an additional prefix.
 /// `mute_role`: Sets the mute role of the server.
 /// `disable_command`: Disables a command.
 /// `enable_command`: Enables a disabled command.
 /// `toggle_anti_spam`: Enables or Disables antispam.
+/// `permissions`: Lists, allows or denies commands per channel and role.
+/// `tags`: Lists, adds, edits or removes the custom text commands.
+/// `aliases`: Lists, adds or removes the custom command aliases.
+/// `feeds`: Lists, adds or removes the RSS and Atom feed subscriptions.
 #[command]
 #[required_permissions(MANAGE_GUILD)]
 #[only_in("guilds")]
 #[aliases(server)]
-#[sub_commands(prefix, mute_role, disable_command, enable_command, toggle_anti_spam)]
+#[sub_commands(
+    prefix,
+    add_prefix,
+    remove_prefix,
+    mute_role,
+    disable_command,
+    enable_command,
+    toggle_anti_spam,
+    permissions,
+    tags,
+    aliases,
+    feeds
+)]
 async fn guild(_ctx: &Context, _msg: &Message, _args: Args) -> CommandResult {
     Ok(())
 }
@@ -1086,11 +1282,7 @@ async fn mute_role(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
         data_read.get::<DatabasePool>().unwrap().clone()
     };
 
-    sqlx::query!("INSERT INTO muted_roles (guild_id, role_id) VALUES ($1, $2) ON CONFLICT (guild_id) DO UPDATE SET role_id = $2",
-                  msg.guild_id.unwrap().0 as i64,
-                  role.0 as i64)
-        .execute(&pool)
-        .await?;
+    set_mute_role(&pool, msg.guild_id.unwrap(), role).await?;
 
     msg.react(ctx, '👍').await?;
 
@@ -1100,47 +1292,124 @@ async fn mute_role(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
 #[command]
 #[min_args(1)]
 async fn prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    if args.is_empty() {
-        msg.reply(ctx, "Invalid prefix was given").await?;
+    let prefix = args.message();
+
+    if let Err(why) = validate_prefix(prefix) {
+        msg.reply(ctx, why).await?;
+        return Ok(());
+    }
+
+    set_prefix(ctx, msg.guild_id.unwrap(), prefix).await?;
+
+    let content_safe_options = ContentSafeOptions::default();
+    let bad_success_message = format!("Successfully changed your prefix to `{}`", prefix);
+    let success_message = content_safe(ctx, bad_success_message, &content_safe_options, &[]);
+    msg.reply(ctx, success_message).await?;
+    Ok(())
+}
+
+/// Adds an additional prefix to this guild, so the bot responds to more than one prefix.
+/// The main prefix, configured with `config guild prefix`, keeps working.
+///
+/// Usage: `config guild add_prefix a!`
+#[command]
+#[min_args(1)]
+#[aliases(addprefix)]
+async fn add_prefix(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let prefix = args.single_quoted::<String>()?;
+    let guild_id = msg.guild_id.unwrap();
+
+    if let Err(why) = validate_prefix(&prefix) {
+        msg.reply(ctx, why).await?;
+        return Ok(());
+    }
+
+    let settings = get_guild_settings(ctx, guild_id).await;
+
+    if settings.prefixes().contains(&prefix) {
+        msg.reply(ctx, "That prefix is already in use.").await?;
+        return Ok(());
+    }
+
+    if settings.additional_prefixes.len() >= MAX_ADDITIONAL_PREFIXES {
+        msg.reply(
+            ctx,
+            format!(
+                "A guild can't have more than {} additional prefixes.",
+                MAX_ADDITIONAL_PREFIXES
+            ),
+        )
+        .await?;
         return Ok(());
     }
-    let prefix = args.message();
 
-    // change prefix on db
+    let mut additional_prefixes = settings.additional_prefixes;
+    additional_prefixes.push(prefix.to_string());
+
     let pool = {
         let data_read = ctx.data.read().await;
         data_read.get::<DatabasePool>().unwrap().clone()
     };
 
-    let guild_id = msg.guild_id.unwrap().0 as i64;
+    sqlx::query!(
+        "INSERT INTO prefixes (guild_id, additional_prefixes) VALUES ($1, $2) ON CONFLICT (guild_id) DO UPDATE SET additional_prefixes = $2",
+        guild_id.0 as i64,
+        &additional_prefixes
+    )
+    .execute(&pool)
+    .await?;
 
-    let data = sqlx::query!("SELECT prefix FROM prefixes WHERE guild_id = $1", guild_id)
-        .fetch_optional(&pool)
-        .boxed()
-        .await?;
+    invalidate_guild_settings(ctx, guild_id).await;
 
-    if data.is_none() {
-        sqlx::query!(
-            "INSERT INTO prefixes (guild_id, prefix) VALUES ($1, $2)",
-            guild_id,
-            &prefix
-        )
-        .execute(&pool)
-        .await?;
+    let content_safe_options = ContentSafeOptions::default();
+    let bad_success_message = format!("Successfully added the prefix `{}`", prefix);
+    let success_message = content_safe(ctx, bad_success_message, &content_safe_options, &[]);
+    msg.reply(ctx, success_message).await?;
+
+    Ok(())
+}
+
+/// Removes an additional prefix from this guild.
+///
+/// Usage: `config guild remove_prefix a!`
+#[command]
+#[min_args(1)]
+#[aliases(removeprefix, rm_prefix, del_prefix)]
+async fn remove_prefix(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let prefix = args.single_quoted::<String>()?;
+    let guild_id = msg.guild_id.unwrap();
+
+    let mut additional_prefixes = get_guild_settings(ctx, guild_id).await.additional_prefixes;
+
+    if let Some(pos) = additional_prefixes.iter().position(|p| p == &prefix) {
+        additional_prefixes.remove(pos);
     } else {
-        sqlx::query!(
-            "UPDATE prefixes SET prefix = $2 WHERE guild_id = $1",
-            guild_id,
-            &prefix
+        msg.reply(
+            ctx,
+            "That prefix is not an additional prefix of this guild.
+To change the main prefix, use `config guild prefix`",
         )
-        .execute(&pool)
         .await?;
+        return Ok(());
     }
 
-    let content_safe_options = ContentSafeOptions::default();
-    let bad_success_message = format!("Successfully changed your prefix to `{}`", prefix);
-    let success_message = content_safe(ctx, bad_success_message, &content_safe_options, &[]);
-    msg.reply(ctx, success_message).await?;
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    sqlx::query!(
+        "UPDATE prefixes SET additional_prefixes = $2 WHERE guild_id = $1",
+        guild_id.0 as i64,
+        &additional_prefixes
+    )
+    .execute(&pool)
+    .await?;
+
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    msg.react(ctx, '✅').await?;
+
     Ok(())
 }
 
@@ -1156,67 +1425,28 @@ async fn disable_command(ctx: &Context, msg: &Message, mut args: Args) -> Comman
         data_read.get::<BooruCommands>().unwrap().clone()
     };
 
-    let mut command_name = args.single_quoted::<String>()?;
-    if booru_commands.contains(&command_name) {
-        command_name = "picture".to_string();
-    }
-
-    for group in MASTER_GROUP.options.sub_groups {
-        for command in group.options.commands {
-            if command.options.names.contains(&command_name.as_str()) {
-                let pool = {
-                    let data_read = ctx.data.read().await;
-                    data_read.get::<DatabasePool>().unwrap().clone()
-                };
-
-                let command_name = command.options.names[0];
-
-                let disallowed_commands = sqlx::query!(
-                    "SELECT disallowed_commands FROM prefixes WHERE guild_id = $1",
-                    msg.guild_id.unwrap().0 as i64,
-                )
-                .fetch_optional(&pool)
-                .await?;
+    let command_name = args.single_quoted::<String>()?;
+    let command_name = if let Some(x) = resolve_disableable_command(&command_name, &booru_commands)
+    {
+        x
+    } else {
+        msg.reply(ctx, "Command not found.").await?;
+        return Ok(());
+    };
 
-                if let Some(x) = disallowed_commands {
-                    if let Some(mut disallowed_commands) = x.disallowed_commands {
-                        disallowed_commands.push(command_name.to_string());
-                        sqlx::query!(
-                            "UPDATE prefixes SET disallowed_commands = $1 WHERE guild_id = $2",
-                            &disallowed_commands,
-                            msg.guild_id.unwrap().0 as i64,
-                        )
-                        .execute(&pool)
-                        .await?;
-                    } else {
-                        let disallowed_commands = vec![command_name.to_string()];
-                        sqlx::query!(
-                            "UPDATE prefixes SET disallowed_commands = $1 WHERE guild_id = $2",
-                            &disallowed_commands,
-                            msg.guild_id.unwrap().0 as i64,
-                        )
-                        .execute(&pool)
-                        .await?;
-                    }
-                } else {
-                    let disallowed_commands = vec![command_name.to_string()];
-                    sqlx::query!("INSERT INTO prefixes (disallowed_commands, guild_id, prefix) VALUES ($1, $2, $3)",
-                        &disallowed_commands,
-                        msg.guild_id.unwrap().0 as i64,
-                        ".".to_string(),
-                    ).execute(&pool).await?;
-                }
+    let guild_id = msg.guild_id.unwrap();
+    let mut disallowed_commands = get_guild_settings(ctx, guild_id).await.disallowed_commands;
 
-                msg.reply(
-                    ctx,
-                    format!("Command `{}` successfully disabled.", command_name),
-                )
-                .await?;
-                return Ok(());
-            }
-        }
+    if !disallowed_commands.iter().any(|c| c == command_name) {
+        disallowed_commands.push(command_name.to_string());
+        set_disabled_commands(ctx, guild_id, &disallowed_commands).await?;
     }
-    msg.reply(ctx, "Command not found.").await?;
+
+    msg.reply(
+        ctx,
+        format!("Command `{}` successfully disabled.", command_name),
+    )
+    .await?;
 
     Ok(())
 }
@@ -1231,98 +1461,806 @@ async fn enable_command(ctx: &Context, msg: &Message, mut args: Args) -> Command
         data_read.get::<BooruCommands>().unwrap().clone()
     };
 
-    let mut command_name = args.single_quoted::<String>()?;
-    if booru_commands.contains(&command_name) {
-        command_name = "picture".to_string();
-    }
-
-    for group in MASTER_GROUP.options.sub_groups {
-        for command in group.options.commands {
-            if command.options.names.contains(&command_name.as_str()) {
-                let command_name = command.options.names[0];
-
-                let pool = {
-                    let data_read = ctx.data.read().await;
-                    data_read.get::<DatabasePool>().unwrap().clone()
-                };
+    let command_name = args.single_quoted::<String>()?;
+    let guild_id = msg.guild_id.unwrap();
+    let mut disallowed_commands = get_guild_settings(ctx, guild_id).await.disallowed_commands;
 
-                let disallowed_commands = sqlx::query!(
-                    "SELECT disallowed_commands FROM prefixes WHERE guild_id = $1",
-                    msg.guild_id.unwrap().0 as i64,
-                )
-                .fetch_optional(&pool)
-                .await?;
+    let position = resolve_disableable_command(&command_name, &booru_commands)
+        .and_then(|name| disallowed_commands.iter().position(|c| c == name));
 
-                if let Some(x) = disallowed_commands {
-                    if let Some(mut disallowed_commands) = x.disallowed_commands {
-                        if disallowed_commands.contains(&command_name.to_string()) {
-                            if let Some(pos) =
-                                disallowed_commands.iter().position(|x| x == command_name)
-                            {
-                                disallowed_commands.remove(pos);
-                            };
-
-                            sqlx::query!(
-                                "UPDATE prefixes SET disallowed_commands = $1 WHERE guild_id = $2",
-                                &disallowed_commands,
-                                msg.guild_id.unwrap().0 as i64,
-                            )
-                            .execute(&pool)
-                            .await?;
+    if let Some(position) = position {
+        let command_name = disallowed_commands.remove(position);
+        set_disabled_commands(ctx, guild_id, &disallowed_commands).await?;
 
-                            msg.reply(
-                                ctx,
-                                format!("Command `{}` successfully enabled.", command_name),
-                            )
-                            .await?;
-                            return Ok(());
-                        }
-                    }
-                }
-            }
-        }
+        msg.reply(
+            ctx,
+            format!("Command `{}` successfully enabled.", command_name),
+        )
+        .await?;
+    } else {
+        msg.reply(ctx, "Command not disabled.").await?;
     }
-    msg.reply(ctx, "Command not disabled.").await?;
 
     Ok(())
 }
 
-/// Toggles the Anti-Spam system on or off.
+/// Lists the rules that allow or deny commands on this guild.
 ///
-/// Currently it's a very simple "if more than 5 messages where sent in less than 5 second
-/// intervals between them, they get deleted"
+/// Rules can target a single command, or a whole group, like `music`, `image_boards` or `AllBoorus`.
+/// If a command has any allow rule, it can only be used where one of them matches.
+/// Deny rules always take priority over allow rules.
 ///
-/// In the future, this will be able to be configured in multiple ways.
+/// Subcommands:
+/// `allow`: Allows a command only on a channel and/or for a role.
+/// `deny`: Denies a command on a channel and/or for a role.
+/// `remove`: Removes a rule by it's id.
 #[command]
-#[aliases(toggleantispam, antispam, anti_spam, "toggle-anti-spam", "anti-spam")]
-async fn toggle_anti_spam(ctx: &Context, msg: &Message) -> CommandResult {
-    let pool = {
-        let data_read = ctx.data.read().await;
-        data_read.get::<DatabasePool>().unwrap().clone()
+#[aliases(perms, rules)]
+#[sub_commands(permissions_allow, permissions_deny, permissions_remove)]
+async fn permissions(ctx: &Context, msg: &Message) -> CommandResult {
+    let settings = get_guild_settings(ctx, msg.guild_id.unwrap()).await;
+
+    if settings.command_rules.is_empty() {
+        msg.reply(ctx, "This guild doesn't have any command rules.")
+            .await?;
+        return Ok(());
+    }
+
+    let rules = settings
+        .command_rules
+        .iter()
+        .map(|r| {
+            format!(
+                "`{}`: {} {} `{}`{}{}",
+                r.id,
+                if r.allow { "Allow" } else { "Deny" },
+                if r.is_group { "group" } else { "command" },
+                r.target,
+                r.channel_id
+                    .map(|c| format!(" in <#{}>", c))
+                    .unwrap_or_default(),
+                r.role_id
+                    .map(|r| format!(" for <@&{}>", r))
+                    .unwrap_or_default(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title("Command rules");
+                e.description(rules)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Allows a command or group only on a channel and/or for a role.
+///
+/// Usage:
+/// `config guild permissions allow music #music`
+/// `config guild permissions allow image_boards #nsfw @verified`
+#[command("allow")]
+#[min_args(2)]
+async fn permissions_allow(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    add_command_rule(ctx, msg, args, true).await
+}
+
+/// Denies a command or group on a channel and/or for a role.
+///
+/// Usage:
+/// `config guild permissions deny urban #general`
+/// `config guild permissions deny music @muted`
+#[command("deny")]
+#[min_args(2)]
+async fn permissions_deny(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    add_command_rule(ctx, msg, args, false).await
+}
+
+async fn add_command_rule(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+    allow: bool,
+) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let target = args.single_quoted::<String>()?;
+
+    let (target, is_group) = if let Some(x) = resolve_rule_target(&target) {
+        x
+    } else {
+        msg.reply(ctx, "Command or group not found.").await?;
+        return Ok(());
+    };
+
+    let mut channel_id = None;
+    let mut role_id = None;
+
+    for arg in args.iter::<String>().quoted() {
+        let arg = arg?;
+        let id = arg
+            .trim_start_matches("<#")
+            .trim_start_matches("<@&")
+            .trim_end_matches('>')
+            .parse::<i64>();
+
+        match id {
+            Ok(id) if arg.starts_with("<#") => channel_id = Some(id),
+            Ok(id) if arg.starts_with("<@&") => role_id = Some(id),
+            _ => {
+                msg.reply(
+                    ctx,
+                    format!(
+                        "`{}` is not a channel or role mention.",
+                        arg.replace('`', "")
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    }
+
+    if channel_id.is_none() && role_id.is_none() {
+        msg.reply(ctx, "Please, mention a channel, a role, or both.")
+            .await?;
+        return Ok(());
+    }
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let row = sqlx::query!(
+        "INSERT INTO command_rules (guild_id, target, is_group, channel_id, role_id, allow) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        guild_id.0 as i64,
+        &target,
+        is_group,
+        channel_id,
+        role_id,
+        allow
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    msg.reply(
+        ctx,
+        format!("Successfully added the rule with id `{}`", row.id),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a command rule by it's id.
+/// The ids can be seen with `config guild permissions`
+///
+/// Usage: `config guild permissions remove 3`
+#[command("remove")]
+#[aliases(delete, rm)]
+#[num_args(1)]
+async fn permissions_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<i32>()?;
+    let guild_id = msg.guild_id.unwrap();
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let result = sqlx::query!(
+        "DELETE FROM command_rules WHERE id = $1 AND guild_id = $2",
+        id,
+        guild_id.0 as i64
+    )
+    .execute(&pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        msg.reply(ctx, "There's no rule with that id on this guild.")
+            .await?;
+        return Ok(());
+    }
+
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    msg.react(ctx, '✅').await?;
+
+    Ok(())
+}
+
+/// Lists the custom text commands of this guild.
+/// Tags are used like any other command, and support the placeholders `{user}`, `{channel}` and `{args}`
+///
+/// `add`: Adds a tag.
+/// `edit`: Changes the content of a tag.
+/// `remove`: Removes a tag.
+#[command]
+#[aliases(tag, custom_commands)]
+#[sub_commands(tags_add, tags_edit, tags_remove)]
+async fn tags(ctx: &Context, msg: &Message) -> CommandResult {
+    let settings = get_guild_settings(ctx, msg.guild_id.unwrap()).await;
+
+    if settings.tags.is_empty() {
+        msg.reply(ctx, "This guild doesn't have any tags.").await?;
+        return Ok(());
+    }
+
+    let mut names = settings
+        .tags
+        .keys()
+        .map(|t| format!("`{}`", t))
+        .collect::<Vec<_>>();
+    names.sort();
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title("Tags");
+                e.description(names.join(", "))
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Adds a custom text command to this guild.
+///
+/// Usage:
+/// `config guild tags add hello Hello {user}!`
+/// `config guild tags add rules Please read the rules on #rules`
+#[command("add")]
+#[aliases(create)]
+#[min_args(2)]
+async fn tags_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let name = args.single_quoted::<String>()?.to_lowercase();
+    let content = args.rest().trim();
+
+    let booru_commands = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<BooruCommands>().unwrap().clone()
+    };
+
+    if let Err(why) = validate_custom_command_name(&name, &booru_commands) {
+        msg.reply(ctx, why).await?;
+        return Ok(());
+    }
+
+    if content.chars().count() > MAX_TAG_CONTENT_LENGTH {
+        msg.reply(
+            ctx,
+            format!(
+                "The content of a tag can't be longer than {} characters.",
+                MAX_TAG_CONTENT_LENGTH
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let settings = get_guild_settings(ctx, guild_id).await;
+
+    if settings.tags.contains_key(&name) {
+        msg.reply(
+            ctx,
+            "That tag already exists, use `config guild tags edit` to change it.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if settings.aliases.contains_key(&name) {
+        msg.reply(ctx, "There's already an alias with that name.")
+            .await?;
+        return Ok(());
+    }
+
+    if settings.tags.len() >= MAX_TAGS_PER_GUILD {
+        msg.reply(
+            ctx,
+            format!("A guild can't have more than {} tags.", MAX_TAGS_PER_GUILD),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    sqlx::query!(
+        "INSERT INTO tags (guild_id, name, content, created_by) VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+        guild_id.0 as i64,
+        &name,
+        content,
+        msg.author.id.0 as i64
+    )
+    .execute(&pool)
+    .await?;
+
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    msg.reply(ctx, format!("Successfully added the tag `{}`", name))
+        .await?;
+
+    Ok(())
+}
+
+/// Changes the content of a custom text command of this guild.
+///
+/// Usage: `config guild tags edit hello Hi {user}, welcome to {channel}`
+#[command("edit")]
+#[min_args(2)]
+async fn tags_edit(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let name = args.single_quoted::<String>()?.to_lowercase();
+    let content = args.rest().trim();
+
+    if content.chars().count() > MAX_TAG_CONTENT_LENGTH {
+        msg.reply(
+            ctx,
+            format!(
+                "The content of a tag can't be longer than {} characters.",
+                MAX_TAG_CONTENT_LENGTH
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let result = sqlx::query!(
+        "UPDATE tags SET content = $3 WHERE guild_id = $1 AND name = $2",
+        guild_id.0 as i64,
+        &name,
+        content
+    )
+    .execute(&pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        msg.reply(ctx, "There's no tag with that name on this guild.")
+            .await?;
+        return Ok(());
+    }
+
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    msg.react(ctx, '✅').await?;
+
+    Ok(())
+}
+
+/// Removes a custom text command from this guild.
+///
+/// Usage: `config guild tags remove hello`
+#[command("remove")]
+#[aliases(delete, rm)]
+#[num_args(1)]
+async fn tags_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let name = args.single_quoted::<String>()?.to_lowercase();
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let result = sqlx::query!(
+        "DELETE FROM tags WHERE guild_id = $1 AND name = $2",
+        guild_id.0 as i64,
+        &name
+    )
+    .execute(&pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        msg.reply(ctx, "There's no tag with that name on this guild.")
+            .await?;
+        return Ok(());
+    }
+
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    msg.react(ctx, '✅').await?;
+
+    Ok(())
+}
+
+/// Lists the command aliases of this guild.
+/// An alias runs a command with preset arguments, anything written after the alias is added at the end.
+///
+/// `add`: Adds an alias.
+/// `remove`: Removes an alias.
+#[command]
+#[aliases(alias)]
+#[sub_commands(aliases_add, aliases_remove)]
+async fn aliases(ctx: &Context, msg: &Message) -> CommandResult {
+    let settings = get_guild_settings(ctx, msg.guild_id.unwrap()).await;
+
+    if settings.aliases.is_empty() {
+        msg.reply(ctx, "This guild doesn't have any aliases.")
+            .await?;
+        return Ok(());
+    }
+
+    let aliases = format_aliases(&settings.aliases);
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title("Aliases");
+                e.description(aliases)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Adds a command alias to this guild.
+///
+/// Usage:
+/// `config guild aliases add nc picture -s cat`
+/// `config guild aliases add p play`
+#[command("add")]
+#[aliases(create)]
+#[min_args(2)]
+async fn aliases_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let name = args.single_quoted::<String>()?.to_lowercase();
+    let command = args.single::<String>()?.to_lowercase();
+    let arguments = args.rest().trim();
+
+    let booru_commands = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<BooruCommands>().unwrap().clone()
+    };
+
+    if let Err(why) = validate_custom_command_name(&name, &booru_commands) {
+        msg.reply(ctx, why).await?;
+        return Ok(());
+    }
+
+    if !is_command_name(&command, &booru_commands) {
+        msg.reply(ctx, "An alias can only point to an existing command.")
+            .await?;
+        return Ok(());
+    }
+
+    if arguments.chars().count() > MAX_ALIAS_ARGUMENTS_LENGTH {
+        msg.reply(
+            ctx,
+            format!(
+                "The arguments of an alias can't be longer than {} characters.",
+                MAX_ALIAS_ARGUMENTS_LENGTH
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let settings = get_guild_settings(ctx, guild_id).await;
+
+    if settings.aliases.contains_key(&name) || settings.tags.contains_key(&name) {
+        msg.reply(ctx, "There's already an alias or a tag with that name.")
+            .await?;
+        return Ok(());
+    }
+
+    if settings.aliases.len() >= MAX_ALIASES_PER_GUILD {
+        msg.reply(
+            ctx,
+            format!(
+                "A guild can't have more than {} aliases.",
+                MAX_ALIASES_PER_GUILD
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    sqlx::query!(
+        "INSERT INTO command_aliases (guild_id, name, command, arguments, created_by) VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+        guild_id.0 as i64,
+        &name,
+        &command,
+        arguments,
+        msg.author.id.0 as i64
+    )
+    .execute(&pool)
+    .await?;
+
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    msg.reply(ctx, format!("Successfully added the alias `{}`", name))
+        .await?;
+
+    Ok(())
+}
+
+/// Removes a command alias from this guild.
+///
+/// Usage: `config guild aliases remove nc`
+#[command("remove")]
+#[aliases(delete, rm)]
+#[num_args(1)]
+async fn aliases_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let name = args.single_quoted::<String>()?.to_lowercase();
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let result = sqlx::query!(
+        "DELETE FROM command_aliases WHERE guild_id = $1 AND name = $2",
+        guild_id.0 as i64,
+        &name
+    )
+    .execute(&pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        msg.reply(ctx, "There's no alias with that name on this guild.")
+            .await?;
+        return Ok(());
+    }
+
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    msg.react(ctx, '✅').await?;
+
+    Ok(())
+}
+
+/// Lists the RSS and Atom feeds this guild is subscribed to.
+/// New items of the feeds are sent to the channel they were added on, every 10 minutes.
+///
+/// `add`: Subscribes this channel to a feed.
+/// `webhook`: Subscribes this channel to a feed, sent with a webhook.
+/// `remove`: Removes a feed subscription by it's id.
+#[command]
+#[aliases(feed, rss)]
+#[sub_commands(feeds_add, feeds_webhook, feeds_remove)]
+async fn feeds(ctx: &Context, msg: &Message) -> CommandResult {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
     };
 
     let data = sqlx::query!(
-        "SELECT enabled FROM anti_spam WHERE guild_id = $1",
+        "SELECT id, channel_id, webhook IS NOT NULL AS is_hook, url, filter FROM feed_subscriptions WHERE guild_id = $1 ORDER BY id",
         msg.guild_id.unwrap().0 as i64
     )
-    .fetch_optional(&pool)
+    .fetch_all(&pool)
     .await?;
 
-    if let Some(row) = data {
-        sqlx::query!(
-            "UPDATE anti_spam SET enabled = $2 WHERE guild_id = $1",
-            msg.guild_id.unwrap().0 as i64,
-            !row.enabled
+    if data.is_empty() {
+        msg.reply(ctx, "This guild isn't subscribed to any feed.")
+            .await?;
+        return Ok(());
+    }
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title("Feeds");
+                for row in &data {
+                    let mut value = format!("<#{}>", row.channel_id);
+                    if row.is_hook.unwrap_or(false) {
+                        value += " with a webhook";
+                    }
+                    if let Some(filter) = &row.filter {
+                        value += &format!("\nFilter: `{}`", filter);
+                    }
+                    value += &format!("\n{}", row.url.chars().take(900).collect::<String>());
+
+                    e.field(format!("`{}`", row.id), value, false);
+                }
+                e
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Subscribes this channel to an RSS or Atom feed.
+/// Only the items that contain the filter on their title or summary are sent, if there's one.
+///
+/// Usage:
+/// `config guild feeds add https://blog.rust-lang.org/feed.xml`
+/// `config guild feeds add https://blog.rust-lang.org/feed.xml release`
+#[command("add")]
+#[aliases(create)]
+#[min_args(1)]
+async fn feeds_add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    add_feed(ctx, msg, args, false).await
+}
+
+/// Subscribes this channel to an RSS or Atom feed, sending the items with a webhook.
+/// Only the items that contain the filter on their title or summary are sent, if there's one.
+///
+/// Usage:
+/// `config guild feeds webhook https://blog.rust-lang.org/feed.xml`
+/// `config guild feeds webhook https://blog.rust-lang.org/feed.xml release`
+#[command("webhook")]
+#[aliases(hook)]
+#[min_args(1)]
+async fn feeds_webhook(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    add_feed(ctx, msg, args, true).await
+}
+
+async fn add_feed(ctx: &Context, msg: &Message, mut args: Args, is_hook: bool) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    // Urls wrapped with <> don't get embedded by discord.
+    let url = args
+        .single::<String>()?
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string();
+    let filter = Some(args.rest().trim()).filter(|f| !f.is_empty());
+
+    if let Err(why) = validate_feed_url(&url) {
+        msg.reply(ctx, why).await?;
+        return Ok(());
+    }
+
+    if filter.map_or(false, |f| f.chars().count() > MAX_FILTER_LENGTH) {
+        msg.reply(
+            ctx,
+            format!(
+                "The filter can't be longer than {} characters.",
+                MAX_FILTER_LENGTH
+            ),
         )
-        .execute(&pool)
         .await?;
-    } else {
-        sqlx::query!(
-            "INSERT INTO anti_spam (guild_id, enabled) VALUES ($1, true)",
-            msg.guild_id.unwrap().0 as i64
+        return Ok(());
+    }
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let count = sqlx::query!(
+        "SELECT count(*) AS count FROM feed_subscriptions WHERE guild_id = $1",
+        guild_id.0 as i64
+    )
+    .fetch_one(&pool)
+    .await?
+    .count
+    .unwrap_or(0);
+
+    if count as usize >= MAX_FEEDS_PER_GUILD {
+        msg.reply(
+            ctx,
+            format!(
+                "A guild can't be subscribed to more than {} feeds.",
+                MAX_FEEDS_PER_GUILD
+            ),
         )
-        .execute(&pool)
         .await?;
+        return Ok(());
+    }
+
+    let feed = match fetch_feed(&url).await {
+        Ok(x) => x,
+        Err(why) => {
+            // The details can say what's on the network of the bot, so they are only logged.
+            warn!("Error obtaining the feed {}: {}", url, why);
+            msg.reply(
+                ctx,
+                "I couldn't read that feed, make sure it's a public RSS or Atom feed.",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut config = FeedSubscription::default();
+    if is_hook {
+        check_hook(ctx, msg, &mut config).await;
+        if config.hook.is_none() {
+            let map = serde_json::json!({"name": "Robo Arc"});
+
+            config.hook = ctx
+                .http
+                .create_webhook(msg.channel_id.0, &map, None)
+                .await
+                .ok();
+        }
+
+        if config.hook.is_none() {
+            msg.reply(ctx, "There was an error obtaining a webhook. Make sure i have the permission to manage webhooks.").await?;
+            return Ok(());
+        }
+    }
+
+    let hook_url = config.hook.map(|webhook| {
+        format!(
+            "https://discord.com/api/webhooks/{}/{}",
+            webhook.id,
+            webhook.token.unwrap_or_default()
+        )
+    });
+
+    let row = sqlx::query!(
+        "INSERT INTO feed_subscriptions (guild_id, channel_id, webhook, url, filter, created_by) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        guild_id.0 as i64,
+        msg.channel_id.0 as i64,
+        hook_url,
+        &url,
+        filter,
+        msg.author.id.0 as i64
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    // The items that already exist are marked as sent, so only the new ones get sent.
+    feeds::mark_as_sent(&pool, row.id, &feed.guids()).await?;
+
+    msg.reply(
+        ctx,
+        format!(
+            "Successfully subscribed this channel to `{}`, with the id `{}`",
+            feed.title.as_deref().unwrap_or(&url),
+            row.id
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a feed subscription of this guild, by the id shown on `config guild feeds`.
+///
+/// Usage: `config guild feeds remove 12`
+#[command("remove")]
+#[aliases(delete, rm)]
+#[num_args(1)]
+async fn feeds_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let id = args.single::<i32>()?;
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let result = sqlx::query!(
+        "DELETE FROM feed_subscriptions WHERE guild_id = $1 AND id = $2",
+        guild_id.0 as i64,
+        id
+    )
+    .execute(&pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        msg.reply(ctx, "There's no feed with that id on this guild.")
+            .await?;
+        return Ok(());
     }
 
     msg.react(ctx, '✅').await?;
@@ -1330,6 +2268,35 @@ async fn toggle_anti_spam(ctx: &Context, msg: &Message) -> CommandResult {
     Ok(())
 }
 
+/// Toggles the Anti-Spam system on or off.
+///
+/// Currently it's a very simple "if more than 5 messages where sent in less than 5 second
+/// intervals between them, they get deleted"
+///
+/// In the future, this will be able to be configured in multiple ways.
+#[command]
+#[aliases(toggleantispam, antispam, anti_spam, "toggle-anti-spam", "anti-spam")]
+async fn toggle_anti_spam(ctx: &Context, msg: &Message) -> CommandResult {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let data = sqlx::query!(
+        "SELECT enabled FROM anti_spam WHERE guild_id = $1",
+        msg.guild_id.unwrap().0 as i64
+    )
+    .fetch_optional(&pool)
+    .await?;
+
+    let enabled = data.map_or(false, |row| row.enabled);
+    set_anti_spam(&pool, msg.guild_id.unwrap(), !enabled).await?;
+
+    msg.react(ctx, '✅').await?;
+
+    Ok(())
+}
+
 /// WIP: Configures logging for the channel.
 ///
 /// Usage: `configure channel logging 134217727`
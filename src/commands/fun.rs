@@ -1,6 +1,8 @@
 use crate::{
     global_data::{DatabasePool, Tokens},
-    utils::basic_functions::string_to_seconds,
+    utils::reminders::{Recurrence, MAX_RECURRING_REMINDERS},
+    utils::scheduler::{schedule_job, JobKind},
+    utils::time_parser::{parse_time, user_timezone},
 };
 
 use std::{collections::HashMap, ops::Deref, time::Duration};
@@ -187,7 +189,11 @@ async fn translate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
     let (token, url) = {
         let data_read = ctx.data.read().await;
         let tokens = data_read.get::<Tokens>().unwrap();
-        (tokens.ibm.token.to_string(), tokens.ibm.url.to_string())
+        let ibm = tokens
+            .ibm
+            .as_ref()
+            .ok_or("The `ibm` section is missing from the configuration.")?;
+        (ibm.token.to_string(), ibm.url.to_string())
     };
 
     let mut dest = args.single_quoted::<String>()?;
@@ -620,10 +626,15 @@ async fn calculator(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 /// Y -> Year
 /// ```
 ///
+/// It also takes dates and times, in the time zone set with `config user timezone`.
+///
 /// Usage:
 /// `remind_me 2h take the dog out for a walk.`
 /// `remind_me "2h 30m" mess with the neighbours :P`
 /// `remind_me "1Y 1M 1W 1D 1h 1m 1s" i bet you forgot about this!`
+/// `remind_me "tomorrow 9am" call the bank`
+/// `remind_me friday buy the tickets`
+/// `remind_me "2026-12-24 18:00" dinner`
 #[command]
 #[aliases(remindme, reminder, remind, schedule)]
 #[min_args(1)]
@@ -638,29 +649,238 @@ async fn remind_me(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
 
     let message = if text.is_empty() { None } else { Some(text) };
 
-    let seconds = string_to_seconds(unformatted_time);
+    let now = chrono::offset::Utc::now();
+    let tz = user_timezone(&pool, msg.author.id.0).await?;
+
+    let date = match parse_time(&unformatted_time, now, tz) {
+        Ok(x) => x,
+        Err(why) => {
+            msg.reply(ctx, why).await?;
+            return Ok(());
+        }
+    };
 
-    if seconds < 30 {
+    if (date - now).num_seconds() < 30 {
         msg.reply(ctx, "Duration is too short").await?;
         return Ok(());
     }
 
-    sqlx::query!("INSERT INTO reminders (date, message_id, channel_id, guild_id, user_id, message) VALUES ($1, $2, $3, $4, $5, $6)",
-        chrono::offset::Utc::now() + chrono::Duration::seconds(seconds as i64),
+    let row = sqlx::query!("INSERT INTO reminders (date, message_id, channel_id, guild_id, user_id, message) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        date,
         msg.id.0 as i64,
         msg.channel_id.0 as i64,
         msg.guild_id.unwrap_or(GuildId(0)).0 as i64,
         msg.author.id.0 as i64,
         message,
     )
-    .execute(&pool)
+    .fetch_one(&pool)
     .await?;
 
+    schedule_job(ctx, JobKind::Reminder, row.id, date).await;
+
     msg.react(ctx, '👍').await?;
 
     Ok(())
 }
 
+/// Lists your pending reminders.
+/// On a guild only the reminders set on it are shown, on DMs all of them are.
+///
+/// `cancel`: Cancels a reminder.
+/// `every`: Sets a reminder that repeats.
+#[command]
+#[aliases(my_reminders)]
+#[sub_commands(reminders_list, reminders_cancel, reminders_every)]
+async fn reminders(ctx: &Context, msg: &Message) -> CommandResult {
+    list_reminders(ctx, msg).await
+}
+
+/// Lists your pending reminders.
+#[command("list")]
+#[aliases(ls)]
+async fn reminders_list(ctx: &Context, msg: &Message) -> CommandResult {
+    list_reminders(ctx, msg).await
+}
+
+async fn list_reminders(ctx: &Context, msg: &Message) -> CommandResult {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let rows = sqlx::query!(
+        "SELECT id, date, message, recurrence FROM reminders WHERE user_id = $1 AND ($2 = 0 OR guild_id = $2) ORDER BY date LIMIT 25",
+        msg.author.id.0 as i64,
+        msg.guild_id.unwrap_or(GuildId(0)).0 as i64,
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    if rows.is_empty() {
+        msg.reply(ctx, "You don't have any pending reminders.")
+            .await?;
+        return Ok(());
+    }
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title("Reminders");
+                for row in &rows {
+                    let mut value = format!("<t:{0}:f> (<t:{0}:R>)", row.date.timestamp());
+
+                    if let Some(recurrence) =
+                        row.recurrence.as_deref().and_then(Recurrence::from_db)
+                    {
+                        value += &format!("\nRepeats {}", recurrence);
+                    }
+
+                    let message = row.message.as_deref().unwrap_or("No Message.");
+                    if message.chars().count() > 200 {
+                        value += &format!("\n{}…", message.chars().take(199).collect::<String>());
+                    } else {
+                        value += &format!("\n{}", message);
+                    }
+
+                    e.field(format!("`{}`", row.id), value, false);
+                }
+                e.footer(|f| f.text("Use `reminders cancel <id>` to cancel one."))
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Cancels one of your reminders, by the id shown on `reminders list`.
+///
+/// Usage: `reminders cancel 42`
+#[command("cancel")]
+#[aliases(delete, remove, rm)]
+#[num_args(1)]
+async fn reminders_cancel(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = if let Ok(x) = args.single::<i32>() {
+        x
+    } else {
+        msg.reply(ctx, "That's not a valid reminder id.").await?;
+        return Ok(());
+    };
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    // A queued job of a deleted row does nothing when it fires.
+    let result = sqlx::query!(
+        "DELETE FROM reminders WHERE id = $1 AND user_id = $2",
+        id,
+        msg.author.id.0 as i64
+    )
+    .execute(&pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        msg.reply(ctx, "You don't have a reminder with that id.")
+            .await?;
+        return Ok(());
+    }
+
+    msg.react(ctx, '✅').await?;
+
+    Ok(())
+}
+
+/// Sets a reminder that repeats, until it's cancelled.
+/// The times are in the time zone set with `config user timezone`, and without a time the
+/// reminder is sent at 9:00.
+///
+/// ```
+/// 2h              -> Every 2 hours.
+/// day 9:00        -> Every day.
+/// weekdays 8am    -> From monday to friday.
+/// monday 9:00     -> Every monday.
+/// mon,fri 18:30   -> Every monday and friday.
+/// ```
+///
+/// Usage:
+/// `reminders every "monday 9:00" take out the trash`
+/// `reminders every 1W water the plants`
+#[command("every")]
+#[aliases(repeat, recurring)]
+#[min_args(1)]
+async fn reminders_every(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let schedule = args.single_quoted::<String>()?;
+    let text = args.rest();
+
+    let message = if text.is_empty() { None } else { Some(text) };
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let tz = user_timezone(&pool, msg.author.id.0).await?;
+
+    let recurrence = match Recurrence::parse(&schedule, tz) {
+        Ok(x) => x,
+        Err(why) => {
+            msg.reply(ctx, why).await?;
+            return Ok(());
+        }
+    };
+
+    let count = sqlx::query!(
+        "SELECT count(*) AS count FROM reminders WHERE user_id = $1 AND recurrence IS NOT NULL",
+        msg.author.id.0 as i64
+    )
+    .fetch_one(&pool)
+    .await?
+    .count
+    .unwrap_or(0);
+
+    if count >= MAX_RECURRING_REMINDERS {
+        msg.reply(
+            ctx,
+            format!(
+                "You can't have more than {} recurring reminders.",
+                MAX_RECURRING_REMINDERS
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let date = recurrence.next_after(chrono::offset::Utc::now());
+
+    let row = sqlx::query!("INSERT INTO reminders (date, message_id, channel_id, guild_id, user_id, message, recurrence) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+        date,
+        msg.id.0 as i64,
+        msg.channel_id.0 as i64,
+        msg.guild_id.unwrap_or(GuildId(0)).0 as i64,
+        msg.author.id.0 as i64,
+        message,
+        recurrence.to_db(),
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    schedule_job(ctx, JobKind::Reminder, row.id, date).await;
+
+    msg.reply(
+        ctx,
+        format!(
+            "I'll remind you {}, starting <t:{}:f>. ID: `{}`",
+            recurrence,
+            date.timestamp(),
+            row.id
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
 /// Twanswates youw mwessage two thwe uwu lwanguagew uwu.
 ///
 /// usage: `uwufy Translates your message to the uwu language`
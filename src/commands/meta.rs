@@ -1,8 +1,12 @@
 use crate::{
-    global_data::{DatabasePool, ShardManagerContainer},
+    config::reload_configuration,
+    global_data::{BackgroundTasks, BlockedTargets, DatabasePool, ShardManagerContainer},
     notifications::notification_loop,
+    utils::analytics::{command_stats, CommandStats},
     utils::basic_functions::*,
-    is_on_guild,
+    utils::blocklist::{block as block_target, unblock as unblock_target, BlockEntry},
+    utils::guild_settings::{get_guild_settings, get_user_prefix, DEFAULT_PREFIX},
+    start_web_server,
     Tokens, Uptime,
 };
 use std::{
@@ -12,8 +16,6 @@ use std::{
     process::id,
     time::Instant,
     sync::Arc,
-    net::SocketAddr,
-    str::FromStr,
 };
 
 use num_format::{Locale, ToFormattedString};
@@ -25,16 +27,17 @@ use serenity::{
     model::{
         application::oauth::Scope,
         channel::Message,
+        id::{GuildId, UserId},
         Permissions,
         //channel::ReactionType,
     },
     prelude::Context,
+    utils::{content_safe, ContentSafeOptions},
 };
 use tokei::{Config, LanguageType, Languages};
 use tokio::process::Command;
 use toml::Value;
 use walkdir::WalkDir;
-use warp::Filter;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Code {
@@ -251,36 +254,28 @@ async fn todo(ctx: &Context, msg: &Message) -> CommandResult {
 #[command]
 #[aliases(prefixes)]
 async fn prefix(ctx: &Context, msg: &Message) -> CommandResult {
-    let pool = {
-        let data_read = ctx.data.read().await;
-        data_read.get::<DatabasePool>().unwrap().clone()
+    // Read the configured prefixes of the guild, or the default prefix if it's not on a guild.
+    let prefixes = if let Some(guild_id) = msg.guild_id {
+        get_guild_settings(ctx, guild_id).await.prefixes()
+    } else {
+        vec![DEFAULT_PREFIX.to_string()]
     };
 
-    let prefix;
-    if let Some(id) = &msg.guild_id {
-        // obtain the id of the guild as an i64, because the id is stored as a u64, which is
-        // not compatible with the postgre datbase types.
-        let gid = id.0 as i64;
-
-        // Read the configured prefix of the guild from the database.
-        let db_prefix = sqlx::query!("SELECT prefix FROM prefixes WHERE guild_id = $1", gid)
-            .fetch_optional(&pool)
-            .await?;
+    let mut content = format!(
+        "Current prefixes:\n{}",
+        prefixes
+            .iter()
+            .map(|p| format!("`{}`", p))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
 
-        // Just read the value that was stored on the database and return it.
-        if let Some(x) = db_prefix {
-            prefix = x.prefix.unwrap();
-            // Else, the guild doesn't have a configured prefix, return the default prefix.
-        } else {
-            prefix = ".".to_string();
-        }
-    } else {
-        prefix = ".".to_string();
+    if let Some(user_prefix) = get_user_prefix(ctx, msg.author.id).await {
+        content += &format!("\nYour personal prefix:\n`{}`", user_prefix);
     }
 
-    msg.channel_id
-        .say(ctx, format!("Current prefix:\n`{}`", &prefix))
-        .await?;
+    let content = content_safe(ctx, content, &ContentSafeOptions::default(), &[]);
+    msg.channel_id.say(ctx, content).await?;
 
     Ok(())
 }
@@ -923,32 +918,425 @@ async fn force_cache_ready(ctx: &Context, msg: &Message) -> CommandResult {
     msg.reply(ctx, "RUNNING").await?;
 
     let ctx_arc = Arc::new(ctx.clone());
-    let ctx_clone = Arc::clone(&ctx_arc);
-    let ctx_clone2 = Arc::clone(&ctx_arc);
 
-    let web_server_info = {
-        let read_data = ctx.data.read().await;
-        let config = read_data.get::<Tokens>().unwrap();
-        config.web_server.clone()
-    };
+    notification_loop(Arc::clone(&ctx_arc)).await;
+    start_web_server(Arc::clone(&ctx_arc)).await;
 
+    msg.reply(ctx, "OK").await?;
+
+    Ok(())
+}
+
+/// Reloads "config.toml" and "boorus.json" without restarting the bot.
+/// If any of the files is invalid, nothing gets changed.
+#[command]
+#[owners_only]
+#[aliases(reload_configuration, reload)]
+async fn reload_config(ctx: &Context, msg: &Message) -> CommandResult {
+    match reload_configuration(&ctx.data).await {
+        Ok(changes) => {
+            if changes.is_empty() {
+                msg.reply(ctx, "Configuration reloaded, nothing changed.")
+                    .await?;
+            } else {
+                msg.reply(
+                    ctx,
+                    format!("Configuration reloaded:\n{}", changes.join("\n")),
+                )
+                .await?;
+            }
+        }
+        Err(why) => {
+            msg.reply(
+                ctx,
+                format!(
+                    "Could not reload the configuration, nothing was changed: {}",
+                    why
+                ),
+            )
+            .await?;
+        }
+    }
 
-    tokio::spawn(async move { notification_loop(ctx_clone).await });
+    Ok(())
+}
 
-    tokio::spawn(async move {
-        let routes = warp::path::param()
-            .and(warp::any().map(move || ctx_clone2.clone()))
-            .and_then(is_on_guild);
+// Formats the lines of a list of command stats.
+fn format_command_stats<'a>(stats: impl Iterator<Item = &'a CommandStats>) -> String {
+    let lines = stats
+        .map(|s| {
+            format!(
+                "`{}` - {} uses, {:.1}% failed, {}ms avg",
+                s.command,
+                s.uses.to_formatted_string(&Locale::en),
+                s.failure_rate(),
+                s.average_duration_ms()
+            )
+        })
+        .collect::<Vec<_>>();
 
-        let ip = web_server_info.server_ip;
-        let port = web_server_info.server_port;
+    if lines.is_empty() {
+        "Nothing yet.".to_string()
+    } else {
+        lines.join("\n")
+    }
+}
 
-        warp::serve(routes)
-            .run(SocketAddr::from_str(format!("{}:{}", ip, port).as_str()).unwrap())
-            .await;
+async fn send_command_stats(
+    ctx: &Context,
+    msg: &Message,
+    title: String,
+    mut stats: Vec<CommandStats>,
+) -> CommandResult {
+    let total_uses = stats.iter().map(|s| s.uses).sum::<i64>();
+    let total_failures = stats.iter().map(|s| s.failures).sum::<i64>();
+    let total_internal = stats.iter().map(|s| s.internal_errors).sum::<i64>();
+
+    stats.sort_by(|a, b| b.uses.cmp(&a.uses));
+    let most_used = format_command_stats(stats.iter().take(10));
+
+    // The commands that fail because of bugs are the ones that need fixing first.
+    stats.sort_by(|a, b| {
+        b.internal_errors
+            .cmp(&a.internal_errors)
+            .then(b.failures.cmp(&a.failures))
     });
+    let most_failing = format_command_stats(stats.iter().filter(|s| s.failures > 0).take(10));
 
-    msg.reply(ctx, "OK").await?;
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title(title);
+                e.description(format!(
+                    "Uses: `{}`\nFailures: `{}`\nInternal errors: `{}`",
+                    total_uses.to_formatted_string(&Locale::en),
+                    total_failures.to_formatted_string(&Locale::en),
+                    total_internal.to_formatted_string(&Locale::en),
+                ));
+                e.field("Most used", most_used, false);
+                e.field("Most failing", most_failing, false)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Shows the usage of every command, and the ones that fail the most.
+///
+/// Usage: `stats [days]`
+/// The default is the last 7 days.
+#[command]
+#[owners_only]
+#[aliases(command_stats, usage)]
+async fn stats(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let days = args.single::<i32>().unwrap_or(7).max(1);
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let stats = command_stats(&pool, days, None).await?;
+
+    send_command_stats(
+        ctx,
+        msg,
+        format!("Command usage, last {} days", days),
+        stats,
+    )
+    .await
+}
+
+/// Shows the most used commands of this guild, and their error rates.
+///
+/// Usage: `guild_stats [days]`
+/// The default is the last 7 days.
+#[command]
+#[only_in("guilds")]
+#[required_permissions(MANAGE_GUILD)]
+#[aliases(server_stats)]
+async fn guild_stats(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let days = args.single::<i32>().unwrap_or(7).max(1);
+    let guild_id = msg.guild_id.unwrap();
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let stats = command_stats(&pool, days, Some(guild_id.0)).await?;
+
+    send_command_stats(
+        ctx,
+        msg,
+        format!("Command usage on this guild, last {} days", days),
+        stats,
+    )
+    .await
+}
+
+// Parses the optional duration and the reason of a block.
+// If the first argument is not a duration, it's treated as part of the reason.
+fn parse_block_arguments(mut args: Args) -> BlockEntry {
+    let expires_at = if let Ok(raw_duration) = args.single_quoted::<String>() {
+        let seconds = string_to_seconds(&raw_duration);
+
+        if seconds == 0 {
+            args.rewind();
+            None
+        } else {
+            Some(chrono::Utc::now() + chrono::Duration::seconds(seconds as i64))
+        }
+    } else {
+        None
+    };
+
+    let reason = args.rest().trim();
+
+    BlockEntry {
+        reason: if reason.is_empty() {
+            None
+        } else {
+            Some(reason.to_string())
+        },
+        expires_at,
+    }
+}
+
+fn format_block_entry(target: String, entry: &BlockEntry) -> String {
+    format!(
+        "{} - {} - {}",
+        target,
+        entry.reason.as_deref().unwrap_or("No reason."),
+        entry
+            .expires_at
+            .map_or("Permanent".to_string(), |e| format!("until `{}`", e))
+    )
+}
+
+/// Lists the blocked users and guilds.
+///
+/// Usage:
+/// `block user @user ["duration"] [reason]`
+/// `block guild guild_id ["duration"] [reason]`
+/// `unblock user @user`
+/// `unblock guild guild_id`
+#[command]
+#[owners_only]
+#[aliases(blocklist, blocked)]
+#[sub_commands(block_user, block_guild)]
+async fn block(ctx: &Context, msg: &Message) -> CommandResult {
+    let blocklist = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<BlockedTargets>().unwrap().clone()
+    };
+
+    let (users, guilds) = {
+        let blocklist = blocklist.read().await;
+
+        let users = blocklist
+            .users
+            .iter()
+            .filter(|(_, e)| e.is_active())
+            .map(|(id, e)| format_block_entry(format!("<@{}>", id), e))
+            .collect::<Vec<_>>();
+        let guilds = blocklist
+            .guilds
+            .iter()
+            .filter(|(_, e)| e.is_active())
+            .map(|(id, e)| format_block_entry(format!("`{}`", id), e))
+            .collect::<Vec<_>>();
+
+        (users, guilds)
+    };
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.allowed_mentions(|am| am.empty_parse());
+            m.embed(|e| {
+                e.title("Blocklist");
+                e.field(
+                    "Users",
+                    if users.is_empty() {
+                        "None".to_string()
+                    } else {
+                        users.join("\n")
+                    },
+                    false,
+                );
+                e.field(
+                    "Guilds",
+                    if guilds.is_empty() {
+                        "None".to_string()
+                    } else {
+                        guilds.join("\n")
+                    },
+                    false,
+                )
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Blocks a user from using the bot.
+///
+/// Usage: `block user @user ["duration"] [reason]`
+#[command("user")]
+#[owners_only]
+#[min_args(1)]
+async fn block_user(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user_id = args.single::<UserId>()?;
+    let entry = parse_block_arguments(args);
+
+    block_target(ctx, user_id.0, false, entry.clone(), msg.author.id.0).await?;
+
+    msg.reply(
+        ctx,
+        format_block_entry(format!("Blocked user `{}`", user_id.0), &entry),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Blocks a guild from using the bot, and leaves it.
+///
+/// Usage: `block guild guild_id ["duration"] [reason]`
+#[command("guild")]
+#[owners_only]
+#[min_args(1)]
+async fn block_guild(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = GuildId(args.single::<u64>()?);
+    let entry = parse_block_arguments(args);
+
+    block_target(ctx, guild_id.0, true, entry.clone(), msg.author.id.0).await?;
+
+    if ctx.cache.guilds().contains(&guild_id) {
+        guild_id.leave(ctx).await?;
+    }
+
+    msg.reply(
+        ctx,
+        format_block_entry(format!("Blocked guild `{}`", guild_id.0), &entry),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a user or a guild from the blocklist.
+///
+/// Usage:
+/// `unblock user @user`
+/// `unblock guild guild_id`
+#[command]
+#[owners_only]
+#[sub_commands(unblock_user, unblock_guild)]
+async fn unblock(ctx: &Context, msg: &Message) -> CommandResult {
+    msg.reply(
+        ctx,
+        "Usage: `unblock user @user` or `unblock guild guild_id`",
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("user")]
+#[owners_only]
+#[min_args(1)]
+async fn unblock_user(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user_id = args.single::<UserId>()?;
+
+    if unblock_target(ctx, user_id.0, false).await? {
+        msg.reply(ctx, format!("Unblocked user `{}`", user_id.0))
+            .await?;
+    } else {
+        msg.reply(ctx, "That user was not blocked.").await?;
+    }
+
+    Ok(())
+}
+
+#[command("guild")]
+#[owners_only]
+#[min_args(1)]
+async fn unblock_guild(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = args.single::<u64>()?;
+
+    if unblock_target(ctx, guild_id, true).await? {
+        msg.reply(ctx, format!("Unblocked guild `{}`", guild_id))
+            .await?;
+    } else {
+        msg.reply(ctx, "That guild was not blocked.").await?;
+    }
+
+    Ok(())
+}
+
+/// Shows the status of the periodic background tasks.
+/// Tasks marked with ⚠️ have been running for longer than twice their interval.
+#[command]
+#[owners_only]
+#[aliases(background_tasks, jobs)]
+async fn tasks(ctx: &Context, msg: &Message) -> CommandResult {
+    let supervisor = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<BackgroundTasks>().unwrap().clone()
+    };
+
+    let statuses = supervisor.statuses().await;
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title("Background tasks");
+
+                if statuses.is_empty() {
+                    e.description("No background tasks are running.");
+                }
+
+                for (name, status) in &statuses {
+                    let mut text = format!(
+                        "Interval: `{}`\nLast run: {}\nLast duration: `{}`\n\
+                        Runs: `{}` | Failures: `{}` | Panics: `{}`",
+                        seconds_to_days(status.interval.as_secs()),
+                        status
+                            .last_run
+                            .map_or("Never".to_string(), |t| format!("<t:{}:R>", t.timestamp())),
+                        status
+                            .last_duration
+                            .map_or("-".to_string(), |d| format!("{:.2}s", d.as_secs_f64())),
+                        status.runs,
+                        status.failures,
+                        status.panics,
+                    );
+
+                    if let Some(since) = status.running_since {
+                        text += &format!("\nRunning since <t:{}:R>", since.timestamp());
+                    }
+
+                    if let Some(error) = &status.last_error {
+                        let error = error.chars().take(200).collect::<String>();
+                        text += &format!("\nLast error: `{}`", error.replace('`', "'"));
+                    }
+
+                    let title = if status.is_stuck() {
+                        format!("⚠️ {}", name)
+                    } else {
+                        name.to_string()
+                    };
+
+                    e.field(title, text, false);
+                }
+
+                e
+            })
+        })
+        .await?;
 
     Ok(())
 }
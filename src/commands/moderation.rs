@@ -1,6 +1,7 @@
 use crate::global_data::DatabasePool;
-use crate::utils::basic_functions::string_to_seconds;
 use crate::utils::checks::BOT_HAS_MANAGE_ROLES_CHECK;
+use crate::utils::scheduler::{schedule_job, JobKind};
+use crate::utils::time_parser::{parse_time, user_timezone};
 
 use std::time::Duration;
 
@@ -341,6 +342,7 @@ async fn permanent_self_mute(ctx: &Context, msg: &Message) -> CommandResult {
 /// `tempmute @member`
 /// `tempmute @member "2D 12h"`
 /// `tempmute @member "1W" posted porn on #general`
+/// `tempmute @member "tomorrow 9am"`
 #[command]
 #[only_in("guilds")]
 #[required_permissions(MANAGE_ROLES)]
@@ -359,9 +361,19 @@ async fn temporal_mute(ctx: &Context, msg: &Message, mut args: Args) -> CommandR
     let unformatted_time = args
         .single_quoted::<String>()
         .unwrap_or_else(|_| "1h".to_string());
-    let seconds = string_to_seconds(unformatted_time);
 
-    if seconds < 30 {
+    let now = chrono::offset::Utc::now();
+    let tz = user_timezone(&pool, msg.author.id.0).await?;
+
+    let date = match parse_time(&unformatted_time, now, tz) {
+        Ok(x) => x,
+        Err(why) => {
+            msg.reply(ctx, why).await?;
+            return Ok(());
+        }
+    };
+
+    if (date - now).num_seconds() < 30 {
         msg.reply(ctx, "Duration is too short").await?;
         return Ok(());
     }
@@ -379,25 +391,27 @@ async fn temporal_mute(ctx: &Context, msg: &Message, mut args: Args) -> CommandR
     if let Some(row) = row {
         member.add_role(ctx, row.role_id as u64).await?;
 
-        sqlx::query!("INSERT INTO muted_members (date, message_id, channel_id, guild_id, user_id, message) VALUES ($1, $2, $3, $4, $5, $6)",
-            chrono::offset::Utc::now() + chrono::Duration::seconds(seconds as i64),
+        let mute_row = sqlx::query!("INSERT INTO muted_members (date, message_id, channel_id, guild_id, user_id, message) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+            date,
             msg.id.0 as i64,
             msg.channel_id.0 as i64,
             msg.guild_id.unwrap().0 as i64,
             member.user.id.0 as i64,
             message,
         )
-        .execute(&pool)
+        .fetch_one(&pool)
         .await?;
 
+        schedule_job(ctx, JobKind::Unmute, mute_row.id, date).await;
+
         msg.reply(
             ctx,
             format!(
-                "Successfully muted member `{}#{}` with id `{}`\n until `{}`",
+                "Successfully muted member `{}#{}` with id `{}`\n until <t:{}:f>",
                 member.user.name,
                 member.user.discriminator,
                 member.user.id.0,
-                chrono::offset::Utc::now() + chrono::Duration::seconds(seconds as i64)
+                date.timestamp()
             ),
         )
         .await?;
@@ -424,6 +438,7 @@ async fn temporal_mute(ctx: &Context, msg: &Message, mut args: Args) -> CommandR
 /// `selftempmute`
 /// `selftempmute "2D 12h"`
 /// `selftempmute "1W" im an idiot :D`
+/// `selftempmute "friday 18:00" exams`
 #[command]
 #[only_in("guilds")]
 #[aliases(
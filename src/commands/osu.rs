@@ -490,7 +490,10 @@ async fn configure_osu(ctx: &Context, msg: &Message, arguments: Args) -> Command
     let osu_key = {
         let data = ctx.data.read().await; // set inmutable global data.
         let tokens = data.get::<Tokens>().unwrap().clone(); // get the tokens from the global data.
-        tokens.old_osu.to_string()
+        tokens
+            .old_osu
+            .clone()
+            .ok_or("The `old_osu` key is missing from the configuration.")?
     };
 
     let pool = {
@@ -693,7 +696,10 @@ async fn osu_profile(ctx: &Context, msg: &Message, args: Args) -> CommandResult
     let osu_key = {
         let data = ctx.data.read().await; // set inmutable global data.
         let tokens = data.get::<Tokens>().unwrap().clone(); // get the tokens from the global data.
-        tokens.old_osu.to_string()
+        tokens
+            .old_osu
+            .clone()
+            .ok_or("The `old_osu` key is missing from the configuration.")?
     };
 
     let pool = {
@@ -982,7 +988,10 @@ async fn score(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let osu_key = {
         let data = ctx.data.read().await; // set inmutable global data.
         let tokens = data.get::<Tokens>().unwrap().clone(); // get the tokens from the global data.
-        tokens.old_osu.to_string()
+        tokens
+            .old_osu
+            .clone()
+            .ok_or("The `old_osu` key is missing from the configuration.")?
     };
 
     let pool = {
@@ -1145,7 +1154,10 @@ async fn recent(ctx: &Context, msg: &Message, arguments: Args) -> CommandResult
     let osu_key = {
         let data = ctx.data.read().await; // set inmutable global data.
         let tokens = data.get::<Tokens>().unwrap().clone(); // get the tokens from the global data.
-        tokens.old_osu.to_string()
+        tokens
+            .old_osu
+            .clone()
+            .ok_or("The `old_osu` key is missing from the configuration.")?
     };
 
     let pool = {
@@ -1383,7 +1395,10 @@ async fn osu_top(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         let data_read = ctx.data.read().await;
         let tokens = data_read.get::<Tokens>().unwrap().clone();
 
-        tokens.old_osu.to_string()
+        tokens
+            .old_osu
+            .clone()
+            .ok_or("The `old_osu` key is missing from the configuration.")?
     };
 
     let mut config = OsuData::default();
@@ -40,10 +40,14 @@ pub async fn idol(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let (login, pass) = {
         let data = ctx.data.read().await; // set inmutable global data.
         let tokens = data.get::<Tokens>().unwrap();
+        let sankaku = tokens
+            .sankaku
+            .as_ref()
+            .ok_or("The `sankaku` section is missing from the configuration.")?;
 
         (
-            tokens.sankaku.idol_login.to_string(),
-            tokens.sankaku.idol_passhash.to_string(),
+            sankaku.idol_login.to_string(),
+            sankaku.idol_passhash.to_string(),
         )
     };
 
@@ -1,54 +1,608 @@
+use crate::framework_methods::Booru;
+use crate::global_data::{BooruCommands, BooruList, Tokens};
+use crate::utils::command_rules::group_of_command;
+
+use std::{collections::HashSet, env, error::Error, fs::read_to_string, io::ErrorKind, sync::Arc};
+
+use serde_aux::{prelude::*, serde_introspection::serde_introspect};
+use serenity::prelude::{RwLock, TypeMap};
+
+// Every environment variable with this prefix overrides a key of "config.toml".
+// Sections are separated with a double underscore, so `ROBOARC_OSU__CLIENT_SECRET` is `osu.client_secret`
+const ENV_PREFIX: &str = "ROBOARC_";
+
+const MISSING_POSTGRES_URL: &str = "`database.postgres_url` is missing, set it on config.toml or with ROBOARC_DATABASE__POSTGRES_URL.";
+
+// Keys that are only read at startup, so changing them needs a restart.
+// The osu! token loop, the presence and the EventSub subscriptions are set up on startup too.
+const RESTART_KEYS: [&str; 10] = [
+    "discord",
+    "enable_tracing",
+    "trace_level",
+    "lavalink",
+    "web_server",
+    "database",
+    "osu",
+    "presence",
+    "twitch_eventsub_callback",
+    "twitch_eventsub_secret",
+];
+
+// Because "boorus.json" boorus key is a list of values.
+#[derive(Debug, Deserialize)]
+struct BooruRaw {
+    boorus: Vec<Booru>,
+}
+
+// The optional sections and keys disable the features that depend on them when they are missing.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct ConfigurationData {
-    pub old_osu: String,
+    pub old_osu: Option<String>,
     pub discord: String,
-    pub twitch: String,
-    pub twitch_client_id: String,
+    pub twitch: Option<String>,
+    pub twitch_client_id: Option<String>,
+    pub twitch_client_secret: Option<String>,
+    #[serde(default = "default_twitch_api_url")]
+    pub twitch_api_url: String,
+    #[serde(default = "default_twitch_auth_url")]
+    pub twitch_auth_url: String,
+    pub twitch_eventsub_callback: Option<String>,
+    pub twitch_eventsub_secret: Option<String>,
+    #[serde(default = "default_trace_level")]
     pub trace_level: String,
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
     pub enable_tracing: bool,
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
     pub webhook_notifications: bool,
 
-    pub presence: PresenceConfig,
-    pub sankaku: SankakuConfig,
-    pub lavalink: LavalinkConfig,
-    pub web_server: WebServerConfig,
-    pub ibm: IBMConfig,
-    pub osu: OsuConfig,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    pub presence: Option<PresenceConfig>,
+    pub sankaku: Option<SankakuConfig>,
+    pub lavalink: Option<LavalinkConfig>,
+    pub web_server: Option<WebServerConfig>,
+    pub ibm: Option<IBMConfig>,
+    pub osu: Option<OsuConfig>,
+    #[serde(default)]
+    pub task_intervals: TaskIntervalsConfig,
+}
+
+fn default_trace_level() -> String {
+    "info".to_string()
+}
+
+// The Twitch urls can be changed to test the notifications against a mock of the API.
+fn default_twitch_api_url() -> String {
+    "https://api.twitch.tv/helix".to_string()
+}
+
+fn default_twitch_auth_url() -> String {
+    "https://id.twitch.tv/oauth2".to_string()
+}
+
+// The old environment variables are still used when the urls are not configured.
+fn default_postgres_url() -> String {
+    env::var("DATABASE_URL2").unwrap_or_default()
+}
+
+fn default_redis_url() -> String {
+    env::var("REDIS_URL").unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatabaseConfig {
+    #[serde(default = "default_postgres_url")]
+    pub postgres_url: String,
+    #[serde(default = "default_redis_url")]
+    pub redis_url: String,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            postgres_url: default_postgres_url(),
+            redis_url: default_redis_url(),
+        }
+    }
+}
+
+// The interval of every background task in seconds, the missing ones use their default.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskIntervalsConfig {
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub posts: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub twitch: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub twitch_token: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub twitch_eventsub: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub sent_posts: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub feeds: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub scheduler: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub osu_token: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub analytics: Option<u64>,
+}
+
+impl TaskIntervalsConfig {
+    /// The configured interval of a task, by it's name.
+    pub fn get(&self, task_name: &str) -> Option<u64> {
+        match task_name {
+            "posts" => self.posts,
+            "twitch" => self.twitch,
+            "twitch_token" => self.twitch_token,
+            "twitch_eventsub" => self.twitch_eventsub,
+            "sent_posts" => self.sent_posts,
+            "feeds" => self.feeds,
+            "scheduler" => self.scheduler,
+            "osu_token" => self.osu_token,
+            "analytics" => self.analytics,
+            _ => None,
+        }
+        .filter(|x| *x > 0)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct PresenceConfig {
     pub play_or_listen: String,
     pub status: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct SankakuConfig {
     pub idol_login: String,
     pub idol_passhash: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct LavalinkConfig {
     pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub password: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct WebServerConfig {
     pub server_ip: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
     pub server_port: u16,
+    #[serde(default)]
+    pub api_token: Option<String>, // The settings API is disabled without it.
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct IBMConfig {
     pub token: String,
     pub url: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct OsuConfig {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
     pub client_id: u16,
     pub client_secret: String,
 }
+
+impl ConfigurationData {
+    /// Checks the values that toml can't check by itself.
+    pub fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.discord.is_empty() {
+            return Err("`discord` can't be empty.".into());
+        }
+
+        if !["error", "warn", "info", "debug", "trace"].contains(&self.trace_level.as_str()) {
+            return Err(format!(
+                "`trace_level` must be one of error, warn, info, debug or trace, but it's `{}`.",
+                self.trace_level
+            )
+            .into());
+        }
+
+        if let Some(presence) = &self.presence {
+            if !["playing", "listening", "competing"].contains(&presence.play_or_listen.as_str()) {
+                return Err(format!(
+                    "`presence.play_or_listen` must be one of playing, listening or competing, but it's `{}`.",
+                    presence.play_or_listen
+                )
+                .into());
+            }
+        }
+
+        if self.database.postgres_url.is_empty() {
+            return Err(MISSING_POSTGRES_URL.into());
+        }
+
+        if self.database.redis_url.is_empty() {
+            return Err("`database.redis_url` is missing, set it on config.toml or with ROBOARC_DATABASE__REDIS_URL.".into());
+        }
+
+        let has_twitch_credentials = self.twitch.is_some() || self.twitch_client_secret.is_some();
+        if has_twitch_credentials != self.twitch_client_id.is_some() {
+            return Err("`twitch_client_id` needs to be configured together with `twitch_client_secret` or `twitch`.".into());
+        }
+
+        if self.twitch_eventsub_callback.is_some() != self.twitch_eventsub_secret.is_some() {
+            return Err("`twitch_eventsub_callback` needs to be configured together with `twitch_eventsub_secret`.".into());
+        }
+
+        if let Some(secret) = &self.twitch_eventsub_secret {
+            if !(10..=100).contains(&secret.len()) {
+                return Err(
+                    "`twitch_eventsub_secret` must be between 10 and 100 characters long.".into(),
+                );
+            }
+
+            if !has_twitch_credentials {
+                return Err(
+                    "Twitch EventSub needs `twitch_client_id` and `twitch_client_secret`.".into(),
+                );
+            }
+
+            if self.web_server.is_none() {
+                return Err(
+                    "Twitch EventSub needs the `web_server` section to receive the events.".into(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Obtains the configuration key a command needs, if it's missing.
+    pub fn missing_key(&self, command_name: &str) -> Option<&'static str> {
+        let group_name = group_of_command(command_name).map(|g| g.name);
+
+        match (group_name, command_name) {
+            (_, "translate") if self.ibm.is_none() => Some("ibm"),
+            (Some("Sankaku"), _) if self.sankaku.is_none() => Some("sankaku"),
+            (Some("osu!"), _) if self.old_osu.is_none() => Some("old_osu"),
+            (Some("new osu!"), _) if self.osu.is_none() => Some("osu"),
+            (Some("Music"), _) if self.lavalink.is_none() => Some("lavalink"),
+            _ => None,
+        }
+    }
+}
+
+// The keys of a section of the configuration, "" being the top level.
+fn section_keys(section: &str) -> Option<&'static [&'static str]> {
+    Some(match section {
+        "" => serde_introspect::<ConfigurationData>(),
+        "database" => serde_introspect::<DatabaseConfig>(),
+        "task_intervals" => serde_introspect::<TaskIntervalsConfig>(),
+        "presence" => serde_introspect::<PresenceConfig>(),
+        "sankaku" => serde_introspect::<SankakuConfig>(),
+        "lavalink" => serde_introspect::<LavalinkConfig>(),
+        "web_server" => serde_introspect::<WebServerConfig>(),
+        "ibm" => serde_introspect::<IBMConfig>(),
+        "osu" => serde_introspect::<OsuConfig>(),
+        _ => return None,
+    })
+}
+
+// Sets `ROBOARC_*` environment variables on top of the values of the file.
+// The values are always strings, the numbers and booleans get parsed from them on deserialization.
+fn apply_env_overrides(
+    table: &mut toml::value::Table,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    for (key, value) in vars {
+        let path = if let Some(x) = key.strip_prefix(ENV_PREFIX) {
+            x.to_lowercase()
+        } else {
+            continue;
+        };
+
+        let mut keys = path.split("__").collect::<Vec<_>>();
+        let last = keys.pop().unwrap();
+
+        // A typo would otherwise be ignored, and the key left with the value of the file.
+        let is_known = match keys.as_slice() {
+            [] => section_keys("").map_or(false, |k| k.contains(&last)),
+            [section] => {
+                section_keys("").map_or(false, |k| k.contains(section))
+                    && section_keys(section).map_or(false, |k| k.contains(&last))
+            }
+            _ => false,
+        };
+
+        if !is_known {
+            return Err(format!("`{}` doesn't match any configuration key.", key).into());
+        }
+
+        let mut current = &mut *table;
+        for section in keys {
+            let entry = current
+                .entry(section.to_string())
+                .or_insert_with(|| toml::Value::Table(Default::default()));
+
+            current = match entry {
+                toml::Value::Table(x) => x,
+                _ => {
+                    return Err(format!(
+                        "`{}` overrides a key inside of `{}`, but `{}` is not a section.",
+                        key, section, section
+                    )
+                    .into())
+                }
+            };
+        }
+
+        current.insert(last.to_string(), toml::Value::String(value));
+    }
+
+    Ok(())
+}
+
+/// Reads "config.toml", applies the `ROBOARC_*` environment variables on top of it, and validates the result.
+/// The file is optional if everything is configured through environment variables.
+pub fn read_configuration() -> Result<ConfigurationData, Box<dyn Error + Send + Sync>> {
+    let table = read_configuration_table()?;
+
+    // Going through the text representation makes the errors name the key that failed.
+    let merged = toml::to_string(&toml::Value::Table(table))?;
+    let configuration = toml::from_str::<ConfigurationData>(&merged)
+        .map_err(|why| format!("Invalid configuration: {}", why))?;
+
+    configuration.validate()?;
+
+    Ok(configuration)
+}
+
+/// Reads only the `database` section, the rest of the configuration is not validated.
+/// Redis is not needed either, so only the postgres url is required.
+/// Used by `--migrate-only`, so the migrations can run with nothing but the database configured.
+pub fn read_database_configuration() -> Result<DatabaseConfig, Box<dyn Error + Send + Sync>> {
+    let mut table = read_configuration_table()?;
+
+    let database = match table.remove("database") {
+        Some(section) => {
+            let section = toml::to_string(&section)?;
+            toml::from_str::<DatabaseConfig>(&section)
+                .map_err(|why| format!("Invalid `database` section: {}", why))?
+        }
+        None => DatabaseConfig::default(),
+    };
+
+    if database.postgres_url.is_empty() {
+        return Err(MISSING_POSTGRES_URL.into());
+    }
+
+    Ok(database)
+}
+
+// "config.toml" with the `ROBOARC_*` environment variables applied on top.
+fn read_configuration_table() -> Result<toml::value::Table, Box<dyn Error + Send + Sync>> {
+    let mut table = match read_to_string("config.toml") {
+        Ok(contents) => toml::from_str::<toml::value::Table>(&contents)
+            .map_err(|why| format!("config.toml is not valid: {}", why))?,
+        Err(why) if why.kind() == ErrorKind::NotFound => toml::value::Table::new(),
+        Err(why) => return Err(format!("Could not read config.toml: {}", why).into()),
+    };
+
+    apply_env_overrides(&mut table, env::vars())?;
+
+    Ok(table)
+}
+
+/// Reads and validates "boorus.json"
+/// Returns the list of boorus, and a HashSet with every command/alias of them.
+pub fn read_boorus() -> Result<(Vec<Booru>, HashSet<String>), Box<dyn Error + Send + Sync>> {
+    let raw_data = read_to_string("boorus.json")?;
+    let boorus = serde_json::from_str::<BooruRaw>(&raw_data)?.boorus;
+
+    // Add every command on the data to a HashSet
+    let mut all_names = HashSet::new();
+    for booru in boorus.iter() {
+        if booru.names.is_empty() {
+            return Err(format!("The booru `{}` doesn't have any names.", booru.url).into());
+        }
+
+        if !(1..=4).contains(&booru.typ) {
+            return Err(format!(
+                "The booru `{}` has an unknown `typ` {}, it must be between 1 and 4.",
+                booru.names[0], booru.typ
+            )
+            .into());
+        }
+
+        for name in booru.names.iter() {
+            if !all_names.insert(name.to_owned()) {
+                return Err(format!("The booru name `{}` is used more than once.", name).into());
+            }
+        }
+    }
+
+    Ok((boorus, all_names))
+}
+
+// Obtains the keys that are different between 2 toml values, like `osu.client_id`
+fn changed_keys(old: &toml::Value, new: &toml::Value, path: &str, changes: &mut Vec<String>) {
+    match (old, new) {
+        (toml::Value::Table(old), toml::Value::Table(new)) => {
+            let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let key_path = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+
+                match (old.get(key), new.get(key)) {
+                    (Some(o), Some(n)) => changed_keys(o, n, &key_path, changes),
+                    _ => changes.push(key_path),
+                }
+            }
+        }
+        (old, new) => {
+            if old != new {
+                changes.push(path.to_string());
+            }
+        }
+    }
+}
+
+// Whether a changed key, like `osu.client_id`, only takes effect after a restart.
+fn requires_restart(key: &str) -> bool {
+    RESTART_KEYS
+        .iter()
+        .any(|k| key.split('.').next() == Some(k))
+}
+
+/// Re-reads "config.toml" and "boorus.json" and replaces the values on the data.
+/// Both files are validated before anything is replaced, so on error nothing changes.
+///
+/// Returns a human readable list of what changed.
+pub async fn reload_configuration(
+    data: &RwLock<TypeMap>,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let configuration = read_configuration()?;
+    let (boorus, booru_commands) = read_boorus()?;
+
+    let mut data = data.write().await;
+
+    let old_configuration = data.get::<Tokens>().unwrap().clone();
+    let old_booru_commands = data.get::<BooruCommands>().unwrap().clone();
+
+    let mut keys = Vec::new();
+    changed_keys(
+        &toml::Value::try_from(&*old_configuration)?,
+        &toml::Value::try_from(&configuration)?,
+        "",
+        &mut keys,
+    );
+
+    // Only the names of the keys are reported, as the values are mostly secrets.
+    let mut changes = keys
+        .into_iter()
+        .map(|key| {
+            if requires_restart(&key) {
+                format!("Changed `{}` (requires a restart)", key)
+            } else {
+                format!("Changed `{}`", key)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut added = booru_commands
+        .difference(&old_booru_commands)
+        .collect::<Vec<_>>();
+    let mut removed = old_booru_commands
+        .difference(&booru_commands)
+        .collect::<Vec<_>>();
+    added.sort();
+    removed.sort();
+
+    if !added.is_empty() {
+        changes.push(format!(
+            "Added booru commands: {}",
+            added
+                .iter()
+                .map(|i| format!("`{}`", i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if !removed.is_empty() {
+        changes.push(format!(
+            "Removed booru commands: {}",
+            removed
+                .iter()
+                .map(|i| format!("`{}`", i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    // Everything is swapped while holding the write lock, so nothing sees a half reloaded state.
+    data.insert::<Tokens>(Arc::new(configuration));
+    data.insert::<BooruList>(Arc::new(boorus));
+    data.insert::<BooruCommands>(Arc::new(booru_commands));
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(old: &str, new: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        changed_keys(
+            &toml::from_str(old).unwrap(),
+            &toml::from_str(new).unwrap(),
+            "",
+            &mut keys,
+        );
+        keys
+    }
+
+    #[test]
+    fn startup_only_keys_require_a_restart() {
+        let old = r#"
+            twitch_eventsub_callback = "https://example.com/twitch/eventsub"
+            twitch_eventsub_secret = "a long random string"
+
+            [presence]
+            play_or_listen = "playing"
+            status = "the game of life."
+
+            [osu]
+            client_id = 1234
+            client_secret = "secret"
+        "#;
+        let new = r#"
+            twitch_eventsub_callback = "https://example.org/twitch/eventsub"
+            twitch_eventsub_secret = "another long random string"
+
+            [presence]
+            play_or_listen = "listening"
+            status = "the game of life."
+
+            [osu]
+            client_id = 4321
+            client_secret = "secret"
+        "#;
+
+        let keys = changes(old, new);
+        assert_eq!(
+            keys,
+            [
+                "osu.client_id",
+                "presence.play_or_listen",
+                "twitch_eventsub_callback",
+                "twitch_eventsub_secret",
+            ]
+        );
+        assert!(keys.iter().all(|k| requires_restart(k)));
+    }
+
+    #[test]
+    fn live_keys_dont_require_a_restart() {
+        let keys = changes(
+            "webhook_notifications = false",
+            "webhook_notifications = true",
+        );
+
+        assert_eq!(keys, ["webhook_notifications"]);
+        assert!(!requires_restart(&keys[0]));
+    }
+}
@@ -0,0 +1,229 @@
+//! The error model used to report command failures to users.
+//!
+//! Commands can return a `BotError` directly, or any other error, which gets classified by it's
+//! type before being shown. Only user mistakes and permission problems are shown as they are,
+//! everything else gets a generic message, so library internals never reach the channel.
+//! Plain string errors, like `Err("No user found.".into())`, are written for the users, so they
+//! are user mistakes too.
+use std::{
+    convert::Infallible,
+    error::Error,
+    fmt,
+    num::{ParseFloatError, ParseIntError},
+    str::ParseBoolError,
+};
+
+use serenity::{
+    framework::standard::{ArgError, CommandError},
+    http::HttpError,
+    model::{
+        channel::Message,
+        misc::{ChannelIdParseError, RoleIdParseError, UserIdParseError},
+        ModelError,
+    },
+    prelude::Context,
+    Error as SerenityError,
+};
+
+type BoxedError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum BotError {
+    /// The user did something wrong, like giving an invalid argument.
+    /// The message is shown to the user as it is.
+    User(String),
+    /// The bot, or the user, doesn't have the permissions needed.
+    Permission(String),
+    /// An external service failed, or returned something unexpected.
+    Upstream {
+        service: &'static str,
+        source: BoxedError,
+    },
+    /// Something that should not happen, a bug.
+    Internal(BoxedError),
+}
+
+impl BotError {
+    pub fn user(message: impl Into<String>) -> Self {
+        Self::User(message.into())
+    }
+
+    pub fn permission(message: impl Into<String>) -> Self {
+        Self::Permission(message.into())
+    }
+
+    pub fn upstream(service: &'static str, source: impl Into<BoxedError>) -> Self {
+        Self::Upstream {
+            service,
+            source: source.into(),
+        }
+    }
+
+    pub fn internal(source: impl Into<BoxedError>) -> Self {
+        Self::Internal(source.into())
+    }
+
+    /// The name of the category, as it's stored on the command analytics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::User(_) => "user",
+            Self::Permission(_) => "permission",
+            Self::Upstream { .. } => "upstream",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    /// Classifies an error returned by a command.
+    pub fn from_command_error(error: CommandError) -> Self {
+        let error = match error.downcast::<BotError>() {
+            Ok(x) => return *x,
+            Err(x) => x,
+        };
+
+        let error = match error.downcast::<SerenityError>() {
+            Ok(x) => return Self::from(*x),
+            Err(x) => x,
+        };
+
+        let error = match error.downcast::<reqwest::Error>() {
+            Ok(x) => return Self::from(*x),
+            Err(x) => x,
+        };
+
+        if error.is::<ParseIntError>()
+            || error.is::<ParseFloatError>()
+            || error.is::<ParseBoolError>()
+            || is_arg_error(error.as_ref())
+        {
+            return Self::User("One of the arguments is not valid.".to_string());
+        }
+
+        if is_plain_message(error.as_ref()) {
+            return Self::User(error.to_string());
+        }
+
+        Self::Internal(error)
+    }
+}
+
+// The errors of `Args::single` and `Args::single_quoted`, for every type the commands parse.
+fn is_arg_error(error: &(dyn Error + Send + Sync + 'static)) -> bool {
+    error.is::<ArgError<Infallible>>()
+        || error.is::<ArgError<ParseIntError>>()
+        || error.is::<ArgError<ParseFloatError>>()
+        || error.is::<ArgError<ParseBoolError>>()
+        || error.is::<ArgError<UserIdParseError>>()
+        || error.is::<ArgError<RoleIdParseError>>()
+        || error.is::<ArgError<ChannelIdParseError>>()
+}
+
+// The errors made from a `&str` or a `String` are of a private type, but their debug output is
+// the quoted message, which no other error type has.
+fn is_plain_message(error: &(dyn Error + Send + Sync + 'static)) -> bool {
+    error.source().is_none() && format!("{:?}", error) == format!("{:?}", error.to_string())
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User(message) => write!(f, "{}", message),
+            Self::Permission(message) => write!(f, "Missing permissions: {}", message),
+            Self::Upstream { service, source } => write!(f, "{} failed: {}", service, source),
+            Self::Internal(source) => write!(f, "Internal error: {}", source),
+        }
+    }
+}
+
+impl Error for BotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Upstream { source, .. } | Self::Internal(source) => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<SerenityError> for BotError {
+    fn from(error: SerenityError) -> Self {
+        if let SerenityError::Model(ModelError::InvalidPermissions(permissions)) = &error {
+            return Self::Permission(format!(
+                "I need the {} permissions to do that.",
+                permissions.get_permission_names().join(", ")
+            ));
+        }
+
+        if let SerenityError::Http(http_error) = &error {
+            if let HttpError::UnsuccessfulRequest(response) = http_error.as_ref() {
+                if response.status_code.as_u16() == 403 {
+                    return Self::Permission("Discord didn't allow me to do that.".to_string());
+                }
+            }
+
+            return Self::upstream("Discord", error);
+        }
+
+        Self::Internal(error.into())
+    }
+}
+
+impl From<reqwest::Error> for BotError {
+    fn from(error: reqwest::Error) -> Self {
+        let service = match error.url().and_then(|u| u.host_str()) {
+            Some(x) if x.contains("osu.ppy.sh") => "osu!",
+            Some(x) if x.contains("twitch.tv") => "Twitch",
+            _ => "An external service",
+        };
+
+        Self::upstream(service, error)
+    }
+}
+
+impl From<sqlx::Error> for BotError {
+    fn from(error: sqlx::Error) -> Self {
+        Self::Internal(error.into())
+    }
+}
+
+// A short random id, so a report from a user can be found on the logs.
+fn incident_id() -> String {
+    format!("{:08x}", rand::random::<u32>())
+}
+
+/// Shows an error to the user that ran a command, and logs it.
+pub async fn report_error(ctx: &Context, msg: &Message, command_name: &str, error: &BotError) {
+    let content = match error {
+        BotError::User(message) => message.to_string(),
+        BotError::Permission(message) => format!("I can't do that. {}", message),
+        BotError::Upstream { service, source } => {
+            warn!(
+                "Upstream error from {} while running command {}: {:?}",
+                service, command_name, source
+            );
+            format!("{} didn't respond correctly, try again later.", service)
+        }
+        BotError::Internal(source) => {
+            let id = incident_id();
+            error!(
+                "Incident {} while running command {} | message: {:?} | author: {} | channel: {} | guild: {:?} | error: {:?}",
+                id,
+                command_name,
+                msg.content,
+                msg.author.id,
+                msg.channel_id,
+                msg.guild_id,
+                source
+            );
+            format!(
+                "Something went wrong on my side. If this keeps happening, report it with the incident id `{}`",
+                id
+            )
+        }
+    };
+
+    if let Err(why) = msg.channel_id.say(ctx, content).await {
+        error!(
+            "Unable to send messages on channel id {}: {}",
+            &msg.channel_id.0, why
+        );
+    }
+}
@@ -1,5 +1,11 @@
-use crate::global_data::Lavalink;
+use crate::api;
+use crate::eventsub;
+use crate::global_data::{BlockedTargets, Lavalink, ShutdownSignal};
 use crate::notifications::notification_loop;
+use crate::slash_commands::{handle_application_command, register_application_commands};
+use crate::utils::analytics::analytics_loop;
+use crate::utils::metrics::{check_health, render_metrics};
+use crate::utils::reminders::{handle_snooze, SNOOZE_PREFIX};
 use crate::AnnoyedChannels;
 use crate::DatabasePool;
 use crate::Tokens;
@@ -13,15 +19,16 @@ use tokio::{
     sync::Mutex,
     time::{sleep, Duration},
 };
-use warp::{reply::json, reply::Json, Filter};
+use warp::{http::StatusCode, reply::json, reply::Json, Filter, Reply};
 
 use serenity::{
     async_trait,
     model::{
+        application::interaction::Interaction,
         channel::{GuildChannel, Message, Reaction, ReactionType},
         event::VoiceServerUpdateEvent,
         gateway::{Activity, Ready},
-        guild::Member,
+        guild::{Guild, Member},
         id::{ChannelId, GuildId},
         user::OnlineStatus,
         voice::VoiceState,
@@ -55,6 +62,75 @@ pub async fn is_on_guild(guild_id: u64, ctx: Arc<Context>) -> Result<Json, warp:
     Ok(json(&data))
 }
 
+/// Serves the metrics in the Prometheus text format.
+pub async fn metrics(ctx: Arc<Context>) -> Result<impl Reply, warp::Rejection> {
+    Ok(warp::reply::with_header(
+        render_metrics(&ctx).await,
+        "content-type",
+        "text/plain; version=0.0.4",
+    ))
+}
+
+/// Responds with an error status when the gateway or the database are not working.
+pub async fn healthz(ctx: Arc<Context>) -> Result<impl Reply, warp::Rejection> {
+    let reply = match check_health(&ctx).await {
+        Ok(_) => warp::reply::with_status("ok".to_string(), StatusCode::OK),
+        Err(why) => {
+            warn!("Health check failed: {}", why);
+            warp::reply::with_status(why, StatusCode::SERVICE_UNAVAILABLE)
+        }
+    };
+
+    Ok(reply)
+}
+
+/// Starts the web server, if it's configured.
+/// The server stops accepting connections when the bot shuts down.
+pub async fn start_web_server(ctx: Arc<Context>) {
+    let (web_server_info, shutdown) = {
+        let read_data = ctx.data.read().await;
+        let config = read_data.get::<Tokens>().unwrap();
+        let shutdown = read_data.get::<ShutdownSignal>().unwrap();
+        (config.web_server.clone(), shutdown.clone())
+    };
+
+    // Without the web_server section, the web server doesn't get started.
+    let web_server_info = if let Some(x) = web_server_info {
+        x
+    } else {
+        return;
+    };
+
+    let api_routes = api::routes(ctx.clone());
+    let eventsub_routes = eventsub::routes(ctx.clone());
+    let with_ctx = warp::any().map(move || ctx.clone());
+
+    let metrics_route = warp::path!("metrics")
+        .and(with_ctx.clone())
+        .and_then(metrics);
+    let healthz_route = warp::path!("healthz")
+        .and(with_ctx.clone())
+        .and_then(healthz);
+    let is_on_guild_route = warp::path::param().and(with_ctx).and_then(is_on_guild);
+
+    let routes = metrics_route
+        .or(healthz_route)
+        .or(api_routes)
+        .or(eventsub_routes)
+        .or(is_on_guild_route);
+
+    let ip = web_server_info.server_ip;
+    let port = web_server_info.server_port;
+
+    let shutdown_clone = shutdown.clone();
+    let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(
+        SocketAddr::from_str(format!("{}:{}", ip, port).as_str()).unwrap(),
+        async move { shutdown_clone.wait().await },
+    );
+
+    shutdown.spawn("web_server", server).await;
+}
+
 #[async_trait]
 impl EventHandler for Handler {
     async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
@@ -65,31 +141,10 @@ impl EventHandler for Handler {
 
             let ctx = Arc::new(ctx);
 
-            let web_server_info = {
-                let read_data = ctx.data.read().await;
-                let config = read_data.get::<Tokens>().unwrap();
-                config.web_server.clone()
-            };
-
-            let ctx_clone = Arc::clone(&ctx);
-            let ctx_clone2 = Arc::clone(&ctx);
+            notification_loop(Arc::clone(&ctx)).await;
+            analytics_loop(Arc::clone(&ctx)).await;
+            start_web_server(Arc::clone(&ctx)).await;
 
-            let notification_loop = tokio::spawn(async move { notification_loop(ctx_clone).await });
-
-            tokio::spawn(async move {
-                let routes = warp::path::param()
-                    .and(warp::any().map(move || ctx_clone2.clone()))
-                    .and_then(is_on_guild);
-
-                let ip = web_server_info.server_ip;
-                let port = web_server_info.server_port;
-
-                warp::serve(routes)
-                    .run(SocketAddr::from_str(format!("{}:{}", ip, port).as_str()).unwrap())
-                    .await;
-            });
-
-            let _ = notification_loop.await;
             *self.run_loops.lock().await = false;
         }
     }
@@ -103,21 +158,30 @@ impl EventHandler for Handler {
             config.presence.clone()
         };
 
-        if info.play_or_listen == "playing" {
-            ctx.set_presence(Some(Activity::playing(&info.status)), OnlineStatus::Online)
+        if let Some(info) = info {
+            if info.play_or_listen == "playing" {
+                ctx.set_presence(Some(Activity::playing(&info.status)), OnlineStatus::Online)
+                    .await;
+            } else if info.play_or_listen == "listening" {
+                ctx.set_presence(
+                    Some(Activity::listening(&info.status)),
+                    OnlineStatus::Online,
+                )
+                .await;
+            } else if info.play_or_listen == "competing" {
+                ctx.set_presence(
+                    Some(Activity::competing(&info.status)),
+                    OnlineStatus::Online,
+                )
                 .await;
-        } else if info.play_or_listen == "listening" {
-            ctx.set_presence(
-                Some(Activity::listening(&info.status)),
-                OnlineStatus::Online,
-            )
-            .await;
-        } else if info.play_or_listen == "competing" {
-            ctx.set_presence(
-                Some(Activity::competing(&info.status)),
-                OnlineStatus::Online,
-            )
-            .await;
+            }
+        }
+
+        // Registering the commands once is enough, as they are global.
+        if ctx.shard_id == 0 {
+            if let Err(why) = register_application_commands(&ctx).await {
+                error!("Error registering application commands: {}", why);
+            }
         }
 
         info!("Bot is READY");
@@ -205,6 +269,46 @@ impl EventHandler for Handler {
         }
     }
 
+    // Slash commands get dispatched to the same framework as the prefix commands.
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::ApplicationCommand(command) => {
+                if let Err(why) = handle_application_command(&ctx, &command).await {
+                    error!(
+                        "Error handling application command {}: {}",
+                        command.data.name, why
+                    );
+                }
+            }
+            Interaction::MessageComponent(component)
+                if component.data.custom_id.starts_with(SNOOZE_PREFIX) =>
+            {
+                if let Err(why) = handle_snooze(&ctx, &component).await {
+                    error!("Error snoozing a reminder: {}", why);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // This also triggers for every guild the bot is on when it starts.
+    async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: bool) {
+        let is_blocked = {
+            let data_read = ctx.data.read().await;
+            let blocklist = data_read.get::<BlockedTargets>().unwrap().clone();
+            let is_blocked = blocklist.read().await.is_guild_blocked(guild.id.0);
+            is_blocked
+        };
+
+        if is_blocked {
+            info!("Leaving blocked guild {}", guild.id);
+
+            if let Err(why) = guild.id.leave(&ctx).await {
+                error!("Unable to leave blocked guild {}: {}", guild.id, why);
+            }
+        }
+    }
+
     /// on_raw_reaction_add event on d.py
     /// This function triggers every time a reaction gets added to a message.
     async fn reaction_add(&self, ctx: Context, add_reaction: Reaction) {
@@ -336,7 +440,11 @@ impl EventHandler for Handler {
 
         if let Some(call) = opt_call {
             let connection_info = call.lock().await.current_connection().unwrap().clone();
-            let lavalink = ctx.data.read().await.get::<Lavalink>().unwrap().clone();
+            let lavalink = if let Some(x) = ctx.data.read().await.get::<Lavalink>() {
+                x.clone()
+            } else {
+                return;
+            };
 
             tokio::spawn(async move {
                 trace!("(Voice Server Update) Call pause");
@@ -392,7 +500,11 @@ impl EventHandler for Handler {
 
                 let lava_client = {
                     let data_read = ctx.data.read().await;
-                    data_read.get::<Lavalink>().unwrap().clone()
+                    if let Some(x) = data_read.get::<Lavalink>() {
+                        x.clone()
+                    } else {
+                        return;
+                    }
                 };
 
                 if let Err(why) = lava_client.destroy(guild_id).await {
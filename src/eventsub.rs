@@ -0,0 +1,313 @@
+//! Receiver of the Twitch EventSub webhooks, served on the web server at `/twitch/eventsub`.
+//!
+//! Every message is verified with the HMAC signature made with `twitch_eventsub_secret`.
+//! `stream.online` and `stream.offline` check that single stream right away, the polling of
+//! `check_twitch_livestreams` is kept as a fallback for the events that don't arrive.
+use crate::api::constant_time_eq;
+use crate::global_data::{DatabasePool, Tokens};
+use crate::notifications::check_twitch_streams;
+use crate::utils::twitch::{EventSubSubscription, Helix};
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    error::Error,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use crypto::{hmac::Hmac, mac::Mac, sha2::Sha256};
+use serenity::prelude::Context;
+use warp::{
+    http::{HeaderMap, StatusCode},
+    hyper::body::Bytes,
+    reply::Response,
+    Filter, Rejection, Reply,
+};
+
+// The events are tiny, anything bigger is not from Twitch.
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+// Messages older than this are rejected, so a captured message can't be replayed later.
+const MAX_MESSAGE_AGE: i64 = 10 * 60;
+// Helix can take a bit to show the change after the event, so the stream is checked again.
+const CHECK_ATTEMPTS: u32 = 4;
+const CHECK_RETRY_DELAY: Duration = Duration::from_secs(15);
+
+const STREAM_EVENTS: [&str; 2] = ["stream.online", "stream.offline"];
+// The subscriptions with any other status are not going to send events anymore.
+const ACTIVE_STATUSES: [&str; 2] = ["enabled", "webhook_callback_verification_pending"];
+
+#[derive(Deserialize, Debug)]
+struct StreamEvent {
+    broadcaster_user_login: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventSubMessage {
+    subscription: EventSubSubscription,
+    challenge: Option<String>,
+    event: Option<StreamEvent>,
+}
+
+/// Checks the `Twitch-Eventsub-Message-Signature` of a message.
+/// The signature is the HMAC-SHA256 of the message id, the timestamp and the body.
+pub fn verify_signature(
+    secret: &str,
+    message_id: &str,
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+) -> bool {
+    let mut mac = Hmac::new(Sha256::new(), secret.as_bytes());
+    mac.input(message_id.as_bytes());
+    mac.input(timestamp.as_bytes());
+    mac.input(body);
+
+    let expected = format!("sha256={}", hex::encode(mac.result().code()));
+
+    constant_time_eq(expected.as_bytes(), signature.as_bytes())
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|x| x.to_str().ok())
+}
+
+async fn receive_event(
+    headers: HeaderMap,
+    body: Bytes,
+    ctx: Arc<Context>,
+) -> Result<Response, Infallible> {
+    let secret = {
+        let data_read = ctx.data.read().await;
+        let config = data_read.get::<Tokens>().unwrap();
+        config.twitch_eventsub_secret.clone()
+    };
+
+    let secret = if let Some(x) = secret {
+        x
+    } else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let (message_id, timestamp, signature, message_type) = match (
+        header(&headers, "twitch-eventsub-message-id"),
+        header(&headers, "twitch-eventsub-message-timestamp"),
+        header(&headers, "twitch-eventsub-message-signature"),
+        header(&headers, "twitch-eventsub-message-type"),
+    ) {
+        (Some(a), Some(b), Some(c), Some(d)) => (a, b, c, d),
+        _ => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    if !verify_signature(&secret, message_id, timestamp, &body, signature) {
+        warn!("Received a Twitch EventSub message with an invalid signature.");
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let sent_at = if let Ok(x) = DateTime::parse_from_rfc3339(timestamp) {
+        x.with_timezone(&Utc)
+    } else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+
+    if (Utc::now() - sent_at).num_seconds().abs() > MAX_MESSAGE_AGE {
+        warn!("Received a Twitch EventSub message that's too old.");
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let message = if let Ok(x) = serde_json::from_slice::<EventSubMessage>(&body) {
+        x
+    } else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+
+    match message_type {
+        "webhook_callback_verification" => {
+            info!(
+                "Verified the Twitch EventSub subscription {} for {}.",
+                message.subscription.id, message.subscription.kind
+            );
+
+            Ok(warp::reply::with_header(
+                message.challenge.unwrap_or_default(),
+                "content-type",
+                "text/plain",
+            )
+            .into_response())
+        }
+        "notification" => {
+            let is_live = match message.subscription.kind.as_str() {
+                "stream.online" => true,
+                "stream.offline" => false,
+                _ => return Ok(StatusCode::NO_CONTENT.into_response()),
+            };
+
+            if let Some(event) = message.event {
+                // Twitch expects a reply within a few seconds, so the stream is checked after.
+                tokio::spawn(check_stream(ctx, event.broadcaster_user_login, is_live));
+            }
+
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        "revocation" => {
+            warn!(
+                "Twitch revoked the EventSub subscription {} for {}: {}",
+                message.subscription.id, message.subscription.kind, message.subscription.status
+            );
+
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        _ => Ok(StatusCode::NO_CONTENT.into_response()),
+    }
+}
+
+// Checking a stream twice is harmless, so the messages Twitch sends again are not filtered.
+async fn check_stream(ctx: Arc<Context>, login: String, is_live: bool) {
+    for attempt in 1..=CHECK_ATTEMPTS {
+        match check_twitch_streams(&ctx, Some(&login)).await {
+            Ok(live) if (live > 0) == is_live => return,
+            Ok(_) => (),
+            Err(why) => error!("Error checking the stream of {}: {}", login, why),
+        }
+
+        if attempt < CHECK_ATTEMPTS {
+            tokio::time::sleep(CHECK_RETRY_DELAY).await;
+        }
+    }
+
+    debug!(
+        "The stream of {} didn't change on Helix yet, the polling will pick it up.",
+        login
+    );
+}
+
+/// Creates the missing EventSub subscriptions of the streamers that have notifications, and
+/// deletes the ones of the streamers that don't have any anymore, or that stopped working.
+pub async fn sync_eventsub_subscriptions(
+    ctx: &Context,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (pool, callback, secret) = {
+        let data_read = ctx.data.read().await;
+        let config = data_read.get::<Tokens>().unwrap();
+
+        (
+            data_read.get::<DatabasePool>().unwrap().clone(),
+            config.twitch_eventsub_callback.clone(),
+            config.twitch_eventsub_secret.clone(),
+        )
+    };
+
+    let (callback, secret) = match (callback, secret) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Ok(()),
+    };
+
+    let helix = if let Some(x) = Helix::new(ctx).await {
+        x
+    } else {
+        return Ok(());
+    };
+
+    let streamers = sqlx::query!(
+        "SELECT streamer FROM streamers s WHERE EXISTS (SELECT 1 FROM streamer_notification_channel c WHERE c.streamer = s.streamer) OR EXISTS (SELECT 1 FROM streamer_notification_webhook w WHERE w.streamer = s.streamer)"
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|r| r.streamer.to_lowercase())
+    .collect::<Vec<_>>();
+
+    let user_ids = helix.user_ids(&streamers).await?;
+    let mut missing = user_ids
+        .values()
+        .flat_map(|id| {
+            STREAM_EVENTS
+                .iter()
+                .map(move |kind| (kind.to_string(), id.clone()))
+        })
+        .collect::<HashSet<_>>();
+
+    // Only the subscriptions sent to this bot are managed.
+    let subscriptions = helix
+        .eventsub_subscriptions()
+        .await?
+        .into_iter()
+        .filter(|s| {
+            s.transport.method == "webhook" && s.transport.callback.as_ref() == Some(&callback)
+        })
+        .collect::<Vec<_>>();
+
+    let mut deleted = 0;
+    for subscription in subscriptions {
+        let key = (
+            subscription.kind.clone(),
+            subscription
+                .condition
+                .broadcaster_user_id
+                .clone()
+                .unwrap_or_default(),
+        );
+
+        let active = ACTIVE_STATUSES.contains(&subscription.status.as_str());
+        if active && missing.remove(&key) {
+            continue;
+        }
+
+        helix.delete_eventsub_subscription(&subscription.id).await?;
+        deleted += 1;
+    }
+
+    let logins = user_ids
+        .iter()
+        .map(|(login, id)| (id.clone(), login.clone()))
+        .collect::<HashMap<_, _>>();
+
+    for (kind, user_id) in &missing {
+        if let Err(why) = helix
+            .create_eventsub_subscription(kind, user_id, &callback, &secret)
+            .await
+        {
+            error!(
+                "Error subscribing to {} of {}: {}",
+                kind,
+                logins.get(user_id).map_or("unknown", |x| x.as_str()),
+                why
+            );
+        }
+    }
+
+    if deleted > 0 || !missing.is_empty() {
+        info!(
+            "Synced the Twitch EventSub subscriptions, {} created and {} deleted.",
+            missing.len(),
+            deleted
+        );
+    }
+
+    Ok(())
+}
+
+/// Syncs the EventSub subscriptions on the background, after the streamers change.
+pub fn spawn_sync(ctx: &Context) {
+    let ctx = ctx.clone();
+
+    tokio::spawn(async move {
+        if let Err(why) = sync_eventsub_subscriptions(&ctx).await {
+            error!("Error syncing the Twitch EventSub subscriptions: {}", why);
+        }
+    });
+}
+
+/// The route that receives the EventSub messages.
+pub fn routes(ctx: Arc<Context>) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    let with_ctx = warp::any().map(move || ctx.clone());
+
+    warp::path!("twitch" / "eventsub")
+        .and(warp::post())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::bytes())
+        .and(with_ctx)
+        .and_then(receive_event)
+}
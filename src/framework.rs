@@ -16,6 +16,8 @@ use crate::commands::new_osu::*; // Import everything from the new osu module.
 use crate::commands::osu::*; // Import everything from the osu module.
 use crate::commands::sankaku::*; // Import everything from the sankaku booru module.
 use crate::commands::serenity_docs::*; // Import everything from the serenity_docs module.
+use crate::utils::aliases::format_aliases;
+use crate::utils::guild_settings::{get_guild_settings, GuildSettings};
 
 use std::collections::HashSet;
 
@@ -23,7 +25,7 @@ use serenity::{
     framework::standard::{
         help_commands,
         macros::{group, help},
-        Args, CommandGroup, CommandResult, HelpOptions,
+        Args, CommandGroup, CommandResult, Delimiter, HelpOptions,
     },
     model::prelude::*,
     prelude::*,
@@ -63,6 +65,12 @@ pub struct Master;
     rust,
     admin_eval,
     force_cache_ready,
+    reload_config,
+    stats,
+    guild_stats,
+    block,
+    unblock,
+    tasks,
 )]
 pub struct Meta;
 
@@ -123,6 +131,7 @@ pub struct ImageManipulation;
     decrypt,
     calculator,
     remind_me,
+    reminders,
     uwufy
 )]
 pub struct Fun;
@@ -201,6 +210,7 @@ If you would like to get more information about a specific command or group, you
 
 NOTE: All the command examples through out the help will be shown without prefix, add whatever command prefix is configured on the server.
 By default it's a mention or `.`, but it can be configured using `configure guild prefix n!` replacing `n!` with the prefix of choice.
+You can also set a personal prefix that works everywhere with `configure user prefix n!`.
 
 You can react with 🚫 on *any* message sent by the bot to delete it.
 Exceptions to this rule include logging messages, some notifications and webhook messages.\n"]
@@ -236,6 +246,48 @@ async fn my_help(
     ho.embed_error_colour = Colour::from_rgb(255, 30, 30);
     ho.embed_success_colour = Colour::from_rgb(141, 91, 255);
 
+    let settings = if let Some(guild_id) = msg.guild_id {
+        get_guild_settings(ctx, guild_id).await
+    } else {
+        GuildSettings::default()
+    };
+
+    // The aliases of the guild are not part of the framework, so they are shown separately.
+    if let Some(alias) = settings.aliases.get(&args.message().trim().to_lowercase()) {
+        msg.channel_id
+            .send_message(ctx, |m| {
+                m.embed(|e| {
+                    e.colour(ho.embed_success_colour);
+                    e.title(args.message().trim());
+                    e.description(format!(
+                        "Alias of this guild for `{}`",
+                        alias.invocation().replace('`', "")
+                    ))
+                })
+            })
+            .await?;
+
+        let args = Args::new(&alias.command, &[Delimiter::Single(' ')]);
+        let _ = help_commands::with_embeds(ctx, msg, args, &ho, groups, owners).await;
+        return Ok(());
+    }
+
+    let show_aliases = args.is_empty() && !settings.aliases.is_empty();
     let _ = help_commands::with_embeds(ctx, msg, args, &ho, groups, owners).await;
+
+    if show_aliases {
+        let aliases = format_aliases(&settings.aliases);
+
+        msg.channel_id
+            .send_message(ctx, |m| {
+                m.embed(|e| {
+                    e.colour(ho.embed_success_colour);
+                    e.title("Aliases of this guild");
+                    e.description(aliases)
+                })
+            })
+            .await?;
+    }
+
     Ok(())
 }
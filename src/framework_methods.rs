@@ -1,7 +1,14 @@
 use crate::commands::booru::get_booru;
 use crate::commands::music::_join;
+use crate::error::{report_error, BotError};
+use crate::utils::aliases::dispatch_alias;
+use crate::utils::analytics::{record_command_usage, start_command_timer};
 use crate::utils::basic_functions::capitalize_first;
-use crate::{BooruCommands, BooruList, DatabasePool};
+use crate::utils::blocklist::is_blocked;
+use crate::utils::command_rules::{author_roles, is_command_allowed};
+use crate::utils::guild_settings::{get_guild_settings, get_user_prefix, DEFAULT_PREFIX};
+use crate::utils::tags::{arguments_after, render_tag};
+use crate::{BooruCommands, BooruList, Tokens};
 
 use serenity::{
     framework::standard::{macros::hook, Args, CommandResult, Delimiter, DispatchError, Reason},
@@ -27,9 +34,14 @@ pub async fn on_dispatch_error(
     ctx: &Context,
     msg: &Message,
     error: DispatchError,
-    _command_name: &str,
+    command_name: &str,
 ) {
-    match error {
+    // Blocked users and guilds don't get the dispatch errors either.
+    if is_blocked(ctx, msg.author.id, msg.guild_id).await {
+        return;
+    }
+
+    let error = match error {
         // Notify the user if the reason of the command failing to execute was because of
         // inssufficient arguments.
         DispatchError::NotEnoughArguments { min, given } => {
@@ -45,65 +57,96 @@ pub async fn on_dispatch_error(
                     )
                 }
             };
-            // Send the message, but supress any errors that may occur.
-            let _ = msg.channel_id.say(ctx, s).await;
+            BotError::User(s)
         }
+        DispatchError::TooManyArguments { max, given } => BotError::User(format!(
+            "This command takes at most {} arguments, but i was given {}.",
+            max, given
+        )),
         //DispatchError::IgnoredBot {} => {
         //    return;
         //},
         DispatchError::CheckFailed(_, reason) => {
             if let Reason::User(r) = reason {
-                let _ = msg.channel_id.say(ctx, r).await;
+                BotError::User(r)
+            } else {
+                return;
             }
         }
-        DispatchError::Ratelimited(x) => {
-            let _ = msg
-                .reply(
-                    ctx,
-                    format!(
-                        "You can't run this command for {} more seconds.",
-                        x.as_secs()
-                    ),
-                )
-                .await;
+        DispatchError::Ratelimited(x) => BotError::User(format!(
+            "You can't run this command for {} more seconds.",
+            x.as_secs()
+        )),
+        DispatchError::LackingPermissions(permissions) => BotError::Permission(format!(
+            "You need the {} permissions to run this command.",
+            permissions.get_permission_names().join(", ")
+        )),
+        DispatchError::OnlyForGuilds => {
+            BotError::User("This command can only be used on a guild.".to_string())
         }
         // eprint prints to stderr rather than stdout.
         _ => {
             error!("Unhandled dispatch error: {:?}", error);
             eprintln!("An unhandled dispatch error has occurred:");
             eprintln!("{:?}", error);
+            return;
         }
-    }
+    };
+
+    report_error(ctx, msg, command_name, &error).await;
 }
 
 // This function executes before a command is called.
 #[hook]
 pub async fn before(ctx: &Context, msg: &Message, cmd_name: &str) -> bool {
+    // Blocked users and guilds are ignored silently.
+    if is_blocked(ctx, msg.author.id, msg.guild_id).await {
+        return false;
+    }
+
+    let missing_key = {
+        let data_read = ctx.data.read().await;
+        let config = data_read.get::<Tokens>().unwrap();
+        config.missing_key(cmd_name)
+    };
+
+    if let Some(key) = missing_key {
+        let _ = msg
+            .reply(
+                ctx,
+                format!(
+                    "This command is disabled, as `{}` is not configured on this bot.",
+                    key
+                ),
+            )
+            .await;
+        return false;
+    }
+
     if let Some(guild_id) = msg.guild_id {
-        let pool = {
-            let data_read = ctx.data.read().await;
-            data_read.get::<DatabasePool>().unwrap().clone()
-        };
+        let settings = get_guild_settings(ctx, guild_id).await;
 
-        let disallowed_commands = sqlx::query!(
-            "SELECT disallowed_commands FROM prefixes WHERE guild_id = $1",
-            guild_id.0 as i64
-        )
-        .fetch_optional(&pool)
-        .await
-        .unwrap();
-
-        if let Some(x) = disallowed_commands {
-            if let Some(disallowed_commands) = x.disallowed_commands {
-                if disallowed_commands.contains(&cmd_name.to_string()) {
-                    let _ = msg
-                        .reply(
-                            ctx,
-                            "This command has been disabled by an administrtor of this guild.",
-                        )
-                        .await;
-                    return false;
-                }
+        if settings.is_disallowed(cmd_name) {
+            let _ = msg
+                .reply(
+                    ctx,
+                    "This command has been disabled by an administrtor of this guild.",
+                )
+                .await;
+            return false;
+        }
+
+        if !settings.command_rules.is_empty() {
+            let roles = author_roles(ctx, msg).await;
+
+            if !is_command_allowed(&settings.command_rules, cmd_name, msg.channel_id, &roles) {
+                let _ = msg
+                    .reply(
+                        ctx,
+                        "This command is not allowed here by the permissions of this guild.",
+                    )
+                    .await;
+                return false;
             }
         }
 
@@ -123,6 +166,8 @@ pub async fn before(ctx: &Context, msg: &Message, cmd_name: &str) -> bool {
     info!("Running command: {}", &cmd_name);
     debug!("Command Message Struct: {:?}", &msg);
 
+    start_command_timer(ctx, msg).await;
+
     true
 }
 
@@ -132,53 +177,103 @@ pub async fn before(ctx: &Context, msg: &Message, cmd_name: &str) -> bool {
 pub async fn after(ctx: &Context, msg: &Message, cmd_name: &str, error: CommandResult) {
     // error is the command result.
     // inform the user about an error when it happens.
-    if let Err(why) = &error {
-        error!("Error while running command {}", &cmd_name);
-        error!("{:?}", &error);
-
-        //let err = why.0.to_string();
-        if msg.channel_id.say(ctx, &why).await.is_err() {
-            error!(
-                "Unable to send messages on channel id {}",
-                &msg.channel_id.0
-            );
-        };
+    if let Err(why) = error {
+        let error = BotError::from_command_error(why);
+        debug!("Error while running command {}: {:?}", &cmd_name, &error);
+
+        record_command_usage(ctx, msg, cmd_name, Some(error.kind())).await;
+        report_error(ctx, msg, cmd_name, &error).await;
+    } else {
+        record_command_usage(ctx, msg, cmd_name, None).await;
     }
 }
 
 // Small error event that triggers when a command doesn't exist.
 #[hook]
 pub async fn unrecognised_command(ctx: &Context, msg: &Message, command_name: &str) {
-    let (pool, commands, boorus) = {
+    if is_blocked(ctx, msg.author.id, msg.guild_id).await {
+        return;
+    }
+
+    let (commands, boorus) = {
         let data_read = ctx.data.read().await;
 
-        let pool = data_read.get::<DatabasePool>().unwrap();
         let commands = data_read.get::<BooruCommands>().unwrap();
         let boorus = data_read.get::<BooruList>().unwrap();
 
-        (pool.clone(), commands.clone(), boorus.clone())
+        (commands.clone(), boorus.clone())
     };
 
     if let Some(guild_id) = msg.guild_id {
-        let disallowed_commands = sqlx::query!(
-            "SELECT disallowed_commands FROM prefixes WHERE guild_id = $1",
-            guild_id.0 as i64
-        )
-        .fetch_optional(&pool)
-        .await
-        .unwrap();
-
-        if let Some(x) = disallowed_commands {
-            if let Some(disallowed_commands) = x.disallowed_commands {
-                if disallowed_commands.contains(&"booru_command".to_string()) {
-                    let _ = msg
-                        .reply(
-                            ctx,
-                            "This command has been disabled by an administrtor of this guild.",
-                        )
-                        .await;
-                    return;
-                }
+        let settings = get_guild_settings(ctx, guild_id).await;
+
+        // Custom aliases and text commands of the guild take priority over the boorus.
+        let custom_name = command_name.to_lowercase();
+
+        let bot_id = ctx.cache.current_user_id();
+        let mut prefixes = message_prefixes(ctx, msg).await;
+        prefixes.push(format!("<@{}>", bot_id));
+        prefixes.push(format!("<@!{}>", bot_id));
+
+        let rest = arguments_after(&msg.content, &prefixes, &custom_name);
+
+        if let Some(alias) = settings.aliases.get(&custom_name) {
+            // An alias can't point to another alias, so they can never loop.
+            if !settings.aliases.contains_key(&alias.command) {
+                dispatch_alias(ctx, msg, alias, rest).await;
+            }
+
+            return;
+        }
+
+        if let Some(content) = settings.tags.get(&custom_name) {
+            let response = render_tag(content, msg, rest);
+
+            // Only the user that used the tag can be pinged by it.
+            let result = msg
+                .channel_id
+                .send_message(ctx, |m| {
+                    m.content(response);
+                    m.allowed_mentions(|am| am.empty_parse().users(vec![msg.author.id]))
+                })
+                .await;
+
+            if let Err(why) = result {
+                error!(
+                    "Unable to send the tag {} on channel id {}: {}",
+                    custom_name, &msg.channel_id.0, why
+                );
+            }
+
+            return;
+        }
+
+        if settings.is_disallowed("booru_command") {
+            let _ = msg
+                .reply(
+                    ctx,
+                    "This command has been disabled by an administrtor of this guild.",
+                )
+                .await;
+            return;
+        }
+
+        if commands.contains(command_name) && !settings.command_rules.is_empty() {
+            let roles = author_roles(ctx, msg).await;
+
+            if !is_command_allowed(
+                &settings.command_rules,
+                "booru_command",
+                msg.channel_id,
+                &roles,
+            ) {
+                let _ = msg
+                    .reply(
+                        ctx,
+                        "This command is not allowed here by the permissions of this guild.",
+                    )
+                    .await;
+                return;
             }
         }
     }
@@ -219,49 +314,41 @@ pub async fn unrecognised_command(ctx: &Context, msg: &Message, command_name: &s
     }
 }
 
+// The prefixes a message can use, the main one first.
+async fn message_prefixes(ctx: &Context, msg: &Message) -> Vec<String> {
+    // Custom per guild prefixes.
+    // If the command was invoked on a guild, use the cached settings of the guild.
+    // If the command was invoked on a dm, use the default prefix.
+    let mut prefixes = if let Some(guild_id) = msg.guild_id {
+        get_guild_settings(ctx, guild_id).await.prefixes()
+    } else {
+        vec![DEFAULT_PREFIX.to_string()]
+    };
+
+    if let Some(user_prefix) = get_user_prefix(ctx, msg.author.id).await {
+        if !user_prefix.trim().is_empty() {
+            prefixes.push(user_prefix);
+        }
+    }
+
+    prefixes
+}
+
 #[hook]
 pub async fn dynamic_prefix(ctx: &Context, msg: &Message) -> Option<String> {
-    // Custom per guild prefixes.
     //info!("Dynamic prefix call.");
-    // obtain the guild id of the command message.
-    let guild_id = &msg.guild_id;
-
-    let p;
-
-    // If the command was invoked on a guild
-    if let Some(id) = guild_id {
-        // Get the real guild id, and the i64 type becaues that's what postgre uses.
-        let gid = id.0 as i64;
-        let pool = {
-            // Open the context data lock in read mode.
-            let data = ctx.data.read().await;
-            // it's safe to clone PgPool
-            data.get::<DatabasePool>().unwrap().clone()
-        };
+    let mut prefixes = message_prefixes(ctx, msg).await;
 
-        // Obtain the database connection for the data.
-        // Obtain the configured prefix from the database
-        match sqlx::query!("SELECT prefix FROM prefixes WHERE guild_id = $1", gid)
-            .fetch_optional(&pool)
-            .await
-        {
-            Err(why) => {
-                error!("Could not query database: {}", why);
-                p = ".".to_string();
-            }
-            Ok(db_prefix) => {
-                p = if let Some(result) = db_prefix {
-                    result.prefix.unwrap_or_else(|| ".".to_string())
-                } else {
-                    ".".to_string()
-                };
-            }
-        }
+    // The framework only accepts a single dynamic prefix, so return the one the message uses.
+    // Longer prefixes are checked first, so `a!!` isn't mistaken for `a!`
+    let content = msg.content.to_lowercase();
+    let main_prefix = prefixes[0].to_string();
+    prefixes.sort_by_key(|p| std::cmp::Reverse(p.len()));
 
-    // If the command was invoked on a dm
-    } else {
-        p = ".".to_string();
-    };
+    let p = prefixes
+        .into_iter()
+        .find(|p| content.starts_with(&p.to_lowercase()))
+        .unwrap_or(main_prefix);
 
     // dynamic_prefix() needs an Option<String>
     Some(p)
@@ -1,10 +1,27 @@
-use crate::{notifications::TwitchStreamData, Booru, ConfigurationData};
-
-use std::{collections::HashSet, sync::Arc, time::Instant};
+use crate::{
+    utils::{
+        blocklist::Blocklist,
+        guild_settings::SettingsCache,
+        metrics::Metrics,
+        scheduler::Scheduler,
+        shutdown::Shutdown,
+        tasks::TaskSupervisor,
+        twitch::{TwitchCache, TwitchStreamData},
+    },
+    Booru, ConfigurationData,
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Instant,
+};
 
 use tokio::sync::{Mutex, RwLock};
 
-use serenity::{client::bridge::gateway::ShardManager, prelude::TypeMapKey};
+use serenity::{
+    client::bridge::gateway::ShardManager, framework::StandardFramework, prelude::TypeMapKey,
+};
 
 use darkredis::ConnectionPool as RedisPool;
 use lavalink_rs::LavalinkClient;
@@ -22,8 +39,18 @@ pub struct BooruList; // This is a HashSet of all the boorus found on "boorus.js
 pub struct BooruCommands; // This is a HashSet of all the commands/aliases found on "boorus.json"
 pub struct Lavalink; //  This is the struct for the lavalink client.
 pub struct SentTwitchStreams; //  This is the struct for the stream data that has already been sent.
+pub struct TwitchApiCache; // This is the app token and the cached games and users of the Twitch API.
 pub struct Uptime; //  This is for the startup time of the bot.
 pub struct OsuHttpClient; // This is the HTTP client to comunicate with osu! API v2.
+pub struct FrameworkContainer; // The command framework, so slash commands can be dispatched to it.
+pub struct GuildSettingsCache; // This is a cache of the prefix and disabled commands of every guild.
+pub struct UserPrefixes; // This is a HashMap of the personal prefix of every user that configured one.
+pub struct ShutdownSignal; // This keeps track of the background tasks, so they can be stopped on shutdown.
+pub struct CommandStartTimes; // This is the moment every running command started, by message id.
+pub struct BlockedTargets; // This is the list of users and guilds that are not allowed to use the bot.
+pub struct BotMetrics; // This is the runtime metrics that are exposed on the web server.
+pub struct BackgroundTasks; // This is the status of every periodic background task.
+pub struct JobScheduler; // This is the queue of the reminders and unmutes that are due soon.
 
 impl TypeMapKey for ShardManagerContainer {
     type Value = Arc<Mutex<ShardManager>>;
@@ -61,6 +88,10 @@ impl TypeMapKey for SentTwitchStreams {
     type Value = Arc<RwLock<Vec<TwitchStreamData>>>;
 }
 
+impl TypeMapKey for TwitchApiCache {
+    type Value = Arc<RwLock<TwitchCache>>;
+}
+
 impl TypeMapKey for Uptime {
     type Value = Arc<Instant>;
 }
@@ -68,3 +99,39 @@ impl TypeMapKey for Uptime {
 impl TypeMapKey for OsuHttpClient {
     type Value = Arc<RwLock<ReqwestClient>>;
 }
+
+impl TypeMapKey for FrameworkContainer {
+    type Value = Arc<StandardFramework>;
+}
+
+impl TypeMapKey for GuildSettingsCache {
+    type Value = Arc<RwLock<SettingsCache>>;
+}
+
+impl TypeMapKey for UserPrefixes {
+    type Value = Arc<RwLock<HashMap<u64, String>>>;
+}
+
+impl TypeMapKey for ShutdownSignal {
+    type Value = Shutdown;
+}
+
+impl TypeMapKey for CommandStartTimes {
+    type Value = Arc<RwLock<HashMap<u64, Instant>>>;
+}
+
+impl TypeMapKey for BlockedTargets {
+    type Value = Arc<RwLock<Blocklist>>;
+}
+
+impl TypeMapKey for BotMetrics {
+    type Value = Arc<RwLock<Metrics>>;
+}
+
+impl TypeMapKey for BackgroundTasks {
+    type Value = TaskSupervisor;
+}
+
+impl TypeMapKey for JobScheduler {
+    type Value = Scheduler;
+}
@@ -15,14 +15,18 @@ extern crate tracing;
 #[macro_use]
 extern crate serde;
 
+pub mod api;
 pub mod commands; // Load the commands module
 pub mod config;
+pub mod error;
 pub mod events;
+pub mod eventsub;
 pub mod framework;
 pub mod framework_methods;
 pub mod global_data;
 pub mod logging;
 pub mod notifications;
+pub mod slash_commands;
 pub mod utils; // Load the utils module
 
 use crate::config::*;
@@ -31,14 +35,17 @@ use crate::framework::*;
 use crate::framework_methods::*;
 use crate::global_data::*;
 
+use utils::blocklist::load_blocklist;
 use utils::database::*; // Obtain the get_database function from the utilities. // Obtain the capitalize_first function from the utilities.
+use utils::guild_settings::SettingsCache;
+use utils::metrics::Metrics;
+use utils::scheduler::Scheduler;
+use utils::shutdown::{graceful_shutdown, wait_for_signal, Shutdown};
+use utils::tasks::TaskSupervisor;
+use utils::twitch::TwitchCache;
 
 use std::{
-    collections::HashSet, // Low cost indexable lists.
-    // For saving / reading files
-    fs::File,
-    io::prelude::*,
-    //
+    collections::{HashMap, HashSet}, // Low cost indexable lists.
     // For having refferences between threads
     sync::Arc,
     time::Instant,
@@ -52,6 +59,7 @@ use tracing_log::LogTracer;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 //use tracing_futures::Instrument;
 
+use clap::{App, Arg};
 use lavalink_rs::LavalinkClient;
 use reqwest::header;
 use songbird::SerenityInit;
@@ -61,16 +69,10 @@ use serenity::{
     client::ClientBuilder,
     framework::standard::StandardFramework,
     http::Http,
-    model::{gateway::GatewayIntents, id::UserId},
+    model::gateway::GatewayIntents,
     prelude::RwLock,
 };
 
-// Because "boorus.json" boorus key is a list of values.
-#[derive(Debug, Deserialize)]
-struct BooruRaw {
-    boorus: Vec<Booru>,
-}
-
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OsuTokenSend {
     pub client_id: u16,
@@ -93,13 +95,27 @@ pub struct OsuTokenRecv {
 #[tokio::main(worker_threads = 8)]
 #[instrument]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Opens the config.toml file and reads it's content
-    let mut file = File::open("config.toml")?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    let matches = App::new("RoboArc")
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(
+            Arg::with_name("migrate-only")
+                .long("migrate-only")
+                .help("Applies the pending database migrations and exits."),
+        )
+        .get_matches();
+
+    // Only the database is needed to migrate, so it can run where nothing else is configured.
+    if matches.is_present("migrate-only") {
+        let database = read_database_configuration()?;
+        let pg_pool = obtain_postgres_pool(&database.postgres_url).await?;
+        run_migrations(&pg_pool).await?;
+
+        println!("The database migrations have been applied.");
+        return Ok(());
+    }
 
     // gets the data from the config.toml file
-    let configuration = toml::from_str::<ConfigurationData>(&contents).unwrap();
+    let configuration = read_configuration()?;
 
     if configuration.enable_tracing {
         LogTracer::init()?;
@@ -129,6 +145,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         info!("Subscriber initialized.");
     }
 
+    // The schema needs to be up to date before anything touches the database.
+    let pg_pool = obtain_postgres_pool(&configuration.database.postgres_url).await?;
+    run_migrations(&pg_pool).await?;
+
     // obtains the discord token from the config
     let bot_token = configuration.discord.to_string();
     // Defines a client with the token obtained from the config.toml file.
@@ -164,7 +184,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 .with_whitespace(true) // Allow a whitespace between the prefix and the command name.
                 .owners(owners) // Defines the owners, this can be later used to make owner specific commands.
                 .case_insensitivity(true) // Makes the prefix and command be case insensitive.
-                .blocked_users(vec![UserId(135423120268984330)].into_iter().collect())
         })
         .on_dispatch_error(on_dispatch_error)
         .unrecognised_command(unrecognised_command)
@@ -186,6 +205,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .group(&CONFIGURATION_GROUP) // Load `Configuration` command group
         .help(&MY_HELP); // Load the custom help command.
 
+    // The framework is shared, so slash command interactions can be dispatched to it.
+    let std_framework = Arc::new(std_framework);
+
     let mut client = ClientBuilder::new(&bot_token, {
         let mut intents = GatewayIntents::all();
         //intents.remove(GatewayIntents::GUILD_PRESENCES);
@@ -197,7 +219,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         run_loops: Mutex::new(true),
     })
     .raw_event_handler(logging::events::RawHandler)
-    .framework(std_framework)
+    .framework_arc(std_framework.clone())
     .register_songbird()
     .application_id(bot_id.0)
     .await?;
@@ -209,28 +231,45 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let mut data = client.data.write().await;
 
         // Add the databases connection pools to the data.
-        let pg_pool = obtain_postgres_pool().await?;
         data.insert::<DatabasePool>(pg_pool.clone());
 
-        let redis_pool = obtain_redis_pool().await?;
+        let redis_pool = obtain_redis_pool(&configuration.database.redis_url).await?;
         data.insert::<CachePool>(redis_pool);
 
         // Add the shard manager to the data.
         data.insert::<ShardManagerContainer>(Arc::clone(&client.shard_manager));
 
+        // Add the command framework to the data.
+        data.insert::<FrameworkContainer>(Arc::clone(&std_framework));
+
         // Add the tokens to the data.
         data.insert::<Tokens>(Arc::new(configuration.clone()));
 
         // Add the sent streams.
         data.insert::<SentTwitchStreams>(Arc::new(RwLock::new(Vec::new())));
+        data.insert::<TwitchApiCache>(Arc::new(RwLock::new(TwitchCache::default())));
 
         data.insert::<Uptime>(Arc::new(Instant::now()));
 
-        {
+        data.insert::<ShutdownSignal>(Shutdown::default());
+
+        data.insert::<BackgroundTasks>(TaskSupervisor::default());
+
+        data.insert::<JobScheduler>(Scheduler::default());
+
+        data.insert::<CommandStartTimes>(Arc::new(RwLock::new(HashMap::new())));
+
+        data.insert::<BotMetrics>(Arc::new(RwLock::new(Metrics::default())));
+
+        // The guild settings get loaded lazily, as messages are sent.
+        data.insert::<GuildSettingsCache>(Arc::new(RwLock::new(SettingsCache::default())));
+
+        // Without the lavalink section, the music commands are disabled.
+        if let Some(lavalink) = &configuration.lavalink {
             // T 0 D 0: get the real shard amount.
-            let host = configuration.lavalink.host;
-            let port = configuration.lavalink.port;
-            let password = configuration.lavalink.password;
+            let host = &lavalink.host;
+            let port = lavalink.port;
+            let password = &lavalink.password;
 
             let lava_client = LavalinkClient::builder(bot_id.0)
                 .set_host(host.to_string())
@@ -244,26 +283,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         }
 
         {
-            // Open the boorus.json file
-            let mut file = File::open("boorus.json").unwrap();
-            // read the content of the file to a string.
-            let mut raw_data = String::new();
-            file.read_to_string(&mut raw_data).unwrap();
-
-            // Serialize the json string into a BooruRaw struct.
-            let boorus: BooruRaw =
-                serde_json::from_str(raw_data.as_str()).expect("JSON was not well-formatted");
-
-            // Add every command on the data to a HashSet
-            let mut all_names = HashSet::new();
-            for boorus_list in boorus.boorus.iter() {
-                for booru in boorus_list.names.iter() {
-                    all_names.insert(booru.to_owned());
-                }
-            }
+            // Read the boorus.json file and obtain every command of the boorus.
+            let (boorus, all_names) = read_boorus()?;
 
             // Add the json file struct and the HashSet of all the commands to the data.
-            data.insert::<BooruList>(Arc::new(boorus.boorus));
+            data.insert::<BooruList>(Arc::new(boorus));
             data.insert::<BooruCommands>(Arc::new(all_names));
         }
 
@@ -288,11 +312,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         }
 
         {
+            // obtain the personal prefixes of every user from the db.
+            let raw_user_prefixes = sqlx::query!("SELECT user_id, prefix FROM user_prefixes")
+                .fetch_all(&pg_pool)
+                .await?;
+
+            let mut user_prefixes = HashMap::new();
+            for row in raw_user_prefixes {
+                user_prefixes.insert(row.user_id as u64, row.prefix);
+            }
+
+            data.insert::<UserPrefixes>(Arc::new(RwLock::new(user_prefixes)));
+        }
+
+        {
+            // The blocked users and guilds are checked on every command.
+            let blocklist = load_blocklist(&pg_pool).await?;
+            data.insert::<BlockedTargets>(Arc::new(RwLock::new(blocklist)));
+        }
+
+        // Without the osu section, the client has no token and the new osu! commands are disabled.
+        if let Some(osu) = &configuration.osu {
             let base_client = reqwest::Client::new();
 
             let send_data = OsuTokenSend {
-                client_id: configuration.osu.client_id,
-                client_secret: configuration.osu.client_secret,
+                client_id: osu.client_id,
+                client_secret: osu.client_secret.to_string(),
                 grant_type: "client_credentials".to_string(),
                 scope: "public".to_string(),
             };
@@ -318,9 +363,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 .build()?;
 
             data.insert::<OsuHttpClient>(Arc::new(RwLock::new(client)));
+        } else {
+            // Only a placeholder, the new osu! commands are disabled by `missing_key` without `[osu]`
+            data.insert::<OsuHttpClient>(Arc::new(RwLock::new(reqwest::Client::new())));
         }
     }
 
+    // Reload config.toml and boorus.json when a SIGHUP is received.
+    #[cfg(unix)]
+    {
+        let data = client.data.clone();
+        tokio::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(x) => x,
+                Err(why) => {
+                    error!("Could not listen for SIGHUP: {}", why);
+                    return;
+                }
+            };
+
+            while hangup.recv().await.is_some() {
+                match reload_configuration(&data).await {
+                    Ok(changes) if changes.is_empty() => {
+                        info!("Configuration reloaded, nothing changed.")
+                    }
+                    Ok(changes) => info!("Configuration reloaded: {}", changes.join("; ")),
+                    Err(why) => error!("Could not reload the configuration: {}", why),
+                }
+            }
+        });
+    }
+
+    // Stop everything cleanly on SIGTERM or Ctrl+C.
+    {
+        let data = client.data.clone();
+        let shard_manager = client.shard_manager.clone();
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            graceful_shutdown(data, shard_manager).await;
+        });
+    }
+
     // start listening for events by starting a single shard
     if let Err(why) = client.start_autosharded().await {
         eprintln!("An error occurred while running the client: {:?}", why);
@@ -1,67 +1,44 @@
-use crate::utils::booru::{SAFE_BANLIST, UNSAFE_BANLIST};
+use crate::utils::booru::{fetch_posts, Post};
+use crate::utils::feeds::{
+    self, feed_item_embed, fetch_feed, prune_sent_items, MAX_ITEMS_PER_POLL,
+};
+use crate::utils::scheduler::{load_upcoming_jobs, run_scheduler};
+use crate::utils::sent_posts::{already_sent, mark_as_sent, prune_sent_posts};
+use crate::utils::tasks::spawn_periodic;
+use crate::utils::twitch::{Helix, TwitchStreamData};
 
+use crate::eventsub::sync_eventsub_subscriptions;
 use crate::global_data::*;
-use crate::{OsuTokenRecv, OsuTokenSend};
+use crate::{Booru, OsuTokenRecv, OsuTokenSend};
 
 use std::{
+    collections::{BTreeMap, HashMap},
     sync::Arc,
-    //collections::HashMap,
     time::Duration,
 };
 
-use reqwest::{header::*, Client as ReqwestClient, Url};
-use serde::Deserialize;
+use reqwest::header::*;
 
 use serenity::{
-    model::{channel::Embed, id::ChannelId},
+    builder::CreateEmbed,
+    model::{
+        channel::Embed,
+        id::{ChannelId, MessageId},
+        webhook::Webhook,
+    },
     prelude::{Context, RwLock},
 };
 
-#[derive(Deserialize)]
-pub struct Post {
-    sample_url: String,
-    pub md5: String,
-    id: u64,
-    tags: String,
-}
-
-#[derive(Deserialize, Debug, Clone, PartialEq)]
-pub struct TwitchStreamData {
-    user_id: String,
-    user_name: String,
-    game_id: String,
-    title: String,
-}
-
-#[derive(Deserialize, Debug)]
-struct TwitchUser {
-    profile_image_url: String,
-}
-
-#[derive(Deserialize, Debug)]
-struct TwitchGame {
-    name: Option<String>,
-}
-
-#[derive(Deserialize, Debug)]
-struct TwitchGameData {
-    data: Vec<TwitchGame>,
-}
-
-#[derive(Deserialize, Debug)]
-struct TwitchStreams {
-    data: Vec<TwitchStreamData>,
-}
-
-#[derive(Deserialize, Debug)]
-struct TwitchUserData {
-    data: Vec<TwitchUser>,
-}
-
 async fn update_osu_token(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::Error>> {
-    let configuration = {
+    let osu = {
         let data_read = ctx.data.read().await;
-        data_read.get::<Tokens>().unwrap().clone()
+        let configuration = data_read.get::<Tokens>().unwrap();
+
+        if let Some(x) = &configuration.osu {
+            x.clone()
+        } else {
+            return Ok(());
+        }
     };
 
     let old_client = {
@@ -72,8 +49,8 @@ async fn update_osu_token(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::E
     };
 
     let send_data = OsuTokenSend {
-        client_id: configuration.osu.client_id,
-        client_secret: configuration.osu.client_secret.to_string(),
+        client_id: osu.client_id,
+        client_secret: osu.client_secret.to_string(),
         grant_type: "client_credentials".to_string(),
         scope: "public".to_string(),
     };
@@ -108,10 +85,28 @@ async fn update_osu_token(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::E
     Ok(())
 }
 
-async fn check_new_posts(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::Error>> {
-    let pool = {
+async fn update_twitch_token(
+    ctx: Arc<Context>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(helix) = Helix::new(&ctx).await {
+        helix.refresh_token_if_needed().await?;
+    }
+
+    Ok(())
+}
+
+async fn check_new_posts(
+    ctx: Arc<Context>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (pool, boorus, allow_hooks) = {
         let data_read = ctx.data.read().await;
-        data_read.get::<DatabasePool>().unwrap().clone()
+        let config = data_read.get::<Tokens>().unwrap();
+
+        (
+            data_read.get::<DatabasePool>().unwrap().clone(),
+            data_read.get::<BooruList>().unwrap().clone(),
+            config.webhook_notifications,
+        )
     };
 
     let data = sqlx::query!("SELECT * FROM new_posts")
@@ -119,94 +114,201 @@ async fn check_new_posts(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::Er
         .await?;
 
     for i in data {
-        let base_url = i.booru_url;
-        let tags = i.tags;
-        let webhooks = i.webhook.unwrap_or_default();
-        let channels = i.channel_id.unwrap_or_default();
-        let mut md5s = i.sent_md5.unwrap_or_default();
-
-        if base_url == "yande.re" {
-            let url = Url::parse_with_params(
-                "https://yande.re/post/index.json",
-                &[("tags", &tags), ("limit", &"100".to_string())],
-            )?;
-            let resp = reqwest::get(url).await?.json::<Vec<Post>>().await?;
-
-            for post in resp {
-                if !md5s.contains(&post.md5) {
-                    for channel in &channels {
-                        let real_channel = ChannelId(*channel as u64).to_channel(&*ctx).await?;
-                        let mut is_unsafe = false;
-
-                        if real_channel.is_nsfw() || real_channel.guild().is_none() {
-                            for tag in post.tags.split(' ').into_iter() {
-                                if UNSAFE_BANLIST.contains(&tag) {
-                                    is_unsafe = true;
-                                }
-                            }
-                        } else {
-                            for tag in post.tags.split(' ').into_iter() {
-                                if SAFE_BANLIST.contains(&tag) {
-                                    is_unsafe = true;
-                                }
-                            }
-                        }
-
-                        if !is_unsafe {
-                            if let Err(why) = ChannelId(*channel as u64)
-                                .send_message(&ctx, |m| {
-                                    m.embed(|e| {
-                                        e.title("Original Post");
-                                        e.url(format!("https://yande.re/post/show/{}", post.id));
-                                        e.image(post.sample_url.clone())
-                                    })
-                                })
-                                .await
-                            {
-                                eprintln!("Error while sending message >>> {}", why);
-                            };
-                        }
+        let booru = if let Some(x) = boorus.iter().find(|b| b.url == i.booru_url) {
+            x
+        } else {
+            warn!(
+                "The booru {} of a subscription is not on boorus.json anymore.",
+                i.booru_url
+            );
+            continue;
+        };
+
+        let posts = match fetch_posts(booru, &i.tags).await {
+            Ok(x) => x,
+            Err(why) => {
+                error!(
+                    "Error obtaining the posts of {} for `{}`: {}",
+                    booru.url, i.tags, why
+                );
+                continue;
+            }
+        };
+
+        let post_hashes = posts.iter().map(|post| post.key()).collect::<Vec<_>>();
+        let sent = already_sent(&pool, i.id, &post_hashes).await?;
+        let new_posts = posts
+            .iter()
+            .filter(|post| !sent.contains(&post.key()))
+            .collect::<Vec<_>>();
+
+        if new_posts.is_empty() {
+            mark_as_sent(&pool, i.id, &post_hashes).await?;
+            continue;
+        }
+
+        // Whether every channel and webhook allows nsfw posts is only checked once per subscription.
+        let mut channels = Vec::new();
+        for channel_id in i.channel_id.unwrap_or_default() {
+            let channel_id = ChannelId(channel_id as u64);
+
+            if let Ok(channel) = channel_id.to_channel(&*ctx).await {
+                let nsfw = channel.is_nsfw();
+                channels.push((channel_id, nsfw || channel.guild().is_none()));
+            }
+        }
+
+        let mut webhooks = Vec::new();
+        if allow_hooks {
+            for webhook in i.webhook.unwrap_or_default() {
+                if let Some(hook) = webhook_from_url(&ctx, &webhook).await {
+                    let nsfw = hook
+                        .channel_id
+                        .to_channel(&*ctx)
+                        .await
+                        .map_or(false, |c| c.is_nsfw());
+                    webhooks.push((hook, nsfw));
+                }
+            }
+        }
+
+        // The boorus return the newest posts first, so they are sent in reverse.
+        for post in new_posts.iter().rev() {
+            if post.image_url().is_some() {
+                for (channel_id, nsfw) in &channels {
+                    if !post.is_allowed(*nsfw) {
+                        continue;
                     }
 
-                    let allow_hooks = {
-                        let data_read = ctx.data.read().await;
-                        let config = data_read.get::<Tokens>().unwrap();
-                        config.webhook_notifications
+                    if let Err(why) = channel_id
+                        .send_message(&ctx, |m| m.embed(|e| post_embed(e, booru, post)))
+                        .await
+                    {
+                        error!("Error while sending message >>> {}", why);
                     };
+                }
 
-                    if allow_hooks {
-                        for webhook in &webhooks {
-                            let mut split = webhook.split('/');
-                            let id = split.nth(5).unwrap().parse::<u64>()?;
-                            let token = split.next().unwrap();
-
-                            let embed = Embed::fake(|e| {
-                                e.title("Original Post");
-                                e.url(format!("https://yande.re/post/show/{}", post.id));
-                                e.image(post.sample_url.clone())
-                            });
-
-                            if let Ok(hook) = &ctx.http.get_webhook_with_token(id, token).await {
-                                hook.execute(&ctx.http, false, |m| m.embeds(vec![embed]))
-                                    .await?;
-                            }
-                        }
+                for (hook, nsfw) in &webhooks {
+                    if !post.is_allowed(*nsfw) {
+                        continue;
                     }
 
-                    md5s.push(post.md5);
+                    let embed = Embed::fake(|e| post_embed(e, booru, post));
 
-                    sqlx::query!(
-                        "UPDATE new_posts SET sent_md5 = $1 WHERE booru_url = $2 AND tags = $3",
-                        &md5s,
-                        &base_url,
-                        &tags
-                    )
-                    .execute(&pool)
-                    .await?;
+                    if let Err(why) = hook
+                        .execute(&ctx.http, false, |m| m.embeds(vec![embed]))
+                        .await
+                    {
+                        error!("Error while executing webhook >>> {}", why);
+                    }
                 }
             }
         }
+
+        mark_as_sent(&pool, i.id, &post_hashes).await?;
     }
+
+    Ok(())
+}
+
+async fn prune_old_sent_posts(
+    ctx: Arc<Context>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let pruned = prune_sent_posts(&pool).await?;
+    debug!("Pruned {} old sent posts.", pruned);
+
+    let pruned = prune_sent_items(&pool).await?;
+    debug!("Pruned {} old sent feed items.", pruned);
+
+    Ok(())
+}
+
+fn post_embed<'a>(e: &'a mut CreateEmbed, booru: &Booru, post: &Post) -> &'a mut CreateEmbed {
+    e.title("Original Post");
+    e.url(format!("{}{}", booru.post_url, post.id));
+    e.image(post.image_url().unwrap_or_default())
+}
+
+async fn check_feeds(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (pool, allow_hooks) = {
+        let data_read = ctx.data.read().await;
+        let config = data_read.get::<Tokens>().unwrap();
+
+        (
+            data_read.get::<DatabasePool>().unwrap().clone(),
+            config.webhook_notifications,
+        )
+    };
+
+    let data = sqlx::query!("SELECT id, channel_id, webhook, url, filter FROM feed_subscriptions")
+        .fetch_all(&pool)
+        .await?;
+
+    // Every feed is only requested once, even if many channels are subscribed to it.
+    let mut subscriptions = BTreeMap::new();
+    for i in data {
+        subscriptions
+            .entry(i.url.clone())
+            .or_insert_with(Vec::new)
+            .push(i);
+    }
+
+    for (url, subscriptions) in subscriptions {
+        let feed = match fetch_feed(&url).await {
+            Ok(x) => x,
+            Err(why) => {
+                warn!("Error obtaining the feed {}: {}", url, why);
+                continue;
+            }
+        };
+
+        let guids = feed.guids();
+
+        for i in subscriptions {
+            let sent = feeds::already_sent(&pool, i.id, &guids).await?;
+            let new_items = feed
+                .items
+                .iter()
+                .filter(|item| !sent.contains(&item.guid))
+                .filter(|item| item.matches(i.filter.as_deref()))
+                .collect::<Vec<_>>();
+
+            // The items are sorted from the oldest, so the newest ones are the last.
+            let skip = new_items.len().saturating_sub(MAX_ITEMS_PER_POLL);
+
+            // The items are sent with the bot when the webhook is not available.
+            let hook = match &i.webhook {
+                Some(webhook) if allow_hooks => webhook_from_url(&ctx, webhook).await,
+                _ => None,
+            };
+
+            for item in new_items.into_iter().skip(skip) {
+                if let Some(hook) = &hook {
+                    let embed = Embed::fake(|e| feed_item_embed(e, &feed, item));
+
+                    if let Err(why) = hook
+                        .execute(&ctx.http, false, |m| m.embeds(vec![embed]))
+                        .await
+                    {
+                        error!("Error while executing webhook >>> {}", why);
+                    }
+                } else if let Err(why) = ChannelId(i.channel_id as u64)
+                    .send_message(&ctx, |m| m.embed(|e| feed_item_embed(e, &feed, item)))
+                    .await
+                {
+                    error!("Error while sending message >>> {}", why);
+                }
+            }
+
+            feeds::mark_as_sent(&pool, i.id, &guids).await?;
+        }
+    }
+
     Ok(())
 }
 
@@ -223,270 +325,429 @@ async fn check_changes(
     false
 }
 
-async fn check_twitch_livestreams(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::Error>> {
-    let (pool, sent_streams, token, client_id) = {
+async fn check_twitch_livestreams(
+    ctx: Arc<Context>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    check_twitch_streams(&ctx, None).await?;
+
+    Ok(())
+}
+
+/// Checks the tracked streams, or only one of them, and sends, updates or ends their
+/// notifications.
+/// Returns how many of the checked streams are live.
+pub async fn check_twitch_streams(
+    ctx: &Arc<Context>,
+    only_streamer: Option<&str>,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let ctx = Arc::clone(ctx);
+
+    let (pool, sent_streams, allow_hooks) = {
         let data_read = ctx.data.read().await;
 
         let tokens = data_read.get::<Tokens>().unwrap();
         let pool = data_read.get::<DatabasePool>().unwrap();
         let sent_streams = data_read.get::<SentTwitchStreams>().unwrap();
 
-        let token = tokens.twitch.to_string();
-        let client_id = tokens.twitch_client_id.to_string();
+        (
+            pool.clone(),
+            sent_streams.clone(),
+            tokens.webhook_notifications,
+        )
+    };
 
-        (pool.clone(), sent_streams.clone(), token, client_id)
+    // The twitch notifications are disabled when twitch is not configured.
+    let helix = if let Some(x) = Helix::new(&ctx).await {
+        x
+    } else {
+        return Ok(0);
     };
 
+    let lock = helix.notifications_lock().await;
+    let _guard = lock.lock().await;
+
     let data = sqlx::query!("SELECT * FROM streamers")
         .fetch_all(&pool)
-        .await?;
+        .await?
+        .into_iter()
+        .filter(|i| only_streamer.map_or(true, |s| s.eq_ignore_ascii_case(&i.streamer)))
+        .collect::<Vec<_>>();
+
+    if data.is_empty() {
+        return Ok(0);
+    }
+
+    let logins = data
+        .iter()
+        .map(|i| i.streamer.to_lowercase())
+        .collect::<Vec<_>>();
+    let live_streams = helix
+        .get_streams(&logins)
+        .await?
+        .into_iter()
+        .map(|s| (s.user_login.to_lowercase(), s))
+        .collect::<HashMap<_, _>>();
+
+    // The games and profile pictures are cached, so only the new ones get requested.
+    let game_ids = live_streams
+        .values()
+        .map(|s| s.game_id.clone())
+        .collect::<Vec<_>>();
+    let user_ids = live_streams
+        .values()
+        .map(|s| s.user_id.clone())
+        .collect::<Vec<_>>();
+    let game_names = helix.game_names(&game_ids).await?;
+    let profile_images = helix.profile_images(&user_ids).await?;
+
+    let mut live = 0;
 
     for i in data {
-        let reqwest = ReqwestClient::new();
-        let url = Url::parse_with_params(
-            "https://api.twitch.tv/helix/streams",
-            &[("user_login", &i.streamer)],
-        )?;
-        let mut headers = HeaderMap::new();
-        headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
-        headers.insert("Client-ID", client_id.to_string().parse().unwrap());
-
-        let resp = match reqwest
-            .get(url)
-            .headers(headers.clone())
-            .send()
-            .await?
-            .json::<TwitchStreams>()
-            .await
-        {
-            Ok(x) => x,
-            Err(why) => {
-                error!("Error quering Helix API: {} | {}", &why, &i.streamer);
-                continue;
-            }
+        let stream = live_streams.get(&i.streamer.to_lowercase()).cloned();
+        if stream.is_some() {
+            live += 1;
+        }
+
+        // Nothing is sent for the streams that are still offline, or still live without changes.
+        let changed = match &stream {
+            Some(stream) if i.is_live => check_changes(stream, Arc::clone(&sent_streams)).await,
+            Some(_) => true,
+            None => i.is_live,
         };
+        if !changed {
+            continue;
+        }
 
-        let stream_data = resp.data;
-        if !stream_data.is_empty() && i.is_live {
-            if check_changes(&stream_data[0], Arc::clone(&sent_streams)).await {
-                let data = sqlx::query!(
-                    "SELECT * FROM streamer_notification_channel WHERE streamer = $1",
-                    &i.streamer
-                )
-                .fetch_all(&pool)
-                .await?;
+        let channels = sqlx::query!(
+            "SELECT * FROM streamer_notification_channel WHERE streamer = $1",
+            &i.streamer
+        )
+        .fetch_all(&pool)
+        .await?;
 
-                for notification_place in data {
-                    let url = format!(
-                        "https://api.twitch.tv/helix/games?id={}",
-                        stream_data[0].game_id
-                    );
-                    let game_resp = reqwest
-                        .get(&url)
-                        .headers(headers.clone())
-                        .send()
-                        .await?
-                        .json::<TwitchGameData>()
-                        .await?;
+        // Webhook notifications are only sent when they are enabled on the configuration.
+        let webhooks = if allow_hooks {
+            sqlx::query!(
+                "SELECT * FROM streamer_notification_webhook WHERE streamer = $1",
+                &i.streamer
+            )
+            .fetch_all(&pool)
+            .await?
+        } else {
+            Vec::new()
+        };
 
-                    let url = format!(
-                        "https://api.twitch.tv/helix/users?id={}",
-                        stream_data[0].user_id
-                    );
-                    let user_resp = reqwest
-                        .get(&url)
-                        .headers(headers.clone())
-                        .send()
-                        .await?
-                        .json::<TwitchUserData>()
-                        .await?;
+        match (stream, i.is_live) {
+            (Some(stream), true) => {
+                let (game_name, profile_image_url) =
+                    stream_details(&stream, &game_names, &profile_images);
 
-                    let game_name = game_resp.data[0]
-                        .name
-                        .clone()
-                        .unwrap_or_else(|| "No Game".to_string());
-                    let streamer_name = notification_place.streamer.clone();
+                for notification_place in channels {
+                    let description = if !notification_place.use_default {
+                        notification_place.live_message.clone()
+                    } else {
+                        i.live_message.clone()
+                    };
 
                     if let Ok(mut message) = ctx
                         .http
                         .get_message(
-                            notification_place.channel_id.unwrap() as u64,
-                            notification_place.message_id.unwrap() as u64,
+                            notification_place.channel_id.unwrap_or(0) as u64,
+                            notification_place.message_id.unwrap_or(0) as u64,
                         )
                         .await
                     {
                         let _ = message
                             .edit(&*ctx, |m| {
-                                if let Some(role_id) = notification_place.role_id.to_owned() {
+                                if let Some(role_id) = notification_place.role_id {
                                     m.content(format!("<@&{}>", role_id));
                                 }
                                 m.embed(|e| {
-                                    if !notification_place.use_default {
-                                        e.description(notification_place.live_message.unwrap());
-                                    } else {
-                                        e.description(i.live_message.clone().unwrap());
-                                    }
-                                    e.author(|a| {
-                                        a.name(&i.streamer);
-                                        a.icon_url(&user_resp.data[0].profile_image_url);
-                                        a.url(format!("https://www.twitch.tv/{}", &i.streamer))
-                                    });
-                                    e.url(format!("https://www.twitch.tv/{}", &i.streamer));
-                                    e.title(stream_data[0].title.to_string());
-                                    e.field("Game", game_name, true)
+                                    live_embed(
+                                        e,
+                                        &i.streamer,
+                                        description.unwrap_or_default(),
+                                        &stream,
+                                        &game_name,
+                                        &profile_image_url,
+                                    )
                                 })
                             })
                             .await;
                     }
+                }
+
+                for notification_place in webhooks {
+                    let description = if !notification_place.use_default.unwrap_or(false) {
+                        notification_place.live_message.clone()
+                    } else {
+                        i.live_message.clone()
+                    };
 
-                    let sent_stream_data = {
-                        let new_vec = sent_streams.read().await;
-                        new_vec.clone()
+                    let message_id = if let Some(x) = notification_place.message_id {
+                        MessageId(x as u64)
+                    } else {
+                        continue;
                     };
-                    for (index, val) in sent_stream_data.iter().enumerate() {
-                        if val.user_name == streamer_name {
-                            sent_streams.write().await.remove(index);
-                        }
+
+                    let embed = Embed::fake(|e| {
+                        live_embed(
+                            e,
+                            &i.streamer,
+                            description.unwrap_or_default(),
+                            &stream,
+                            &game_name,
+                            &profile_image_url,
+                        )
+                    });
+
+                    if let Some(hook) = webhook_from_url(&ctx, &notification_place.webhook).await {
+                        let _ = hook
+                            .edit_message(&ctx.http, message_id, |m| {
+                                if let Some(role_id) = notification_place.role_id {
+                                    m.content(format!("<@&{}>", role_id));
+                                }
+                                m.embeds(vec![embed])
+                            })
+                            .await;
                     }
-                    sent_streams.write().await.push(stream_data[0].clone());
                 }
+
+                update_sent_stream(&sent_streams, &stream).await;
             }
-        } else if !stream_data.is_empty() && !i.is_live {
-            let data = sqlx::query!(
-                "SELECT * FROM streamer_notification_channel WHERE streamer = $1",
-                &i.streamer
-            )
-            .fetch_all(&pool)
-            .await?;
+            (Some(stream), false) => {
+                let (game_name, profile_image_url) =
+                    stream_details(&stream, &game_names, &profile_images);
+
+                for notification_place in channels {
+                    let description = if !notification_place.use_default {
+                        notification_place.live_message.clone()
+                    } else {
+                        i.live_message.clone()
+                    };
 
-            for notification_place in data {
-                let url = format!(
-                    "https://api.twitch.tv/helix/games?id={}",
-                    stream_data[0].game_id
-                );
-                let game_resp = reqwest
-                    .get(&url)
-                    .headers(headers.clone())
-                    .send()
-                    .await?
-                    .json::<TwitchGameData>()
-                    .await?;
-
-                let url = format!(
-                    "https://api.twitch.tv/helix/users?id={}",
-                    stream_data[0].user_id
-                );
-                let user_resp = reqwest
-                    .get(&url)
-                    .headers(headers.clone())
-                    .send()
-                    .await?
-                    .json::<TwitchUserData>()
-                    .await?;
-
-                let game_data = game_resp.data.get(0);
-                let game_name = if let Some(x) = game_data {
-                    x.name.clone().unwrap_or_else(|| "No Game".to_string())
-                } else {
-                    "Unknown game".to_string()
-                };
-                let streamer_name = notification_place.streamer.clone();
-
-                let message = ChannelId(notification_place.channel_id.unwrap() as u64)
-                    .send_message(&ctx, |m| {
-                        if let Some(role_id) = notification_place.role_id {
-                            m.content(format!("<@&{}>", role_id));
-                        }
-                        m.embed(|e| {
-                            if !notification_place.use_default {
-                                e.description(notification_place.live_message.unwrap());
-                            } else {
-                                e.description(i.live_message.clone().unwrap());
+                    let message = ChannelId(notification_place.channel_id.unwrap_or(0) as u64)
+                        .send_message(&ctx, |m| {
+                            if let Some(role_id) = notification_place.role_id {
+                                m.content(format!("<@&{}>", role_id));
                             }
-                            e.author(|a| {
-                                a.name(&i.streamer);
-                                a.icon_url(&user_resp.data[0].profile_image_url);
-                                a.url(format!("https://www.twitch.tv/{}", &i.streamer))
-                            });
-                            e.url(format!("https://www.twitch.tv/{}", &i.streamer));
-                            e.title(stream_data[0].title.to_string());
-                            e.field("Game", game_name, true)
+                            m.embed(|e| {
+                                live_embed(
+                                    e,
+                                    &i.streamer,
+                                    description.unwrap_or_default(),
+                                    &stream,
+                                    &game_name,
+                                    &profile_image_url,
+                                )
+                            })
                         })
-                    })
-                    .await;
-                if let Ok(message_ok) = message {
-                    sqlx::query!("UPDATE streamer_notification_channel SET message_id = $1 WHERE channel_id = $2 AND streamer = $3", message_ok.id.as_u64().to_owned() as i64, message_ok.channel_id.0 as i64, &i.streamer)
+                        .await;
+                    if let Ok(message_ok) = message {
+                        sqlx::query!("UPDATE streamer_notification_channel SET message_id = $1 WHERE channel_id = $2 AND streamer = $3", message_ok.id.as_u64().to_owned() as i64, message_ok.channel_id.0 as i64, &i.streamer)
                         .execute(&pool)
                         .await?;
+                    }
                 }
 
-                let sent_stream_data = {
-                    let new_vec = sent_streams.read().await;
-                    new_vec.clone()
-                };
-                for (index, val) in sent_stream_data.iter().enumerate() {
-                    if val.user_name == streamer_name {
-                        sent_streams.write().await.remove(index);
+                for notification_place in webhooks {
+                    let description = if !notification_place.use_default.unwrap_or(false) {
+                        notification_place.live_message.clone()
+                    } else {
+                        i.live_message.clone()
+                    };
+
+                    let embed = Embed::fake(|e| {
+                        live_embed(
+                            e,
+                            &i.streamer,
+                            description.unwrap_or_default(),
+                            &stream,
+                            &game_name,
+                            &profile_image_url,
+                        )
+                    });
+
+                    let hook = if let Some(x) =
+                        webhook_from_url(&ctx, &notification_place.webhook).await
+                    {
+                        x
+                    } else {
+                        warn!(
+                            "The twitch notification webhook of {} is not valid anymore.",
+                            &i.streamer
+                        );
+                        continue;
+                    };
+
+                    // The message is waited for, so it can be edited when the stream changes or ends.
+                    let message = hook
+                        .execute(&ctx.http, true, |m| {
+                            if let Some(role_id) = notification_place.role_id {
+                                m.content(format!("<@&{}>", role_id));
+                            }
+                            m.embeds(vec![embed])
+                        })
+                        .await;
+                    if let Ok(Some(message_ok)) = message {
+                        sqlx::query!("UPDATE streamer_notification_webhook SET message_id = $1 WHERE webhook = $2 AND streamer = $3", message_ok.id.0 as i64, &notification_place.webhook, &i.streamer)
+                        .execute(&pool)
+                        .await?;
                     }
                 }
-                sent_streams.write().await.push(stream_data[0].clone());
+
+                update_sent_stream(&sent_streams, &stream).await;
+
+                sqlx::query!(
+                    "UPDATE streamers SET is_live = true WHERE streamer = $1",
+                    &i.streamer
+                )
+                .execute(&pool)
+                .await?;
             }
+            (None, _) => {
+                for notification_place in channels {
+                    let description = if !notification_place.use_default {
+                        notification_place.not_live_message.clone()
+                    } else {
+                        i.not_live_message.clone()
+                    };
 
-            sqlx::query!(
-                "UPDATE streamers SET is_live = true WHERE streamer = $1",
-                &i.streamer
-            )
-            .execute(&pool)
-            .await?;
-        } else if stream_data.is_empty() && i.is_live {
-            let data = sqlx::query!(
-                "SELECT * FROM streamer_notification_channel WHERE streamer = $1",
-                i.streamer
-            )
-            .fetch_all(&pool)
-            .await?;
-
-            for notification_place in data {
-                if let Ok(mut message) = ctx
-                    .http
-                    .get_message(
-                        notification_place.channel_id.unwrap_or(0) as u64,
-                        notification_place.message_id.unwrap_or(0) as u64,
-                    )
-                    .await
-                {
-                    let _ = message
-                        .edit(&*ctx, |m| {
-                            if let Some(role_id) = notification_place.role_id.to_owned() {
-                                m.content(format!("<@&{}>", role_id));
-                            }
-                            m.embed(|e| {
-                                if !notification_place.use_default {
-                                    e.description(notification_place.not_live_message.unwrap());
-                                } else {
-                                    e.description(i.not_live_message.clone().unwrap());
+                    if let Ok(mut message) = ctx
+                        .http
+                        .get_message(
+                            notification_place.channel_id.unwrap_or(0) as u64,
+                            notification_place.message_id.unwrap_or(0) as u64,
+                        )
+                        .await
+                    {
+                        let _ = message
+                            .edit(&*ctx, |m| {
+                                if let Some(role_id) = notification_place.role_id {
+                                    m.content(format!("<@&{}>", role_id));
                                 }
-                                e.author(|a| {
-                                    a.name(&i.streamer);
-                                    a.url(format!("https://www.twitch.tv/{}", &i.streamer))
-                                });
-                                e.url(format!("https://www.twitch.tv/{}", &i.streamer));
-                                e.title("No longer live.")
+                                m.embed(|e| {
+                                    offline_embed(e, &i.streamer, description.unwrap_or_default())
+                                })
                             })
-                        })
-                        .await;
+                            .await;
+                    }
+                }
+
+                for notification_place in webhooks {
+                    let description = if !notification_place.use_default.unwrap_or(false) {
+                        notification_place.not_live_message.clone()
+                    } else {
+                        i.not_live_message.clone()
+                    };
+
+                    let message_id = if let Some(x) = notification_place.message_id {
+                        MessageId(x as u64)
+                    } else {
+                        continue;
+                    };
+
+                    let embed = Embed::fake(|e| {
+                        offline_embed(e, &i.streamer, description.unwrap_or_default())
+                    });
+
+                    if let Some(hook) = webhook_from_url(&ctx, &notification_place.webhook).await {
+                        let _ = hook
+                            .edit_message(&ctx.http, message_id, |m| {
+                                if let Some(role_id) = notification_place.role_id {
+                                    m.content(format!("<@&{}>", role_id));
+                                }
+                                m.embeds(vec![embed])
+                            })
+                            .await;
+                    }
                 }
+
+                sqlx::query!(
+                    "UPDATE streamers SET is_live = false WHERE streamer = $1",
+                    i.streamer
+                )
+                .execute(&pool)
+                .await?;
             }
-            sqlx::query!(
-                "UPDATE streamers SET is_live = false WHERE streamer = $1",
-                i.streamer
-            )
-            .execute(&pool)
-            .await?;
         }
     }
 
-    Ok(())
+    Ok(live)
+}
+
+// The name of the game being streamed, and the profile picture of the streamer.
+fn stream_details(
+    stream: &TwitchStreamData,
+    game_names: &HashMap<String, String>,
+    profile_images: &HashMap<String, String>,
+) -> (String, String) {
+    let game_name = if stream.game_id.is_empty() {
+        "No Game".to_string()
+    } else {
+        game_names
+            .get(&stream.game_id)
+            .cloned()
+            .unwrap_or_else(|| "Unknown game".to_string())
+    };
+    let profile_image_url = profile_images
+        .get(&stream.user_id)
+        .cloned()
+        .unwrap_or_default();
+
+    (game_name, profile_image_url)
+}
+
+// Replaces the last known data of a stream, so changes to it can be detected.
+async fn update_sent_stream(
+    sent_streams: &Arc<RwLock<Vec<TwitchStreamData>>>,
+    stream: &TwitchStreamData,
+) {
+    let mut sent_streams = sent_streams.write().await;
+    sent_streams.retain(|s| s.user_id != stream.user_id);
+    sent_streams.push(stream.clone());
+}
+
+fn live_embed<'a>(
+    e: &'a mut CreateEmbed,
+    streamer: &str,
+    description: String,
+    stream: &TwitchStreamData,
+    game_name: &str,
+    profile_image_url: &str,
+) -> &'a mut CreateEmbed {
+    e.description(description);
+    e.author(|a| {
+        a.name(streamer);
+        a.icon_url(profile_image_url);
+        a.url(format!("https://www.twitch.tv/{}", streamer))
+    });
+    e.url(format!("https://www.twitch.tv/{}", streamer));
+    e.title(&stream.title);
+    e.field("Game", game_name, true)
+}
+
+fn offline_embed<'a>(
+    e: &'a mut CreateEmbed,
+    streamer: &str,
+    description: String,
+) -> &'a mut CreateEmbed {
+    e.description(description);
+    e.author(|a| {
+        a.name(streamer);
+        a.url(format!("https://www.twitch.tv/{}", streamer))
+    });
+    e.url(format!("https://www.twitch.tv/{}", streamer));
+    e.title("No longer live.")
+}
+
+// Obtains a webhook from it's url, `https://discord.com/api/webhooks/{id}/{token}`
+async fn webhook_from_url(ctx: &Context, url: &str) -> Option<Webhook> {
+    let mut split = url.split('/');
+    let id = split.nth(5)?.parse::<u64>().ok()?;
+    let token = split.next()?;
+
+    ctx.http.get_webhook_with_token(id, token).await.ok()
 }
 
 //async fn _check_empty_vc(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::Error>> {
@@ -520,234 +781,91 @@ async fn check_twitch_livestreams(ctx: Arc<Context>) -> Result<(), Box<dyn std::
 //    Ok(())
 //}
 
-async fn reminder_check(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::Error>> {
-    let pool = {
-        let data_read = ctx.data.read().await;
-        data_read.get::<DatabasePool>().unwrap().clone()
-    };
-
-    let reminders = sqlx::query!("SELECT * FROM reminders")
-        .fetch_all(&pool)
-        .await?;
-
-    for row in reminders {
-        if row.date < chrono::offset::Utc::now() {
-            let _ = ChannelId(row.channel_id as u64)
-                .send_message(&ctx, |m| {
-                    m.content(format!("<@!{}>: Reminder!", row.user_id));
-                    m.embed(|e| {
-                        e.description(if let Some(x) = &row.message {
-                            x
-                        } else {
-                            "No Message."
-                        });
-                        e.field(
-                            "Original Message",
-                            format!(
-                                "[Jump](https://discord.com/channels/{}/{}/{})",
-                                if row.guild_id == 0 {
-                                    "@me".to_string()
-                                } else {
-                                    row.guild_id.to_string()
-                                },
-                                &row.channel_id,
-                                &row.message_id,
-                            ),
-                            true,
-                        )
-                    })
-                })
-                .await;
-
-            sqlx::query!("DELETE FROM reminders WHERE id = $1", row.id)
-                .execute(&pool)
-                .await?;
-        }
-    }
-
-    Ok(())
-}
-async fn unmute_check(ctx: Arc<Context>) -> Result<(), Box<dyn std::error::Error>> {
-    let pool = {
-        let data_read = ctx.data.read().await;
-        data_read.get::<DatabasePool>().unwrap().clone()
-    };
-
-    let muted_members = sqlx::query!("SELECT * FROM muted_members")
-        .fetch_all(&pool)
-        .await?;
-
-    for row in muted_members {
-        if row.date < chrono::offset::Utc::now() {
-            sqlx::query!("DELETE FROM muted_members WHERE id = $1", row.id)
-                .execute(&pool)
-                .await?;
-
-            let mut member = if let Ok(x) = ctx
-                .http
-                .get_member(row.guild_id as u64, row.user_id as u64)
+pub async fn notification_loop(ctx: Arc<Context>) {
+    spawn_periodic(
+        Arc::clone(&ctx),
+        "posts",
+        Duration::from_secs(120),
+        |ctx| async move { check_new_posts(ctx).await.map_err(|why| why.to_string()) },
+    )
+    .await;
+
+    spawn_periodic(
+        Arc::clone(&ctx),
+        "twitch",
+        Duration::from_secs(120),
+        |ctx| async move {
+            check_twitch_livestreams(ctx)
                 .await
-            {
-                x
-            } else {
-                if let Err(why) = ChannelId(row.channel_id as u64)
-                    .say(
-                        &ctx,
-                        format!("Unable to unmute <@{}> from temporal mute.", row.user_id),
-                    )
-                    .await
-                {
-                    error!("Unable to send message A: {}", why);
-                }
-                continue;
-            };
-
-            let role_id = {
-                let role_row = sqlx::query!(
-                    "SELECT role_id FROM muted_roles WHERE guild_id = $1",
-                    row.guild_id
-                )
-                .fetch_optional(&pool)
-                .await?;
-
-                if let Some(role_row) = role_row {
-                    role_row.role_id as u64
-                } else {
-                    if let Err(why) = ChannelId(row.channel_id as u64).say(&ctx, format!("Unable to unmute <@{}> from temporal mute because there's no configured role.", row.user_id)).await {
-                        error!("Unable to send message B: {}", why);
-                    }
-                    continue;
-                }
-            };
-
-            if member.remove_role(&ctx, role_id).await.is_err() {
-                if let Err(why) = ChannelId(row.channel_id as u64)
-                    .say(
-                        &ctx,
-                        format!("Unable to unmute <@{}> from temporal mute.", row.user_id),
-                    )
-                    .await
-                {
-                    error!("Unable to send message C: {}", why);
-                }
-                continue;
-            }
-
-            if let Err(why) = ChannelId(row.channel_id as u64)
-                .send_message(&ctx, |m| {
-                    m.content(format!("<@!{}> has been unmuted.", row.user_id));
-                    m.embed(|e| {
-                        e.description(if let Some(x) = &row.message {
-                            format!("Mute Reason: {}", x)
-                        } else {
-                            "No Message.".to_string()
-                        });
-                        e.field(
-                            "Original Message",
-                            format!(
-                                "[Jump](https://discord.com/channels/{}/{}/{})",
-                                if row.guild_id == 0 {
-                                    "@me".to_string()
-                                } else {
-                                    row.guild_id.to_string()
-                                },
-                                &row.channel_id,
-                                &row.message_id,
-                            ),
-                            true,
-                        )
-                    })
-                })
+                .map_err(|why| why.to_string())
+        },
+    )
+    .await;
+
+    spawn_periodic(
+        Arc::clone(&ctx),
+        "feeds",
+        Duration::from_secs(600),
+        |ctx| async move { check_feeds(ctx).await.map_err(|why| why.to_string()) },
+    )
+    .await;
+
+    // Once a day
+    spawn_periodic(
+        Arc::clone(&ctx),
+        "sent_posts",
+        Duration::from_secs(86400),
+        |ctx| async move {
+            prune_old_sent_posts(ctx)
                 .await
-            {
-                error!("Unable to send message D: {}", why);
-            }
-        }
-    }
-
-    Ok(())
-}
-
-pub async fn notification_loop(ctx: Arc<Context>) {
-    let ctx = Arc::clone(&ctx);
-    let ctx_clone = Arc::clone(&ctx);
-    let ctx_clone_clone = Arc::clone(&ctx);
-
-    tokio::spawn(async move {
-        loop {
-            info!("Notification loop started.");
-            let ctx1 = Arc::clone(&ctx);
-            tokio::spawn(async move {
-                if let Err(why) = check_new_posts(Arc::clone(&ctx1)).await {
-                    error!("check_new_posts :: {}", why);
-                    error!(
-                        "An error occurred while running check_new_posts() >>> {}",
-                        why
-                    );
-                }
-            });
-
-            let ctx2 = Arc::clone(&ctx);
-            tokio::spawn(async move {
-                if let Err(why) = check_twitch_livestreams(Arc::clone(&ctx2)).await {
-                    error!("check_twitch_livestreams :: {}", why);
-                    error!(
-                        "An error occurred while running check_twitch_livestreams() >>> {}",
-                        why
-                    );
-                }
-            });
-
-            //let ctx3 = Arc::clone(&ctx);
-            //tokio::spawn(async move {
-            //    if let Err(why) = check_empty_vc(Arc::clone(&ctx3)).await {
-            //        error!("check_empty_vc :: {}", why);
-            //        eprintln!("An error occurred while running check_empty_vc() >>> {}", why);
-            //    }
-            //});
-            debug!("Notification loop finished.");
-
-            tokio::time::sleep(Duration::from_secs(120)).await;
-        }
-    });
-
-    tokio::spawn(async move {
-        loop {
-            let ctx1 = Arc::clone(&ctx_clone);
-            tokio::spawn(async move {
-                if let Err(why) = reminder_check(Arc::clone(&ctx1)).await {
-                    error!("remider_check :: {}", why);
-                    error!(
-                        "An error occurred while running reminder_check() >>> {}",
-                        why
-                    );
-                }
-            });
-
-            let ctx2 = Arc::clone(&ctx_clone);
-            tokio::spawn(async move {
-                if let Err(why) = unmute_check(Arc::clone(&ctx2)).await {
-                    error!("unmute_check :: {}", why);
-                    error!("An error occurred while running unmute_check() >>> {}", why);
-                }
-            });
-            tokio::time::sleep(Duration::from_secs(15)).await;
-        }
-    });
-
-    tokio::spawn(async move {
-        loop {
-            let ctx = Arc::clone(&ctx_clone_clone);
-
-            if let Err(why) = update_osu_token(ctx.clone()).await {
-                error!(
-                    "An error occurred while running osu! token update >>> {}",
-                    why
-                );
-            }
-
-            // 4 times a day
-            tokio::time::sleep(Duration::from_secs(21600)).await;
-        }
-    });
+                .map_err(|why| why.to_string())
+        },
+    )
+    .await;
+
+    // Reminders and unmutes fire at their exact time, this only queues the ones that are due
+    // soon, and the ones that were missed while offline.
+    run_scheduler(Arc::clone(&ctx)).await;
+    spawn_periodic(
+        Arc::clone(&ctx),
+        "scheduler",
+        Duration::from_secs(3600),
+        |ctx| async move { load_upcoming_jobs(ctx).await.map_err(|why| why.to_string()) },
+    )
+    .await;
+
+    // Also creates the subscriptions on startup.
+    spawn_periodic(
+        Arc::clone(&ctx),
+        "twitch_eventsub",
+        Duration::from_secs(3600),
+        |ctx| async move {
+            sync_eventsub_subscriptions(&ctx)
+                .await
+                .map_err(|why| why.to_string())
+        },
+    )
+    .await;
+
+    // 4 times a day
+    spawn_periodic(
+        Arc::clone(&ctx),
+        "osu_token",
+        Duration::from_secs(21600),
+        |ctx| async move { update_osu_token(ctx).await.map_err(|why| why.to_string()) },
+    )
+    .await;
+
+    // Every hour, but the token is only refreshed when it's close to expire.
+    spawn_periodic(
+        Arc::clone(&ctx),
+        "twitch_token",
+        Duration::from_secs(3600),
+        |ctx| async move {
+            update_twitch_token(ctx)
+                .await
+                .map_err(|why| why.to_string())
+        },
+    )
+    .await;
 }
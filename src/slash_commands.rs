@@ -0,0 +1,525 @@
+//! Application (slash) command layer.
+//!
+//! Every command group is registered as a slash command, with each of it's commands as a
+//! subcommand. Groups with a prefix, like `config`, are registered under the prefix, and their
+//! commands become subcommand groups of their own subcommands. When an interaction comes in, it
+//! gets turned back into a regular prefixed message and handed to the `StandardFramework`, so
+//! checks, buckets, hooks and the command logic itself are exactly the same as when the command
+//! is invoked through chat.
+use crate::global_data::FrameworkContainer;
+use crate::utils::blocklist::is_blocked;
+use crate::utils::command_rules::{command_groups, normalize_group_name};
+
+use std::error::Error;
+
+use serenity::{
+    builder::CreateApplicationCommandOption,
+    framework::{
+        standard::{Command as FrameworkCommand, CommandGroup},
+        Framework,
+    },
+    model::application::{
+        command::{Command, CommandOptionType},
+        interaction::{
+            application_command::{ApplicationCommandInteraction, CommandDataOption},
+            InteractionResponseType,
+        },
+    },
+    prelude::Context,
+};
+
+// Discord limits for names and descriptions of commands and options.
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_DESCRIPTION_LENGTH: usize = 100;
+const MAX_SUBCOMMANDS: usize = 25;
+
+/// A typed option of a slash command.
+/// The options are converted back into arguments in the same order they are defined in.
+struct SlashOption {
+    name: &'static str,
+    description: &'static str,
+    kind: CommandOptionType,
+    required: bool,
+    /// The argument used when the option is left out but a later one is given, so the later
+    /// ones don't end up in it's place.
+    default: Option<&'static str>,
+}
+
+impl SlashOption {
+    const fn new(
+        name: &'static str,
+        description: &'static str,
+        kind: CommandOptionType,
+        required: bool,
+    ) -> Self {
+        Self {
+            name,
+            description,
+            kind,
+            required,
+            default: None,
+        }
+    }
+
+    const fn with_default(mut self, default: &'static str) -> Self {
+        self.default = Some(default);
+        self
+    }
+}
+
+// Commands that don't have an entry here get a single generic `arguments` string option.
+fn typed_options(command_name: &str) -> Option<Vec<SlashOption>> {
+    use CommandOptionType as Kind;
+
+    let options = match command_name {
+        "help" => vec![SlashOption::new(
+            "command",
+            "Command or group to get information about.",
+            Kind::String,
+            false,
+        )],
+        "play" | "play_playlist" => vec![SlashOption::new(
+            "query",
+            "Name or url of what to play.",
+            Kind::String,
+            true,
+        )],
+        "seek" => vec![SlashOption::new(
+            "time",
+            "Time to jump to, like 1:30",
+            Kind::String,
+            true,
+        )],
+        "remove" => vec![SlashOption::new(
+            "index",
+            "Position of the song in the queue.",
+            Kind::Integer,
+            true,
+        )],
+        "qr" => vec![SlashOption::new(
+            "text",
+            "Text to encode.",
+            Kind::String,
+            true,
+        )],
+        "urban" | "dictionary" => vec![SlashOption::new(
+            "term",
+            "Term to look up.",
+            Kind::String,
+            true,
+        )],
+        "duck_duck_go" => vec![SlashOption::new(
+            "query",
+            "What to search for.",
+            Kind::String,
+            true,
+        )],
+        "translate" => vec![
+            SlashOption::new("language", "Language to translate to.", Kind::String, true),
+            SlashOption::new("text", "Text to translate.", Kind::String, true),
+        ],
+        "calculator" => vec![SlashOption::new(
+            "expression",
+            "Expression to evaluate.",
+            Kind::String,
+            true,
+        )],
+        "encrypt" | "decrypt" | "uwufy" => vec![SlashOption::new(
+            "text",
+            "Text to transform.",
+            Kind::String,
+            true,
+        )],
+        "remind_me" => vec![
+            SlashOption::new(
+                "time",
+                "When to remind you, like 2h 30m",
+                Kind::String,
+                true,
+            ),
+            SlashOption::new("message", "What to remind you about.", Kind::String, false),
+        ],
+        "profile" => vec![SlashOption::new(
+            "user",
+            "User to show the profile of.",
+            Kind::User,
+            false,
+        )],
+        "kick" | "ban" | "permanent_mute" => vec![
+            SlashOption::new("member", "Member to moderate.", Kind::User, true),
+            SlashOption::new("reason", "Reason of the action.", Kind::String, false),
+        ],
+        "permanent_ban" => vec![SlashOption::new(
+            "member",
+            "Member to ban forever.",
+            Kind::User,
+            true,
+        )],
+        "temporal_mute" => vec![
+            SlashOption::new("member", "Member to mute.", Kind::User, true),
+            SlashOption::new(
+                "time",
+                "Duration of the mute, like 2D 12h",
+                Kind::String,
+                false,
+            )
+            .with_default("1h"),
+            SlashOption::new("reason", "Reason of the mute.", Kind::String, false),
+        ],
+        "temporal_self_mute" => vec![
+            SlashOption::new(
+                "time",
+                "Duration of the mute, like 2D 12h",
+                Kind::String,
+                false,
+            )
+            .with_default("1h"),
+            SlashOption::new("reason", "Reason of the mute.", Kind::String, false),
+        ],
+        "clear" => vec![SlashOption::new(
+            "amount",
+            "Number of messages to delete.",
+            Kind::Integer,
+            true,
+        )],
+        _ => return None,
+    };
+
+    Some(options)
+}
+
+fn command_options(command: &FrameworkCommand) -> Vec<SlashOption> {
+    typed_options(command.options.names[0]).unwrap_or_else(|| {
+        vec![SlashOption::new(
+            "arguments",
+            "Arguments of the command, the same as with the prefix version.",
+            CommandOptionType::String,
+            command.options.min_args.unwrap_or(0) > 0,
+        )]
+    })
+}
+
+// Turns a group or command name into a valid application command name.
+fn slash_name(name: &str) -> String {
+    normalize_group_name(name)
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect()
+}
+
+fn slash_description(description: Option<&str>) -> String {
+    let description = description
+        .and_then(|d| d.lines().find(|l| !l.trim().is_empty()))
+        .unwrap_or("No description.")
+        .trim();
+
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        let mut d = description
+            .chars()
+            .take(MAX_DESCRIPTION_LENGTH - 3)
+            .collect::<String>();
+        d += "...";
+        d
+    } else {
+        description.to_string()
+    }
+}
+
+fn slash_groups() -> impl Iterator<Item = &'static CommandGroup> {
+    command_groups().filter(|g| g.options.help_available && !g.options.owners_only)
+}
+
+fn visible_commands(
+    commands: &'static [&'static FrameworkCommand],
+) -> impl Iterator<Item = &'static FrameworkCommand> {
+    commands
+        .iter()
+        .copied()
+        .filter(|c| c.options.help_available && !c.options.owners_only)
+        .take(MAX_SUBCOMMANDS)
+}
+
+// Groups with a prefix are named after it, like `config`.
+fn group_slash_name(group: &CommandGroup) -> String {
+    slash_name(group.options.prefixes.first().unwrap_or(&group.name))
+}
+
+// The commands of a prefixed group are just namespaces for their subcommands, so they become
+// subcommand groups. On the other groups, subcommands are reached through the arguments.
+fn is_subcommand_group(group: &CommandGroup, command: &FrameworkCommand) -> bool {
+    !group.options.prefixes.is_empty() && !command.options.sub_commands.is_empty()
+}
+
+fn create_command_option(o: &mut CreateApplicationCommandOption, command: &FrameworkCommand) {
+    o.name(slash_name(command.options.names[0]))
+        .description(slash_description(command.options.desc))
+        .kind(CommandOptionType::SubCommand);
+
+    for option in command_options(command) {
+        o.create_sub_option(|so| {
+            so.name(option.name)
+                .description(option.description)
+                .kind(option.kind)
+                .required(option.required)
+        });
+    }
+}
+
+/// Registers every command group as a global slash command.
+pub async fn register_application_commands(
+    ctx: &Context,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let commands = Command::set_global_application_commands(&ctx.http, |commands| {
+        for group in slash_groups() {
+            commands.create_application_command(|c| {
+                c.name(group_slash_name(group))
+                    .description(slash_description(group.options.description));
+
+                for command in visible_commands(group.options.commands) {
+                    c.create_option(|o| {
+                        if is_subcommand_group(group, command) {
+                            o.name(slash_name(command.options.names[0]))
+                                .description(slash_description(command.options.desc))
+                                .kind(CommandOptionType::SubCommandGroup);
+
+                            for sub_command in visible_commands(command.options.sub_commands) {
+                                o.create_sub_option(|so| {
+                                    create_command_option(so, sub_command);
+                                    so
+                                });
+                            }
+                        } else {
+                            create_command_option(o, command);
+                        }
+                        o
+                    });
+                }
+
+                c
+            });
+        }
+
+        commands.create_application_command(|c| {
+            c.name("help")
+                .description("Shows the list of commands, or information about one of them.");
+
+            for option in typed_options("help").unwrap_or_default() {
+                c.create_option(|o| {
+                    o.name(option.name)
+                        .description(option.description)
+                        .kind(option.kind)
+                        .required(option.required)
+                });
+            }
+
+            c
+        })
+    })
+    .await?;
+
+    info!("Registered {} application commands.", commands.len());
+
+    Ok(())
+}
+
+const QUOTE_IN_OPTION: &str = "Only the last option can contain `\"`.";
+
+// Returns None when the option has no value, and an error when it can't be turned into a single
+// argument.
+fn option_to_argument(
+    option: &CommandDataOption,
+    quote: bool,
+) -> Result<Option<String>, &'static str> {
+    let value = if let Some(x) = option.value.as_ref() {
+        x
+    } else {
+        return Ok(None);
+    };
+
+    let argument = match option.kind {
+        CommandOptionType::User => value.as_str().map(|v| format!("<@{}>", v)),
+        CommandOptionType::Role => value.as_str().map(|v| format!("<@&{}>", v)),
+        CommandOptionType::Channel => value.as_str().map(|v| format!("<#{}>", v)),
+        CommandOptionType::String => match value.as_str() {
+            // The arguments have no way to escape a quote, it would end the quoted argument
+            // early, so the values with them are rejected instead of parsed wrong.
+            Some(v) if quote && v.contains('"') => return Err(QUOTE_IN_OPTION),
+            // Every string but the last one gets quoted, so multi word values still get parsed
+            // as a single argument by `single_quoted`.
+            Some(v) if quote && v.contains(' ') => Some(format!("\"{}\"", v)),
+            v => v.map(|v| v.to_string()),
+        },
+        _ => Some(value.to_string()),
+    };
+
+    Ok(argument)
+}
+
+// Converts the values of the options back into the text arguments the command expects.
+// Discord sends the options in any order and skips the ones left out, so they are placed by the
+// order they are defined in. A left out option takes it's default when a later one is given,
+// and the arguments stop there when it doesn't have one.
+fn options_to_arguments(
+    defined: &[SlashOption],
+    options: &[CommandDataOption],
+) -> Result<String, &'static str> {
+    let mut values = defined
+        .iter()
+        .map(|d| options.iter().find(|o| o.name == d.name))
+        .collect::<Vec<_>>();
+
+    while let Some(None) = values.last() {
+        values.pop();
+    }
+
+    let last = values.len().saturating_sub(1);
+    let mut arguments = Vec::new();
+
+    for (index, (definition, option)) in defined.iter().zip(&values).enumerate() {
+        let argument = match option {
+            Some(option) => option_to_argument(option, index != last)?,
+            None => definition.default.map(|d| d.to_string()),
+        };
+
+        match argument {
+            Some(x) => arguments.push(x),
+            None => break,
+        }
+    }
+
+    Ok(arguments.join(" "))
+}
+
+// Finds the command an interaction refers to.
+// Returns the text that invokes it, with the group prefix and the parent commands, and it's
+// arguments.
+fn resolve_command(
+    name: &str,
+    options: &[CommandDataOption],
+) -> Option<(String, Result<String, &'static str>)> {
+    let group = slash_groups().find(|g| group_slash_name(g) == name)?;
+    let option = options.get(0)?;
+
+    let command = visible_commands(group.options.commands)
+        .find(|c| slash_name(c.options.names[0]) == option.name)?;
+
+    let mut words = group
+        .options
+        .prefixes
+        .first()
+        .map(|p| p.to_string())
+        .into_iter()
+        .collect::<Vec<_>>();
+    words.push(command.options.names[0].to_string());
+
+    let (command, option) = if is_subcommand_group(group, command) {
+        let sub_option = option.options.get(0)?;
+        let sub_command = visible_commands(command.options.sub_commands)
+            .find(|c| slash_name(c.options.names[0]) == sub_option.name)?;
+
+        words.push(sub_command.options.names[0].to_string());
+        (sub_command, sub_option)
+    } else {
+        (command, option)
+    };
+
+    Some((
+        words.join(" "),
+        options_to_arguments(&command_options(command), &option.options),
+    ))
+}
+
+/// Dispatches a slash command interaction to the `StandardFramework`.
+pub async fn handle_application_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if is_blocked(ctx, command.user.id, command.guild_id).await {
+        command
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.content("You are not allowed to use this bot.")
+                            .ephemeral(true)
+                    })
+            })
+            .await?;
+        return Ok(());
+    }
+
+    let resolved = if command.data.name == "help" {
+        Some((
+            "help".to_string(),
+            options_to_arguments(
+                &typed_options("help").unwrap_or_default(),
+                &command.data.options,
+            ),
+        ))
+    } else {
+        resolve_command(&command.data.name, &command.data.options)
+    };
+
+    let (command_name, arguments) = match resolved {
+        Some((name, Ok(arguments))) => (name, arguments),
+        Some((_, Err(why))) => {
+            command
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| d.content(why).ephemeral(true))
+                })
+                .await?;
+            return Ok(());
+        }
+        None => {
+            command
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| {
+                            d.content("This command no longer exists.").ephemeral(true)
+                        })
+                })
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // The interaction needs a response within 3 seconds, and it's also used as the message
+    // the command replies and reacts to.
+    // Discord can't reply to an interaction, so `msg.reply` quotes this response, which is why
+    // it names the user that invoked the command, without pinging them.
+    command
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content(format!(
+                        "<@{}> used `{} {}`",
+                        command.user.id.0, &command_name, &arguments
+                    ))
+                    .allowed_mentions(|m| m.empty_parse())
+                })
+        })
+        .await?;
+
+    let mut msg = command.get_interaction_response(&ctx.http).await?;
+
+    // Make the response look like a message sent by the user that invoked the command.
+    msg.author = command.user.clone();
+    msg.member = None;
+    msg.webhook_id = None;
+    msg.guild_id = command.guild_id;
+    msg.content = format!(
+        "<@{}> {} {}",
+        ctx.cache.current_user_id().0,
+        command_name,
+        arguments
+    );
+
+    let framework = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<FrameworkContainer>().unwrap().clone()
+    };
+
+    framework.dispatch(ctx.clone(), msg).await;
+
+    Ok(())
+}
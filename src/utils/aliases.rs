@@ -0,0 +1,59 @@
+use crate::global_data::FrameworkContainer;
+
+use std::collections::HashMap;
+
+use serenity::{framework::Framework, model::channel::Message, prelude::Context};
+
+pub const MAX_ALIASES_PER_GUILD: usize = 100;
+pub const MAX_ALIAS_ARGUMENTS_LENGTH: usize = 200;
+
+/// A guild defined alias to a command, with preset arguments.
+#[derive(Debug, Clone)]
+pub struct CommandAlias {
+    pub command: String,
+    pub arguments: String,
+}
+
+impl CommandAlias {
+    /// The command this alias runs, like it would be typed.
+    pub fn invocation(&self) -> String {
+        if self.arguments.is_empty() {
+            self.command.to_string()
+        } else {
+            format!("{} {}", self.command, self.arguments)
+        }
+    }
+}
+
+/// Formats a list of aliases, one per line, sorted by name.
+pub fn format_aliases(aliases: &HashMap<String, CommandAlias>) -> String {
+    let mut aliases = aliases
+        .iter()
+        .map(|(name, alias)| {
+            let invocation = alias.invocation().replace('`', "");
+            format!("`{}` → `{}`", name, invocation)
+        })
+        .collect::<Vec<_>>();
+    aliases.sort();
+
+    aliases.join("\n")
+}
+
+/// Runs the command an alias points to, through the framework, so checks and hooks still apply.
+/// Whatever was written after the alias gets appended to the preset arguments.
+pub async fn dispatch_alias(ctx: &Context, msg: &Message, alias: &CommandAlias, rest: &str) {
+    let framework = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<FrameworkContainer>().unwrap().clone()
+    };
+
+    let mut msg = msg.clone();
+    msg.content = format!(
+        "<@{}> {} {}",
+        ctx.cache.current_user_id().0,
+        alias.invocation(),
+        rest.trim()
+    );
+
+    framework.dispatch(ctx.clone(), msg).await;
+}
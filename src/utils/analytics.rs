@@ -0,0 +1,219 @@
+use crate::global_data::{BotMetrics, CommandStartTimes, DatabasePool};
+use crate::utils::tasks::spawn_periodic;
+
+use std::{
+    error::Error,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serenity::{model::channel::Message, prelude::Context};
+use sqlx::PgPool;
+
+/// The usage of a command over a period of time.
+#[derive(Debug, Clone)]
+pub struct CommandStats {
+    pub command: String,
+    pub uses: i64,
+    pub failures: i64,
+    pub internal_errors: i64,
+    pub total_duration_ms: i64,
+}
+
+impl CommandStats {
+    pub fn failure_rate(&self) -> f64 {
+        if self.uses == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.uses as f64 * 100.0
+        }
+    }
+
+    pub fn average_duration_ms(&self) -> i64 {
+        if self.uses == 0 {
+            0
+        } else {
+            self.total_duration_ms / self.uses
+        }
+    }
+}
+
+/// Marks the moment a command starts running, so it's duration can be recorded.
+pub async fn start_command_timer(ctx: &Context, msg: &Message) {
+    let start_times = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<CommandStartTimes>().unwrap().clone()
+    };
+
+    start_times.write().await.insert(msg.id.0, Instant::now());
+}
+
+/// Records a command invocation on the database.
+/// `error_kind` is the kind of `BotError` the command failed with, if it failed.
+pub async fn record_command_usage(
+    ctx: &Context,
+    msg: &Message,
+    command_name: &str,
+    error_kind: Option<&'static str>,
+) {
+    let (pool, start_times, metrics) = {
+        let data_read = ctx.data.read().await;
+        let pool = data_read.get::<DatabasePool>().unwrap();
+        let start_times = data_read.get::<CommandStartTimes>().unwrap();
+        let metrics = data_read.get::<BotMetrics>().unwrap();
+
+        (pool.clone(), start_times.clone(), metrics.clone())
+    };
+
+    let duration = start_times
+        .write()
+        .await
+        .remove(&msg.id.0)
+        .map_or(Duration::default(), |start| start.elapsed());
+    let duration_ms = duration.as_millis() as i32;
+
+    metrics
+        .write()
+        .await
+        .record_command(command_name, duration, error_kind.is_some());
+
+    let command_name = command_name.to_string();
+    let guild_id = msg.guild_id.map_or(0, |g| g.0 as i64);
+    let user_id = msg.author.id.0 as i64;
+
+    // Recording shouldn't make the command feel slower, so it's done in the background.
+    tokio::spawn(async move {
+        if let Err(why) = insert_command_usage(
+            &pool,
+            &command_name,
+            guild_id,
+            user_id,
+            duration_ms,
+            error_kind,
+        )
+        .await
+        {
+            error!("Could not record the usage of {}: {}", command_name, why);
+        }
+    });
+}
+
+async fn insert_command_usage(
+    pool: &PgPool,
+    command_name: &str,
+    guild_id: i64,
+    user_id: i64,
+    duration_ms: i32,
+    error_kind: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    // Both tables are written on the same transaction, so the daily rollup always matches.
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO command_usage (command, guild_id, user_id, duration_ms, success, error_kind) VALUES ($1, $2, $3, $4, $5, $6)",
+        command_name,
+        guild_id,
+        user_id,
+        duration_ms,
+        error_kind.is_none(),
+        error_kind,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO command_usage_daily (day, command, guild_id, uses, failures, internal_errors, total_duration_ms) VALUES (CURRENT_DATE, $1, $2, 1, $3, $4, $5) ON CONFLICT (day, command, guild_id) DO UPDATE SET uses = command_usage_daily.uses + 1, failures = command_usage_daily.failures + EXCLUDED.failures, internal_errors = command_usage_daily.internal_errors + EXCLUDED.internal_errors, total_duration_ms = command_usage_daily.total_duration_ms + EXCLUDED.total_duration_ms",
+        command_name,
+        guild_id,
+        error_kind.is_some() as i64,
+        (error_kind == Some("internal")) as i64,
+        duration_ms as i64,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Obtains the usage of every command during the last `days` days.
+/// If a guild is given, only the commands ran on that guild are counted.
+pub async fn command_stats(
+    pool: &PgPool,
+    days: i32,
+    guild_id: Option<u64>,
+) -> Result<Vec<CommandStats>, sqlx::Error> {
+    let stats = if let Some(guild_id) = guild_id {
+        sqlx::query!(
+            r#"SELECT command, SUM(uses)::int8 AS "uses!", SUM(failures)::int8 AS "failures!", SUM(internal_errors)::int8 AS "internal_errors!", SUM(total_duration_ms)::int8 AS "total_duration_ms!" FROM command_usage_daily WHERE day > CURRENT_DATE - $1::int4 AND guild_id = $2 GROUP BY command"#,
+            days,
+            guild_id as i64,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| CommandStats {
+            command: r.command,
+            uses: r.uses,
+            failures: r.failures,
+            internal_errors: r.internal_errors,
+            total_duration_ms: r.total_duration_ms,
+        })
+        .collect()
+    } else {
+        sqlx::query!(
+            r#"SELECT command, SUM(uses)::int8 AS "uses!", SUM(failures)::int8 AS "failures!", SUM(internal_errors)::int8 AS "internal_errors!", SUM(total_duration_ms)::int8 AS "total_duration_ms!" FROM command_usage_daily WHERE day > CURRENT_DATE - $1::int4 GROUP BY command"#,
+            days,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| CommandStats {
+            command: r.command,
+            uses: r.uses,
+            failures: r.failures,
+            internal_errors: r.internal_errors,
+            total_duration_ms: r.total_duration_ms,
+        })
+        .collect()
+    };
+
+    Ok(stats)
+}
+
+/// Removes the individual invocations that are older than 30 days.
+/// The daily rollups are kept.
+async fn prune_command_usage(ctx: &Context) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let result =
+        sqlx::query!("DELETE FROM command_usage WHERE invoked_at < now() - interval '30 days'")
+            .execute(&pool)
+            .await?;
+
+    debug!(
+        "Pruned {} old command usage records.",
+        result.rows_affected()
+    );
+
+    Ok(())
+}
+
+pub async fn analytics_loop(ctx: Arc<Context>) {
+    // Once a day
+    spawn_periodic(
+        ctx,
+        "analytics",
+        Duration::from_secs(86400),
+        |ctx| async move {
+            prune_command_usage(&ctx)
+                .await
+                .map_err(|why| why.to_string())
+        },
+    )
+    .await;
+}
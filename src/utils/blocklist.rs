@@ -0,0 +1,148 @@
+use crate::global_data::{BlockedTargets, DatabasePool};
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serenity::{
+    model::id::{GuildId, UserId},
+    prelude::Context,
+};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone)]
+pub struct BlockEntry {
+    pub reason: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl BlockEntry {
+    pub fn is_active(&self) -> bool {
+        self.expires_at.map_or(true, |e| e > Utc::now())
+    }
+}
+
+/// The users and guilds that are not allowed to use the bot.
+/// This is a copy of the `blocklist` table, so it doesn't need to be queried on every message.
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    pub users: HashMap<u64, BlockEntry>,
+    pub guilds: HashMap<u64, BlockEntry>,
+}
+
+impl Blocklist {
+    pub fn is_user_blocked(&self, user_id: u64) -> bool {
+        self.users.get(&user_id).map_or(false, |e| e.is_active())
+    }
+
+    pub fn is_guild_blocked(&self, guild_id: u64) -> bool {
+        self.guilds.get(&guild_id).map_or(false, |e| e.is_active())
+    }
+
+    fn entries_mut(&mut self, is_guild: bool) -> &mut HashMap<u64, BlockEntry> {
+        if is_guild {
+            &mut self.guilds
+        } else {
+            &mut self.users
+        }
+    }
+}
+
+/// Loads every block that has not expired yet.
+pub async fn load_blocklist(pool: &PgPool) -> Result<Blocklist, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT target_id, is_guild, reason, expires_at FROM blocklist WHERE expires_at IS NULL OR expires_at > now()"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut blocklist = Blocklist::default();
+
+    for row in rows {
+        blocklist.entries_mut(row.is_guild).insert(
+            row.target_id as u64,
+            BlockEntry {
+                reason: row.reason,
+                expires_at: row.expires_at,
+            },
+        );
+    }
+
+    Ok(blocklist)
+}
+
+/// Checks if a user, or the guild they are using the bot on, is blocked.
+pub async fn is_blocked(ctx: &Context, user_id: UserId, guild_id: Option<GuildId>) -> bool {
+    let blocklist = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<BlockedTargets>().unwrap().clone()
+    };
+
+    let blocklist = blocklist.read().await;
+
+    blocklist.is_user_blocked(user_id.0)
+        || guild_id.map_or(false, |g| blocklist.is_guild_blocked(g.0))
+}
+
+/// Blocks a user or a guild, replacing the previous block if there was one.
+pub async fn block(
+    ctx: &Context,
+    target_id: u64,
+    is_guild: bool,
+    entry: BlockEntry,
+    blocked_by: u64,
+) -> Result<(), sqlx::Error> {
+    let (pool, blocklist) = {
+        let data_read = ctx.data.read().await;
+        let pool = data_read.get::<DatabasePool>().unwrap();
+        let blocklist = data_read.get::<BlockedTargets>().unwrap();
+
+        (pool.clone(), blocklist.clone())
+    };
+
+    sqlx::query!(
+        "INSERT INTO blocklist (target_id, is_guild, reason, expires_at, blocked_by) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (target_id, is_guild) DO UPDATE SET reason = EXCLUDED.reason, expires_at = EXCLUDED.expires_at, blocked_by = EXCLUDED.blocked_by, created_at = now()",
+        target_id as i64,
+        is_guild,
+        entry.reason,
+        entry.expires_at,
+        blocked_by as i64,
+    )
+    .execute(&pool)
+    .await?;
+
+    blocklist
+        .write()
+        .await
+        .entries_mut(is_guild)
+        .insert(target_id, entry);
+
+    Ok(())
+}
+
+/// Removes the block of a user or a guild.
+/// Returns false if it was not blocked.
+pub async fn unblock(ctx: &Context, target_id: u64, is_guild: bool) -> Result<bool, sqlx::Error> {
+    let (pool, blocklist) = {
+        let data_read = ctx.data.read().await;
+        let pool = data_read.get::<DatabasePool>().unwrap();
+        let blocklist = data_read.get::<BlockedTargets>().unwrap();
+
+        (pool.clone(), blocklist.clone())
+    };
+
+    let result = sqlx::query!(
+        "DELETE FROM blocklist WHERE target_id = $1 AND is_guild = $2",
+        target_id as i64,
+        is_guild,
+    )
+    .execute(&pool)
+    .await?;
+
+    blocklist
+        .write()
+        .await
+        .entries_mut(is_guild)
+        .remove(&target_id);
+
+    Ok(result.rows_affected() > 0)
+}
@@ -1,4 +1,11 @@
+use crate::framework_methods::Booru;
+
+use std::error::Error;
+
 use rand::Rng;
+use reqwest::{header::*, Client as ReqwestClient};
+use serde::{Deserialize, Serialize};
+use serde_aux::prelude::*;
 use serenity::framework::standard::Args;
 
 pub static SAFE_BANLIST: [&str; 12] = [
@@ -121,3 +128,227 @@ pub async fn illegal_check_safe(tags: &mut Vec<String>) -> Vec<String> {
     }
     new_tags
 }
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+struct ScoreData {
+    total: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Url {
+    pub url: Option<String>,
+    pub md5: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+struct Tags {
+    general: Option<Vec<Option<String>>>,
+}
+
+// defining the Post type to be used for the xml deserialized on the Posts vector.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Post {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub id: u64,
+    pub md5: Option<String>,
+    pub score: Option<String>,
+    pub actual_score: Option<String>,
+    pub source: Option<String>,
+    pub sources: Option<Vec<String>>,
+    pub rating: Option<String>,
+    pub sample_url: Option<String>,
+    pub file_url: Option<String>,
+    pub sample: Option<Url>,
+    pub file: Option<Url>,
+    pub tags: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+struct PostE621 {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    id: u64,
+    md5: Option<String>,
+    score: Option<ScoreData>,
+    actual_score: Option<String>,
+    source: Option<String>,
+    sources: Option<Vec<String>>,
+    rating: Option<String>,
+    sample_url: Option<String>,
+    file_url: Option<String>,
+    sample: Option<Url>,
+    file: Option<Url>,
+    tags: Option<Tags>,
+    string_tags: Option<String>,
+}
+
+// defining the Posts vector to Deserialize the requested xml list.
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+struct Posts {
+    post: Vec<Post>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+struct PostsE621 {
+    posts: Vec<PostE621>,
+}
+
+impl Post {
+    /// The identifier used to know if a post was already sent.
+    /// The md5 of the file when the booru has it, as it's shared between boorus.
+    pub fn key(&self) -> String {
+        self.md5.clone().unwrap_or_else(|| self.id.to_string())
+    }
+
+    /// The url of the image to embed, the sample if there's one.
+    pub fn image_url(&self) -> Option<&str> {
+        self.sample_url
+            .as_deref()
+            .filter(|u| !u.is_empty())
+            .or_else(|| self.file_url.as_deref().filter(|u| !u.is_empty()))
+    }
+
+    /// Checks if the post can be sent on a channel.
+    /// Outside of nsfw channels, only safe posts without any of the safe banlist tags are allowed.
+    pub fn is_allowed(&self, nsfw: bool) -> bool {
+        let tags = self.tags.clone().unwrap_or_else(|| "gore".to_string());
+
+        if nsfw {
+            !tags.split(' ').any(|tag| UNSAFE_BANLIST.contains(&tag))
+        } else {
+            !tags.split(' ').any(|tag| SAFE_BANLIST.contains(&tag))
+                && self.rating.as_deref().unwrap_or("x") == "s"
+        }
+    }
+}
+
+// Obtains the url of the api of the booru, for the first page of posts with the tags.
+fn posts_url(booru: &Booru, tags: &str) -> String {
+    // TODO: replace this with Url::parse_with_params
+    let tags = tags.replace(' ', "+");
+    let page: usize = 0;
+
+    if booru.typ == 1 {
+        format!(
+            "https://{}/index.php?page=dapi&s=post&q=index&tags={}&pid={}&limit=50",
+            booru.url, tags, page
+        )
+    } else if booru.typ == 2 {
+        format!(
+            "https://{}/post/index.xml?tags={}&page={}&limit=50",
+            booru.url, tags, page
+        )
+    } else if booru.typ == 3 {
+        format!(
+            "http://{}/post/index.xml?tags={}&page={}&limit=50",
+            booru.url, tags, page
+        )
+    } else if booru.typ == 4 {
+        format!(
+            "http://{}/posts.json?tags={}&page={}&limit=50",
+            booru.url, tags, page
+        )
+    } else {
+        "https://safebooru.org/index.php?page=dapi&s=post&q=index".to_string()
+    }
+}
+
+/// The headers needed for the boorus to accept a request, some of them block unknown clients.
+pub fn request_headers(url: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        ACCEPT,
+        "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8"
+            .parse()
+            .unwrap(),
+    );
+    headers.insert(
+        USER_AGENT,
+        "Mozilla/5.0 (X11; Linux x86_64; rv:99.0) Gecko/20100101 Firefox/99.0"
+            .parse()
+            .unwrap(),
+    );
+    if let Ok(referer) = url.parse() {
+        headers.insert("Referer", referer);
+    }
+
+    headers
+}
+
+/// Obtains the latest posts of a booru with the tags, separated by spaces.
+/// Boorus reply with invalid XML when nothing matches the tags, so that results in no posts.
+pub async fn fetch_posts(
+    booru: &Booru,
+    tags: &str,
+) -> Result<Vec<Post>, Box<dyn Error + Send + Sync>> {
+    let url = posts_url(booru, tags);
+
+    // Send a request with the parsed url, and return the output text.
+    let resp = ReqwestClient::new()
+        .get(&url)
+        .headers(request_headers(&url))
+        .send()
+        .await?
+        .text()
+        .await?;
+
+    // deserialize the request XML into the Posts struct.
+    if booru.typ == 4 {
+        let mut posts: PostsE621 = serde_json::from_str(resp.as_str())?;
+        for post in posts.posts.iter_mut() {
+            post.actual_score = Some(if let Some(score_data) = post.score.clone() {
+                score_data.total.to_string()
+            } else {
+                "0".to_string()
+            });
+            post.score = None;
+            post.source = if let Some(sources) = post.sources.clone() {
+                if !sources.is_empty() {
+                    Some(sources[0].clone())
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+            post.md5 = post.file.as_ref().and_then(|f| f.md5.clone());
+            post.sample_url = Some(post.sample.clone().unwrap().url.unwrap_or_default());
+            post.file_url = Some(post.file.clone().unwrap().url.unwrap_or_default());
+            post.string_tags = Some({
+                if let Some(t) = post.tags.clone() {
+                    t.general
+                        .unwrap_or_else(|| vec![Some("gore".to_string())])
+                        .iter()
+                        .map(|tag| format!("{} ", tag.as_ref().unwrap_or(&"gore".to_string())))
+                        .collect::<String>()
+                } else {
+                    "gore".to_string()
+                }
+            });
+        }
+
+        let mut new_raw_posts = serde_json::to_string(&posts)?;
+        new_raw_posts = new_raw_posts.replace("\"posts\"", "\"post\"");
+        new_raw_posts = new_raw_posts.replace("\"score\"", "\"__\"");
+        new_raw_posts = new_raw_posts.replace("\"tags\"", "\"___\"");
+        new_raw_posts = new_raw_posts.replace("\"string_tags\"", "\"tags\"");
+        new_raw_posts = new_raw_posts.replace("\"actual_score\"", "\"score\"");
+        let new_posts: Posts = serde_json::from_str(&new_raw_posts)?;
+
+        Ok(new_posts.post)
+    } else {
+        let mut posts = match quick_xml::de::from_str::<Posts>(resp.as_str()) {
+            Ok(x) => x,
+            Err(why) => {
+                error!("Error with booru XML deser: {}", why);
+                return Ok(Vec::new());
+            }
+        };
+
+        for ref mut post in posts.post.iter_mut() {
+            post.actual_score = post.score.clone();
+        }
+
+        Ok(posts.post)
+    }
+}
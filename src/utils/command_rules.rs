@@ -0,0 +1,186 @@
+use crate::framework::{
+    ALLBOORUS_GROUP, CONFIGURATION_GROUP, FUN_GROUP, GAMES_GROUP, IMAGEMANIPULATION_GROUP,
+    MASTER_GROUP, META_GROUP, MOD_GROUP, MUSIC_GROUP, NEWOSU_GROUP, OSU_GROUP, SANKAKU_GROUP,
+    SERENITYDOCS_GROUP,
+};
+
+use std::collections::HashSet;
+
+use serenity::{
+    framework::standard::CommandGroup,
+    model::{
+        channel::Message,
+        id::{ChannelId, RoleId},
+    },
+    prelude::Context,
+};
+
+/// A rule that allows or denies a command, or a whole group, on a channel and/or for a role.
+///
+/// If a command has any allow rule, it can only be used where one of them matches.
+/// Deny rules always take priority over allow rules.
+#[derive(Debug, Clone)]
+pub struct CommandRule {
+    pub id: i32,
+    pub target: String,
+    pub is_group: bool,
+    pub channel_id: Option<u64>,
+    pub role_id: Option<u64>,
+    pub allow: bool,
+}
+
+impl CommandRule {
+    fn targets(&self, command_name: &str, group_name: Option<&str>) -> bool {
+        if self.is_group {
+            group_name.map_or(false, |g| g == self.target)
+        } else {
+            self.target == command_name
+        }
+    }
+
+    fn matches(&self, channel_id: ChannelId, roles: &[RoleId]) -> bool {
+        let channel_matches = self.channel_id.map_or(true, |c| c == channel_id.0);
+        let role_matches = self.role_id.map_or(true, |r| roles.contains(&RoleId(r)));
+
+        channel_matches && role_matches
+    }
+}
+
+/// Turns a group name like "Image Boards" into `image_boards`, so it can be typed as an argument.
+pub fn normalize_group_name(name: &str) -> String {
+    let name = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+
+    name.trim_matches('_').to_string()
+}
+
+// The groups that are loaded on the framework on their own, instead of through `MASTER_GROUP`.
+static STANDALONE_GROUPS: [&CommandGroup; 2] = [&CONFIGURATION_GROUP, &NEWOSU_GROUP];
+
+// The names of the group structs on `framework.rs`, which can be used instead of the display
+// names, like `AllBoorus` for "Image Boards".
+static GROUP_STRUCT_NAMES: [(&str, &CommandGroup); 12] = [
+    ("Meta", &META_GROUP),
+    ("Sankaku", &SANKAKU_GROUP),
+    ("Osu", &OSU_GROUP),
+    ("NewOsu", &NEWOSU_GROUP),
+    ("AllBoorus", &ALLBOORUS_GROUP),
+    ("ImageManipulation", &IMAGEMANIPULATION_GROUP),
+    ("Fun", &FUN_GROUP),
+    ("Games", &GAMES_GROUP),
+    ("Mod", &MOD_GROUP),
+    ("Music", &MUSIC_GROUP),
+    ("SerenityDocs", &SERENITYDOCS_GROUP),
+    ("Configuration", &CONFIGURATION_GROUP),
+];
+
+/// Every command group the framework loads.
+pub fn command_groups() -> impl Iterator<Item = &'static CommandGroup> {
+    MASTER_GROUP
+        .options
+        .sub_groups
+        .iter()
+        .chain(STANDALONE_GROUPS.iter())
+        .copied()
+}
+
+/// Obtains the group a command belongs to.
+pub fn group_of_command(command_name: &str) -> Option<&'static CommandGroup> {
+    command_groups().find(|g| {
+        g.options
+            .commands
+            .iter()
+            .any(|c| c.options.names[0] == command_name)
+    })
+}
+
+/// Resolves what a rule argument refers to, a group by it's name or struct name, or a command.
+/// Returns the name to store, and whether it's a group or a single command.
+/// Groups are always stored by their normalized name, like `image_boards`.
+pub fn resolve_rule_target(name: &str) -> Option<(String, bool)> {
+    let normalized = normalize_group_name(name);
+
+    let group = command_groups()
+        .find(|g| normalize_group_name(g.name) == normalized)
+        .or_else(|| {
+            GROUP_STRUCT_NAMES
+                .iter()
+                .find(|(struct_name, _)| struct_name.eq_ignore_ascii_case(name))
+                .map(|(_, g)| *g)
+        });
+
+    if let Some(group) = group {
+        return Some((normalize_group_name(group.name), true));
+    }
+
+    for group in command_groups() {
+        for command in group.options.commands {
+            if command.options.names.contains(&name) {
+                return Some((command.options.names[0].to_string(), false));
+            }
+        }
+    }
+
+    None
+}
+
+/// Checks if a name is already used by a command, a command group prefix or a booru.
+pub fn is_command_name(name: &str, booru_commands: &HashSet<String>) -> bool {
+    let is_command = command_groups().any(|g| {
+        g.options.prefixes.contains(&name)
+            || g.options
+                .commands
+                .iter()
+                .any(|c| c.options.names.contains(&name))
+    });
+
+    is_command || name == "help" || booru_commands.contains(name)
+}
+
+/// Checks if the rules allow a command to be ran.
+pub fn is_command_allowed(
+    rules: &[CommandRule],
+    command_name: &str,
+    channel_id: ChannelId,
+    roles: &[RoleId],
+) -> bool {
+    let group_name = group_of_command(command_name).map(|g| normalize_group_name(g.name));
+
+    let mut has_allow_rules = false;
+    let mut allowed = false;
+
+    for rule in rules
+        .iter()
+        .filter(|r| r.targets(command_name, group_name.as_deref()))
+    {
+        if rule.allow {
+            has_allow_rules = true;
+
+            if rule.matches(channel_id, roles) {
+                allowed = true;
+            }
+        } else if rule.matches(channel_id, roles) {
+            return false;
+        }
+    }
+
+    !has_allow_rules || allowed
+}
+
+/// Obtains the roles of the author of a message.
+pub async fn author_roles(ctx: &Context, msg: &Message) -> Vec<RoleId> {
+    if let Some(member) = &msg.member {
+        return member.roles.clone();
+    }
+
+    if let Some(guild_id) = msg.guild_id {
+        if let Ok(member) = guild_id.member(ctx, msg.author.id).await {
+            return member.roles;
+        }
+    }
+
+    Vec::new()
+}
@@ -1,27 +1,72 @@
 use darkredis::ConnectionPool;
-use sqlx::postgres::{PgPool, PgPoolOptions};
-use std::env;
+use sqlx::{
+    migrate::Migrator,
+    postgres::{PgPool, PgPoolOptions},
+};
 
-// This function obtains a database connection to the postgresql database used for the bot.
-pub async fn obtain_postgres_pool() -> Result<PgPool, Box<dyn std::error::Error + Send + Sync>> {
-    // Obtain the postgresql url.
-    let pg_url = env::var("DATABASE_URL2")?;
+// Every script in "migrations/" is embedded on the binary, so the bot can keep the schema up to date.
+static MIGRATOR: Migrator = sqlx::migrate!();
 
+// This function obtains a database connection to the postgresql database used for the bot.
+pub async fn obtain_postgres_pool(
+    pg_url: &str,
+) -> Result<PgPool, Box<dyn std::error::Error + Send + Sync>> {
     // Connect to the database with the information provided on the configuration.
     // and return a pool of connections
     let pool = PgPoolOptions::new()
         .max_connections(20)
-        .connect(&pg_url)
+        .connect(pg_url)
         .await?;
 
     // return the pool
     Ok(pool)
 }
 
-pub async fn obtain_redis_pool() -> Result<ConnectionPool, Box<dyn std::error::Error + Send + Sync>>
-{
-    let redis_url = env::var("REDIS_URL")?;
+pub async fn obtain_redis_pool(
+    redis_url: &str,
+) -> Result<ConnectionPool, Box<dyn std::error::Error + Send + Sync>> {
     let pool = ConnectionPool::create(redis_url, None, num_cpus::get()).await?;
 
     Ok(pool)
 }
+
+/// Applies the migrations that have not been applied yet.
+/// Refuses to do anything if the database has a newer schema than the one this build knows about,
+/// as that means an older version of the bot is being ran against it.
+pub async fn run_migrations(pool: &PgPool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let latest_known = MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0);
+
+    let has_migrations_table = sqlx::query!(
+        "SELECT EXISTS (SELECT 1 FROM information_schema.tables WHERE table_schema = current_schema() AND table_name = '_sqlx_migrations')"
+    )
+    .fetch_one(pool)
+    .await?
+    .exists
+    .unwrap_or(false);
+
+    if has_migrations_table {
+        let latest_applied =
+            sqlx::query!("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+                .fetch_one(pool)
+                .await?
+                .max
+                .unwrap_or(0);
+
+        if latest_applied > latest_known {
+            return Err(format!(
+                "The database schema is at version {}, but this build only knows up to version {}. Refusing to start, update the bot first.",
+                latest_applied, latest_known
+            )
+            .into());
+        }
+    }
+
+    info!(
+        "Applying database migrations up to version {}.",
+        latest_known
+    );
+    MIGRATOR.run(pool).await?;
+    info!("Database migrations applied.");
+
+    Ok(())
+}
@@ -0,0 +1,402 @@
+//! RSS and Atom feed subscriptions.
+//!
+//! Every feed is requested once per run no matter how many channels are subscribed to it, and
+//! the items are de-duplicated per subscription by their guid on the `sent_feed_items` table.
+//!
+//! The feeds are added by guild administrators, so they can only point to public addresses.
+//! Every redirect is checked the same way, and the request goes to the address that was checked.
+use crate::utils::sent_posts::RETENTION_DAYS;
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::{header::*, redirect::Policy, Client as ReqwestClient, Response, Url};
+use serenity::builder::CreateEmbed;
+use sqlx::PgPool;
+
+pub const MAX_FEEDS_PER_GUILD: usize = 25;
+pub const MAX_FILTER_LENGTH: usize = 100;
+// When a feed gets a lot of items at once, only the newest ones are sent.
+pub const MAX_ITEMS_PER_POLL: usize = 5;
+// Bigger feeds are rejected, 5MB
+const MAX_FEED_SIZE: usize = 5 * 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_SUMMARY_LENGTH: usize = 350;
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct FeedItem {
+    pub guid: String,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub summary: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+}
+
+impl FeedItem {
+    /// Whether the title or the summary contain the filter, ignoring the case.
+    /// Every item matches when there's no filter.
+    pub fn matches(&self, filter: Option<&str>) -> bool {
+        let filter = match filter {
+            Some(x) if !x.trim().is_empty() => x.trim().to_lowercase(),
+            _ => return true,
+        };
+
+        [&self.title, &self.summary]
+            .iter()
+            .filter_map(|x| x.as_ref())
+            .any(|x| x.to_lowercase().contains(&filter))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub title: Option<String>,
+    /// Sorted from the oldest to the newest.
+    pub items: Vec<FeedItem>,
+}
+
+impl Feed {
+    pub fn guids(&self) -> Vec<String> {
+        self.items.iter().map(|i| i.guid.clone()).collect()
+    }
+}
+
+/// Checks that a feed url is valid, only http and https are allowed.
+pub fn validate_feed_url(url: &str) -> Result<(), &'static str> {
+    let url = Url::parse(url).map_err(|_| "That's not a valid url.")?;
+
+    if !["http", "https"].contains(&url.scheme()) {
+        return Err("Only http and https feeds are supported.");
+    }
+
+    let host = url.host_str().ok_or("That's not a valid url.")?;
+
+    // The names are checked when the feed is requested, as they can resolve to anything.
+    if host_ip(host).map_or(false, |ip| !is_global_ip(ip)) {
+        return Err("Only feeds on public addresses are supported.");
+    }
+
+    Ok(())
+}
+
+// The address of a host that's an ip, IPv6 hosts are wrapped with [] on urls.
+fn host_ip(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+fn is_global_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space, used by carrier-grade NATs.
+        || (a == 100 && (b & 0b1100_0000) == 64)
+        // IETF protocol assignments.
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking.
+        || (a == 198 && (b & 0b1111_1110) == 18)
+        // Reserved.
+        || a >= 240)
+}
+
+fn is_global_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+
+    // IPv4-mapped and NAT64 addresses go to the IPv4 address they contain.
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_global_ipv4(ipv4);
+    }
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+        return is_global_ipv4(Ipv4Addr::new(a, b, c, d));
+    }
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local.
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link local and the deprecated site local.
+        || (segments[0] & 0xffc0) == 0xfe80
+        || (segments[0] & 0xffc0) == 0xfec0
+        // Documentation.
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+}
+
+/// Whether an address is reachable from the internet, rather than a private, loopback, link
+/// local or otherwise reserved one.
+pub fn is_global_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_global_ipv4(ip),
+        IpAddr::V6(ip) => is_global_ipv6(ip),
+    }
+}
+
+// Resolves the host of the url, and returns the address to connect to.
+// Hosts that resolve to any address that isn't public are rejected.
+async fn resolve_public_host(url: &Url) -> Result<SocketAddr, Box<dyn Error + Send + Sync>> {
+    let host = url.host_str().ok_or("The url has no host.")?;
+    let port = url.port_or_known_default().ok_or("The url has no port.")?;
+
+    let addresses = if let Some(ip) = host_ip(host) {
+        vec![SocketAddr::new(ip, port)]
+    } else {
+        tokio::net::lookup_host((host, port))
+            .await?
+            .collect::<Vec<_>>()
+    };
+
+    if addresses.is_empty() {
+        return Err(format!("{} didn't resolve to any address.", host).into());
+    }
+
+    if let Some(address) = addresses.iter().find(|a| !is_global_ip(a.ip())) {
+        return Err(format!(
+            "{} resolves to the non-public address {}.",
+            host,
+            address.ip()
+        )
+        .into());
+    }
+
+    Ok(addresses[0])
+}
+
+// Sends a request to the url, following the redirects by hand, so every address is checked.
+async fn get_public(
+    url: &str,
+    headers: HeaderMap,
+) -> Result<Response, Box<dyn Error + Send + Sync>> {
+    let mut url = Url::parse(url)?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(format!("Unsupported scheme on {}.", url).into());
+        }
+
+        let address = resolve_public_host(&url).await?;
+
+        // The client connects to the checked address, so the name can't resolve differently
+        // between the check and the request.
+        let mut builder = ReqwestClient::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(Policy::none());
+        if let Some(domain) = url.host_str().filter(|h| host_ip(h).is_none()) {
+            builder = builder.resolve(domain, address);
+        }
+
+        let resp = builder
+            .build()?
+            .get(url.clone())
+            .headers(headers.clone())
+            .send()
+            .await?;
+
+        if !resp.status().is_redirection() {
+            return Ok(resp);
+        }
+
+        let location = resp
+            .headers()
+            .get(LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .ok_or("The redirect has no location.")?;
+        url = url.join(location)?;
+    }
+
+    Err("The feed redirected too many times.".into())
+}
+
+/// Obtains and parses an RSS or Atom feed.
+pub async fn fetch_feed(url: &str) -> Result<Feed, Box<dyn Error + Send + Sync>> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        ACCEPT,
+        "application/rss+xml,application/atom+xml,application/xml;q=0.9,*/*;q=0.8"
+            .parse()
+            .unwrap(),
+    );
+    headers.insert(
+        USER_AGENT,
+        concat!("RoboArc/", env!("CARGO_PKG_VERSION"))
+            .parse()
+            .unwrap(),
+    );
+
+    let resp = get_public(url, headers).await?.error_for_status()?;
+
+    if resp
+        .content_length()
+        .map_or(false, |x| x as usize > MAX_FEED_SIZE)
+    {
+        return Err("The feed is too big.".into());
+    }
+
+    let bytes = resp.bytes().await?;
+    if bytes.len() > MAX_FEED_SIZE {
+        return Err("The feed is too big.".into());
+    }
+
+    let feed = feed_rs::parser::parse(&bytes[..])?;
+
+    let mut items = feed
+        .entries
+        .into_iter()
+        .map(|entry| FeedItem {
+            guid: entry.id,
+            title: entry.title.map(|t| strip_html(&t.content)),
+            url: entry.links.into_iter().next().map(|l| l.href),
+            summary: entry
+                .summary
+                .map(|s| s.content)
+                .or_else(|| entry.content.and_then(|c| c.body))
+                .map(|s| strip_html(&s)),
+            published: entry.published.or(entry.updated),
+        })
+        .collect::<Vec<_>>();
+
+    // Feeds list the newest items first, but not all of them do, so the dates are used if
+    // every item has one.
+    if items.iter().all(|i| i.published.is_some()) {
+        items.sort_by_key(|i| i.published);
+    } else {
+        items.reverse();
+    }
+
+    Ok(Feed {
+        title: feed.title.map(|t| strip_html(&t.content)),
+        items,
+    })
+}
+
+// Summaries are usually html, the tags are removed and the common entities decoded.
+fn strip_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                out.push(' ');
+            }
+            _ if !in_tag => out.push(c),
+            _ => (),
+        }
+    }
+
+    let out = out
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() > max {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn feed_item_embed<'a>(
+    e: &'a mut CreateEmbed,
+    feed: &Feed,
+    item: &FeedItem,
+) -> &'a mut CreateEmbed {
+    e.title(truncate(item.title.as_deref().unwrap_or("Untitled"), 256));
+
+    if let Some(url) = &item.url {
+        e.url(url);
+    }
+
+    if let Some(summary) = item.summary.as_ref().filter(|s| !s.is_empty()) {
+        e.description(truncate(summary, MAX_SUMMARY_LENGTH));
+    }
+
+    if let Some(title) = &feed.title {
+        e.author(|a| a.name(truncate(title, 256)));
+    }
+
+    if let Some(published) = item.published {
+        e.timestamp(published);
+    }
+
+    e
+}
+
+/// Obtains which of the items were already sent by a subscription.
+pub async fn already_sent(
+    pool: &PgPool,
+    subscription_id: i32,
+    guids: &[String],
+) -> Result<HashSet<String>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT guid FROM sent_feed_items WHERE subscription_id = $1 AND guid = ANY($2)",
+        subscription_id,
+        guids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.guid).collect())
+}
+
+/// Records the items as sent, the same way as the booru posts.
+pub async fn mark_as_sent(
+    pool: &PgPool,
+    subscription_id: i32,
+    guids: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut guids = guids.to_vec();
+    guids.sort();
+    guids.dedup();
+
+    if guids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "INSERT INTO sent_feed_items (subscription_id, guid) SELECT $1, unnest($2::text[]) ON CONFLICT (subscription_id, guid) DO UPDATE SET last_seen_at = now() WHERE sent_feed_items.last_seen_at < now() - interval '1 day'",
+        subscription_id,
+        &guids
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes the items that were not on their feed for longer than the retention.
+pub async fn prune_sent_items(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM sent_feed_items WHERE last_seen_at < now() - make_interval(days => $1)",
+        RETENTION_DAYS
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,392 @@
+use crate::framework::MASTER_GROUP;
+use crate::global_data::{DatabasePool, GuildSettingsCache, UserPrefixes};
+use crate::utils::{aliases::CommandAlias, command_rules::CommandRule};
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    model::id::{GuildId, RoleId, UserId},
+    prelude::Context,
+};
+use sqlx::PgExecutor;
+
+/// The prefix used when nothing else is configured.
+pub const DEFAULT_PREFIX: &str = ".";
+
+// Limits to keep the prefix checks on every message cheap.
+pub const MAX_PREFIX_LENGTH: usize = 16;
+pub const MAX_ADDITIONAL_PREFIXES: usize = 5;
+
+// The cached settings expire after this, so the guilds that stop sending messages make room.
+// Every command and the API invalidate the settings they change, it's not needed for that.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+// When the cache is full, the expired entries are removed, and then the oldest ones.
+const MAX_CACHED_GUILDS: usize = 10_000;
+
+/// The per guild configuration that's needed on every message.
+/// This is cached so the database doesn't get queried every time a message is sent.
+#[derive(Debug, Clone, Default)]
+pub struct GuildSettings {
+    pub prefix: Option<String>,
+    pub additional_prefixes: Vec<String>,
+    pub disallowed_commands: Vec<String>,
+    pub command_rules: Vec<CommandRule>,
+    /// The custom text commands of the guild, by name.
+    pub tags: HashMap<String, String>,
+    /// The custom aliases of the guild, by name.
+    pub aliases: HashMap<String, CommandAlias>,
+}
+
+impl GuildSettings {
+    /// The main prefix of the guild.
+    pub fn prefix(&self) -> String {
+        self.prefix
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PREFIX.to_string())
+    }
+
+    /// Every prefix the guild accepts, starting with the main one.
+    pub fn prefixes(&self) -> Vec<String> {
+        let mut prefixes = vec![self.prefix()];
+
+        // Empty prefixes saved before they were validated would match every message.
+        for prefix in &self.additional_prefixes {
+            if !prefix.trim().is_empty() && !prefixes.contains(prefix) {
+                prefixes.push(prefix.to_string());
+            }
+        }
+
+        prefixes
+    }
+
+    pub fn is_disallowed(&self, command_name: &str) -> bool {
+        self.disallowed_commands.iter().any(|c| c == command_name)
+    }
+}
+
+/// The cached settings of the guilds.
+#[derive(Debug, Default)]
+pub struct SettingsCache {
+    entries: HashMap<u64, (Instant, GuildSettings)>,
+    // Bumped on every invalidation. Settings loaded while it changed may be outdated, so they are
+    // not cached.
+    generation: u64,
+}
+
+impl SettingsCache {
+    fn get(&self, guild_id: u64) -> Option<&GuildSettings> {
+        self.entries
+            .get(&guild_id)
+            .filter(|(cached_at, _)| cached_at.elapsed() < CACHE_TTL)
+            .map(|(_, settings)| settings)
+    }
+
+    fn insert(&mut self, guild_id: u64, settings: GuildSettings, generation: u64) {
+        if generation != self.generation {
+            return;
+        }
+
+        if self.entries.len() >= MAX_CACHED_GUILDS {
+            self.entries
+                .retain(|_, (cached_at, _)| cached_at.elapsed() < CACHE_TTL);
+        }
+
+        if self.entries.len() >= MAX_CACHED_GUILDS {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (cached_at, _))| *cached_at)
+                .map(|(id, _)| *id);
+
+            if let Some(id) = oldest {
+                self.entries.remove(&id);
+            }
+        }
+
+        self.entries.insert(guild_id, (Instant::now(), settings));
+    }
+
+    fn invalidate(&mut self, guild_id: u64) {
+        self.generation += 1;
+        self.entries.remove(&guild_id);
+    }
+}
+
+/// Obtains the settings of a guild, from the cache if they are there, or from the database.
+pub async fn get_guild_settings(ctx: &Context, guild_id: impl Into<GuildId>) -> GuildSettings {
+    let guild_id = guild_id.into();
+
+    let (pool, cache) = {
+        let data_read = ctx.data.read().await;
+        let pool = data_read.get::<DatabasePool>().unwrap();
+        let cache = data_read.get::<GuildSettingsCache>().unwrap();
+
+        (pool.clone(), cache.clone())
+    };
+
+    let generation = {
+        let cache = cache.read().await;
+
+        if let Some(settings) = cache.get(guild_id.0) {
+            return settings.clone();
+        }
+
+        cache.generation
+    };
+
+    // The queries are independent, so they run at the same time.
+    let queried = tokio::try_join!(
+        sqlx::query!(
+            "SELECT prefix, additional_prefixes, disallowed_commands FROM prefixes WHERE guild_id = $1",
+            guild_id.0 as i64
+        )
+        .fetch_optional(&pool),
+        sqlx::query!(
+            "SELECT id, target, is_group, channel_id, role_id, allow FROM command_rules WHERE guild_id = $1 ORDER BY id",
+            guild_id.0 as i64
+        )
+        .fetch_all(&pool),
+        sqlx::query!(
+            "SELECT name, content FROM tags WHERE guild_id = $1",
+            guild_id.0 as i64
+        )
+        .fetch_all(&pool),
+        sqlx::query!(
+            "SELECT name, command, arguments FROM command_aliases WHERE guild_id = $1",
+            guild_id.0 as i64
+        )
+        .fetch_all(&pool),
+    );
+
+    let (row, rules, tags, aliases) = match queried {
+        Ok(x) => x,
+        Err(why) => {
+            // Don't cache anything, so it's retried on the next message.
+            error!("Could not query database: {}", why);
+            return GuildSettings::default();
+        }
+    };
+
+    let tags = tags.into_iter().map(|t| (t.name, t.content)).collect();
+
+    let aliases = aliases
+        .into_iter()
+        .map(|a| {
+            (
+                a.name,
+                CommandAlias {
+                    command: a.command,
+                    arguments: a.arguments,
+                },
+            )
+        })
+        .collect();
+
+    let command_rules = rules
+        .into_iter()
+        .map(|r| CommandRule {
+            id: r.id,
+            target: r.target,
+            is_group: r.is_group,
+            channel_id: r.channel_id.map(|c| c as u64),
+            role_id: r.role_id.map(|r| r as u64),
+            allow: r.allow,
+        })
+        .collect();
+
+    let settings = if let Some(row) = row {
+        GuildSettings {
+            prefix: row.prefix,
+            additional_prefixes: row.additional_prefixes.unwrap_or_default(),
+            disallowed_commands: row.disallowed_commands.unwrap_or_default(),
+            command_rules,
+            tags,
+            aliases,
+        }
+    } else {
+        GuildSettings {
+            command_rules,
+            tags,
+            aliases,
+            ..GuildSettings::default()
+        }
+    };
+
+    cache
+        .write()
+        .await
+        .insert(guild_id.0, settings.clone(), generation);
+
+    settings
+}
+
+/// Removes the cached settings of a guild.
+/// This needs to be called every time the settings of the guild are modified.
+pub async fn invalidate_guild_settings(ctx: &Context, guild_id: impl Into<GuildId>) {
+    let cache = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<GuildSettingsCache>().unwrap().clone()
+    };
+
+    cache.write().await.invalidate(guild_id.into().0);
+}
+
+/// Obtains the personal prefix of a user, if they have one configured.
+pub async fn get_user_prefix(ctx: &Context, user_id: impl Into<UserId>) -> Option<String> {
+    let user_prefixes = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<UserPrefixes>().unwrap().clone()
+    };
+
+    let prefix = user_prefixes.read().await.get(&user_id.into().0).cloned();
+    prefix
+}
+
+/// Checks if a prefix can be used.
+pub fn validate_prefix(prefix: &str) -> Result<(), String> {
+    if prefix.trim().is_empty() {
+        return Err("Invalid prefix was given".to_string());
+    }
+
+    if prefix.chars().count() > MAX_PREFIX_LENGTH {
+        return Err(format!(
+            "The prefix can't be longer than {} characters.",
+            MAX_PREFIX_LENGTH
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolves the name a command is stored with when it gets disabled.
+/// Every booru command is disabled as a whole, under the name of the `picture` command.
+pub fn resolve_disableable_command(
+    command_name: &str,
+    booru_commands: &HashSet<String>,
+) -> Option<&'static str> {
+    let command_name = if booru_commands.contains(command_name) {
+        "picture"
+    } else {
+        command_name
+    };
+
+    MASTER_GROUP
+        .options
+        .sub_groups
+        .iter()
+        .flat_map(|g| g.options.commands.iter())
+        .find(|c| c.options.names.contains(&command_name))
+        .map(|c| c.options.names[0])
+}
+
+/// Changes the main prefix of a guild.
+pub async fn set_prefix(
+    ctx: &Context,
+    guild_id: impl Into<GuildId>,
+    prefix: &str,
+) -> Result<(), sqlx::Error> {
+    let guild_id = guild_id.into();
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    save_prefix(&pool, guild_id, prefix).await?;
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    Ok(())
+}
+
+/// Saves the main prefix of a guild, without invalidating the cached settings.
+/// Used to change it as part of a transaction, the settings have to be invalidated after it.
+pub async fn save_prefix<'c>(
+    executor: impl PgExecutor<'c>,
+    guild_id: impl Into<GuildId>,
+    prefix: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO prefixes (guild_id, prefix) VALUES ($1, $2) ON CONFLICT (guild_id) DO UPDATE SET prefix = $2",
+        guild_id.into().0 as i64,
+        prefix
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Replaces the disabled commands of a guild.
+/// The names need to be resolved with `resolve_disableable_command` first.
+pub async fn set_disabled_commands(
+    ctx: &Context,
+    guild_id: impl Into<GuildId>,
+    disallowed_commands: &[String],
+) -> Result<(), sqlx::Error> {
+    let guild_id = guild_id.into();
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    save_disabled_commands(&pool, guild_id, disallowed_commands).await?;
+    invalidate_guild_settings(ctx, guild_id).await;
+
+    Ok(())
+}
+
+/// Saves the disabled commands of a guild, without invalidating the cached settings.
+/// Used to change them as part of a transaction, the settings have to be invalidated after it.
+pub async fn save_disabled_commands<'c>(
+    executor: impl PgExecutor<'c>,
+    guild_id: impl Into<GuildId>,
+    disallowed_commands: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO prefixes (guild_id, disallowed_commands) VALUES ($1, $2) ON CONFLICT (guild_id) DO UPDATE SET disallowed_commands = $2",
+        guild_id.into().0 as i64,
+        disallowed_commands
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Enables or disables the anti spam of a guild.
+pub async fn set_anti_spam<'c>(
+    executor: impl PgExecutor<'c>,
+    guild_id: impl Into<GuildId>,
+    enabled: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO anti_spam (guild_id, enabled) VALUES ($1, $2) ON CONFLICT (guild_id) DO UPDATE SET enabled = $2",
+        guild_id.into().0 as i64,
+        enabled
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Changes the role used to mute members on a guild.
+pub async fn set_mute_role<'c>(
+    executor: impl PgExecutor<'c>,
+    guild_id: impl Into<GuildId>,
+    role_id: impl Into<RoleId>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO muted_roles (guild_id, role_id) VALUES ($1, $2) ON CONFLICT (guild_id) DO UPDATE SET role_id = $2",
+        guild_id.into().0 as i64,
+        role_id.into().0 as i64
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
@@ -2,7 +2,7 @@
 use bitflags::bitflags;
 
 use serenity::model::id::GuildId;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 
 bitflags! {
     pub struct LoggingEvents: u64 {
@@ -69,3 +69,21 @@ pub async fn guild_has_logging(
         None
     }
 }
+
+/// Changes the events that get logged on a guild.
+/// Returns false if the guild doesn't have a logging channel configured.
+pub async fn set_logging_events<'c>(
+    executor: impl PgExecutor<'c>,
+    guild_id: impl Into<GuildId>,
+    events: LoggingEvents,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE logging_channels SET bitwise = $2 WHERE guild_id = $1",
+        guild_id.into().0 as i64,
+        events.bits() as i64
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() != 0)
+}
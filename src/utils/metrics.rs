@@ -0,0 +1,285 @@
+//! Runtime metrics, exposed on the web server in the Prometheus text format.
+use crate::global_data::{
+    BackgroundTasks, BotMetrics, DatabasePool, Lavalink, ShardManagerContainer,
+};
+
+use std::{collections::HashMap, fmt::Write, time::Duration};
+
+use serenity::{client::bridge::gateway::ShardId, gateway::ConnectionStage, prelude::Context};
+
+#[derive(Debug, Default, Clone)]
+struct CommandMetrics {
+    uses: u64,
+    failures: u64,
+    total_duration: Duration,
+}
+
+/// The metrics that are not available anywhere else, and need to be counted as they happen.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    commands: HashMap<String, CommandMetrics>,
+}
+
+impl Metrics {
+    pub fn record_command(&mut self, command_name: &str, duration: Duration, failed: bool) {
+        let command = self.commands.entry(command_name.to_string()).or_default();
+
+        command.uses += 1;
+        command.total_duration += duration;
+        if failed {
+            command.failures += 1;
+        }
+    }
+}
+
+// Label values can't contain quotes, backslashes or newlines without escaping them.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Renders every metric in the Prometheus text format.
+pub async fn render_metrics(ctx: &Context) -> String {
+    let (shard_manager, pool, lavalink, metrics, tasks) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<ShardManagerContainer>().unwrap().clone(),
+            data_read.get::<DatabasePool>().unwrap().clone(),
+            data_read.get::<Lavalink>().cloned(),
+            data_read.get::<BotMetrics>().unwrap().clone(),
+            data_read.get::<BackgroundTasks>().unwrap().clone(),
+        )
+    };
+
+    let mut out = String::new();
+
+    let shards = {
+        let manager = shard_manager.lock().await;
+        let runners = manager.runners.lock().await;
+        runners
+            .iter()
+            .map(|(ShardId(id), runner)| (*id, runner.latency, runner.stage))
+            .collect::<Vec<_>>()
+    };
+
+    write_header(
+        &mut out,
+        "roboarc_shard_latency_seconds",
+        "gauge",
+        "Latency of the last heartbeat of every shard.",
+    );
+    for (id, latency, _) in &shards {
+        if let Some(latency) = latency {
+            let _ = writeln!(
+                out,
+                "roboarc_shard_latency_seconds{{shard=\"{}\"}} {}",
+                id,
+                latency.as_secs_f64()
+            );
+        }
+    }
+
+    write_header(
+        &mut out,
+        "roboarc_shard_connected",
+        "gauge",
+        "Whether every shard is connected to the gateway.",
+    );
+    for (id, _, stage) in &shards {
+        let _ = writeln!(
+            out,
+            "roboarc_shard_connected{{shard=\"{}\"}} {}",
+            id,
+            (*stage == ConnectionStage::Connected) as u8
+        );
+    }
+
+    write_header(
+        &mut out,
+        "roboarc_guilds",
+        "gauge",
+        "Amount of guilds the bot is in.",
+    );
+    let _ = writeln!(out, "roboarc_guilds {}", ctx.cache.guild_count());
+
+    {
+        let metrics = metrics.read().await;
+
+        let mut commands = metrics.commands.iter().collect::<Vec<_>>();
+        commands.sort_by(|a, b| a.0.cmp(b.0));
+
+        write_header(
+            &mut out,
+            "roboarc_commands_total",
+            "counter",
+            "Amount of times every command was ran.",
+        );
+        for (name, command) in &commands {
+            let _ = writeln!(
+                out,
+                "roboarc_commands_total{{command=\"{}\"}} {}",
+                escape_label(name),
+                command.uses
+            );
+        }
+
+        write_header(
+            &mut out,
+            "roboarc_command_failures_total",
+            "counter",
+            "Amount of times every command failed.",
+        );
+        for (name, command) in &commands {
+            let _ = writeln!(
+                out,
+                "roboarc_command_failures_total{{command=\"{}\"}} {}",
+                escape_label(name),
+                command.failures
+            );
+        }
+
+        write_header(
+            &mut out,
+            "roboarc_command_duration_seconds",
+            "summary",
+            "Time spent running every command.",
+        );
+        for (name, command) in &commands {
+            let name = escape_label(name);
+            let _ = writeln!(
+                out,
+                "roboarc_command_duration_seconds_sum{{command=\"{}\"}} {}",
+                name,
+                command.total_duration.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "roboarc_command_duration_seconds_count{{command=\"{}\"}} {}",
+                name, command.uses
+            );
+        }
+    }
+
+    let tasks = tasks.statuses().await;
+
+    write_header(
+        &mut out,
+        "roboarc_task_last_success_timestamp_seconds",
+        "gauge",
+        "Last time every background task finished a run without errors.",
+    );
+    for (name, status) in &tasks {
+        if let Some(time) = status.last_success {
+            let _ = writeln!(
+                out,
+                "roboarc_task_last_success_timestamp_seconds{{task=\"{}\"}} {}",
+                name,
+                time.timestamp()
+            );
+        }
+    }
+
+    write_header(
+        &mut out,
+        "roboarc_task_last_duration_seconds",
+        "gauge",
+        "Time the last run of every background task took.",
+    );
+    for (name, status) in &tasks {
+        if let Some(duration) = status.last_duration {
+            let _ = writeln!(
+                out,
+                "roboarc_task_last_duration_seconds{{task=\"{}\"}} {}",
+                name,
+                duration.as_secs_f64()
+            );
+        }
+    }
+
+    write_header(
+        &mut out,
+        "roboarc_task_failures_total",
+        "counter",
+        "Amount of runs of every background task that failed or panicked.",
+    );
+    for (name, status) in &tasks {
+        let _ = writeln!(
+            out,
+            "roboarc_task_failures_total{{task=\"{}\"}} {}",
+            name, status.failures
+        );
+    }
+
+    if let Some(lavalink) = lavalink {
+        write_header(
+            &mut out,
+            "roboarc_lavalink_players",
+            "gauge",
+            "Amount of guilds with an active music player.",
+        );
+        let _ = writeln!(
+            out,
+            "roboarc_lavalink_players {}",
+            lavalink.nodes().await.len()
+        );
+    }
+
+    write_header(
+        &mut out,
+        "roboarc_database_connections",
+        "gauge",
+        "Connections opened by the database pool.",
+    );
+    let _ = writeln!(out, "roboarc_database_connections {}", pool.size());
+
+    write_header(
+        &mut out,
+        "roboarc_database_idle_connections",
+        "gauge",
+        "Connections of the database pool that are not in use.",
+    );
+    let _ = writeln!(out, "roboarc_database_idle_connections {}", pool.num_idle());
+
+    out
+}
+
+/// Checks if the bot is able to work.
+/// Returns the reason it isn't healthy, if it isn't.
+pub async fn check_health(ctx: &Context) -> Result<(), String> {
+    let (shard_manager, pool) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<ShardManagerContainer>().unwrap().clone(),
+            data_read.get::<DatabasePool>().unwrap().clone(),
+        )
+    };
+
+    {
+        let manager = shard_manager.lock().await;
+        let runners = manager.runners.lock().await;
+
+        if runners.is_empty() {
+            return Err("No shards are running.".to_string());
+        }
+
+        for (ShardId(id), runner) in runners.iter() {
+            if runner.stage != ConnectionStage::Connected {
+                return Err(format!("Shard {} is {}.", id, runner.stage));
+            }
+        }
+    }
+
+    let query = sqlx::query!("SELECT 1 AS ok").fetch_one(&pool);
+    match tokio::time::timeout(Duration::from_secs(5), query).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(why)) => Err(format!("The database is not reachable: {}", why)),
+        Err(_) => Err("The database took too long to respond.".to_string()),
+    }
+}
@@ -1,7 +1,22 @@
+pub mod aliases;
+pub mod analytics;
 pub mod basic_functions;
+pub mod blocklist;
 pub mod booru;
 pub mod checks;
+pub mod command_rules;
 pub mod database;
+pub mod feeds;
+pub mod guild_settings;
 pub mod logging;
+pub mod metrics;
 pub mod osu;
 pub mod osu_model;
+pub mod reminders;
+pub mod scheduler;
+pub mod sent_posts;
+pub mod shutdown;
+pub mod tags;
+pub mod tasks;
+pub mod time_parser;
+pub mod twitch;
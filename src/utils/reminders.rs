@@ -0,0 +1,292 @@
+//! Recurring reminders, and the snooze buttons of the delivered reminders.
+use crate::global_data::DatabasePool;
+use crate::utils::basic_functions::string_to_seconds;
+use crate::utils::scheduler::{schedule_job, JobKind};
+use crate::utils::time_parser::{local_to_utc, parse_time_of_day, parse_weekday, DEFAULT_TIME};
+
+use std::{error::Error, fmt};
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serenity::{
+    builder::CreateComponents,
+    model::application::{
+        component::ButtonStyle,
+        interaction::{message_component::MessageComponentInteraction, InteractionResponseType},
+    },
+    prelude::Context,
+};
+
+// Shorter intervals would be spam more than reminders.
+pub const MIN_RECURRENCE_INTERVAL: u64 = 600;
+pub const MAX_RECURRING_REMINDERS: i64 = 10;
+pub const SNOOZE_PREFIX: &str = "reminder_snooze:";
+const SNOOZE_OPTIONS: [(u64, &str); 3] = [(600, "10 minutes"), (3600, "1 hour"), (86400, "1 day")];
+
+const ALL_DAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// How a reminder repeats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recurrence {
+    /// Every amount of seconds.
+    Interval(u64),
+    /// On some days of the week at a time, in the time zone of the user.
+    Weekly {
+        days: Vec<Weekday>,
+        time: NaiveTime,
+        tz: Tz,
+    },
+}
+
+fn parse_days(text: &str) -> Option<Vec<Weekday>> {
+    let mut days = Vec::new();
+
+    for day in text.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match day {
+            "day" | "days" | "daily" | "everyday" => days.extend(ALL_DAYS.iter()),
+            "weekday" | "weekdays" => days.extend(ALL_DAYS[..5].iter()),
+            "weekend" | "weekends" => days.extend(ALL_DAYS[5..].iter()),
+            _ => {
+                days.push(parse_weekday(day)?);
+            }
+        }
+    }
+
+    days.sort_by_key(|d| d.num_days_from_monday());
+    days.dedup();
+
+    if days.is_empty() {
+        None
+    } else {
+        Some(days)
+    }
+}
+
+impl Recurrence {
+    /// Parses a recurrence, like `2h`, `1D 12h`, `day 9:00`, `monday 9am` or `mon,fri 18:30`.
+    /// The times of the weekly ones are on the given time zone.
+    pub fn parse(text: &str, tz: Tz) -> Result<Self, &'static str> {
+        let text = text.trim();
+
+        // The units of the intervals are case sensitive, `1M` is a month and `1m` a minute.
+        if text.starts_with(|c: char| c.is_ascii_digit()) && parse_time_of_day(text).is_none() {
+            let seconds = string_to_seconds(text.replace(' ', ""));
+
+            if seconds == 0 {
+                return Err("That's not a valid interval, use something like `2h` or `1W`.");
+            }
+            if seconds < MIN_RECURRENCE_INTERVAL {
+                return Err("A reminder can't repeat more often than every 10 minutes.");
+            }
+
+            return Ok(Self::Interval(seconds));
+        }
+
+        let text = text.to_lowercase();
+        let mut words = text.split_whitespace().collect::<Vec<_>>();
+
+        let time = match words.last().and_then(|w| parse_time_of_day(w)) {
+            Some(time) => {
+                words.pop();
+                time
+            }
+            None => NaiveTime::from_hms(DEFAULT_TIME.0, DEFAULT_TIME.1, 0),
+        };
+
+        let days = parse_days(&words.join(",")).ok_or(
+            "That's not a valid recurrence, use something like `monday 9:00`, `mon,fri 18:30` or `day 9am`.",
+        )?;
+
+        Ok(Self::Weekly { days, time, tz })
+    }
+
+    /// The form stored on `reminders.recurrence`.
+    pub fn to_db(&self) -> String {
+        match self {
+            Self::Interval(seconds) => format!("interval {}", seconds),
+            Self::Weekly { days, time, tz } => format!(
+                "weekly {} {} {}",
+                days.iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                time.format("%H:%M"),
+                tz.name()
+            ),
+        }
+    }
+
+    pub fn from_db(text: &str) -> Option<Self> {
+        let mut split = text.split(' ');
+
+        match split.next()? {
+            "interval" => Some(Self::Interval(split.next()?.parse().ok()?)),
+            "weekly" => Some(Self::Weekly {
+                days: parse_days(&split.next()?.to_lowercase())?,
+                time: NaiveTime::parse_from_str(split.next()?, "%H:%M").ok()?,
+                // The ones set before the time zones were added are in UTC.
+                tz: split.next().map_or(Some(Tz::UTC), |x| x.parse().ok())?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The first time the reminder has to fire after the date.
+    pub fn next_after(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Self::Interval(seconds) => after + Duration::seconds(*seconds as i64),
+            Self::Weekly { days, time, tz } => {
+                let start = after.with_timezone(tz).naive_local().date();
+
+                (0..=7)
+                    .map(|i| start + Duration::days(i))
+                    .filter(|date| days.contains(&date.weekday()))
+                    .map(|date| local_to_utc(*tz, date.and_time(*time)))
+                    .find(|date| *date > after)
+                    .unwrap_or_else(|| after + Duration::weeks(1))
+            }
+        }
+    }
+
+    /// The next time the reminder has to fire, skipping the times that were missed.
+    pub fn next_from_now(&self, last: DateTime<Utc>) -> DateTime<Utc> {
+        let now = Utc::now();
+        let mut next = self.next_after(last);
+
+        while next <= now {
+            next = self.next_after(next);
+        }
+
+        next
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Interval(seconds) => write!(
+                f,
+                "every {}",
+                humantime::format_duration(std::time::Duration::from_secs(*seconds))
+            ),
+            Self::Weekly { days, time, tz } => {
+                let days = if days.len() == 7 {
+                    "day".to_string()
+                } else {
+                    days.iter()
+                        .map(|d| d.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+
+                write!(
+                    f,
+                    "every {} at {} {}",
+                    days,
+                    time.format("%H:%M"),
+                    tz.name()
+                )
+            }
+        }
+    }
+}
+
+/// Adds the snooze buttons to a delivered reminder.
+pub fn snooze_buttons(c: &mut CreateComponents, user_id: i64) -> &mut CreateComponents {
+    c.create_action_row(|ar| {
+        for (seconds, label) in SNOOZE_OPTIONS.iter() {
+            ar.create_button(|b| {
+                b.style(ButtonStyle::Secondary);
+                b.label(format!("Snooze {}", label));
+                b.custom_id(format!("{}{}:{}", SNOOZE_PREFIX, seconds, user_id))
+            });
+        }
+        ar
+    })
+}
+
+/// Sets the reminder again, after the time of the button that was pressed.
+/// The new reminder links to the delivered one, and has the same message.
+pub async fn handle_snooze(
+    ctx: &Context,
+    component: &MessageComponentInteraction,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let data = component
+        .data
+        .custom_id
+        .strip_prefix(SNOOZE_PREFIX)
+        .unwrap_or_default();
+    let mut split = data.split(':');
+
+    let (seconds, user_id) = match (
+        split.next().and_then(|x| x.parse::<u64>().ok()),
+        split.next().and_then(|x| x.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Ok(()),
+    };
+
+    if component.user.id.0 as i64 != user_id {
+        component
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.content("Only the owner of the reminder can snooze it.")
+                            .ephemeral(true)
+                    })
+            })
+            .await?;
+        return Ok(());
+    }
+
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let message = component
+        .message
+        .embeds
+        .first()
+        .and_then(|e| e.description.clone())
+        .filter(|d| d != "No Message.");
+    let date = Utc::now() + Duration::seconds(seconds as i64);
+
+    let row = sqlx::query!("INSERT INTO reminders (date, message_id, channel_id, guild_id, user_id, message) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        date,
+        component.message.id.0 as i64,
+        component.channel_id.0 as i64,
+        component.guild_id.map_or(0, |g| g.0 as i64),
+        user_id,
+        message,
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    schedule_job(ctx, JobKind::Reminder, row.id, date).await;
+
+    // The buttons are removed, so the same reminder can't be snoozed twice.
+    component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.content(format!(
+                        "<@!{}>: Reminder! Snoozed until <t:{}:f>.",
+                        user_id,
+                        date.timestamp()
+                    ))
+                    .components(|c| c.set_action_rows(vec![]))
+                })
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,469 @@
+//! Scheduler of the reminders and the temporal mutes.
+//!
+//! The rows that are due soon are kept on a queue sorted by date, and every job fires at it's
+//! exact time. The queue is refilled from the database periodically, so the rows far in the
+//! future are not kept in memory, and the commands add the new rows to it as they insert them.
+//! The jobs that were missed while the bot was offline fire on startup, with a note of how late
+//! they are, and the ones that failed are retried a few times before they are given up on.
+use crate::global_data::{DatabasePool, JobScheduler, ShutdownSignal};
+use crate::utils::reminders::{snooze_buttons, Recurrence};
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+    error::Error,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use futures::stream::{FuturesUnordered, StreamExt};
+use serenity::{
+    model::id::ChannelId,
+    prelude::{Context, Mutex},
+};
+use tokio::sync::Notify;
+
+// The jobs due within this time are loaded on every refill, it has to be longer than the
+// interval of the `scheduler` task.
+const QUEUE_HORIZON_HOURS: i64 = 24;
+// The queue is checked at least this often, in case the clock jumps.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+// Jobs that fire later than this get a note saying how late they are.
+const LATE_NOTE_THRESHOLD: i64 = 60;
+// Failed jobs are retried after this many seconds, doubled on every failed attempt.
+const RETRY_DELAY: i64 = 60;
+// Jobs that failed this many times are given up on.
+const MAX_ATTEMPTS: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JobKind {
+    Reminder,
+    Unmute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Job {
+    date: DateTime<Utc>,
+    kind: JobKind,
+    id: i32,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    jobs: BinaryHeap<Reverse<Job>>,
+    queued: HashSet<Job>,
+}
+
+/// The queue of the jobs that are due soon.
+///
+/// The commands that set reminders or mutes push to it, and every new job wakes up the
+/// scheduler task, in case it is due before the one it was waiting for.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    queue: Arc<Mutex<Queue>>,
+    notify: Arc<Notify>,
+}
+
+impl Scheduler {
+    /// Queues a job, if it's due soon enough. The rest get queued by the refills.
+    pub async fn schedule(&self, kind: JobKind, id: i32, date: DateTime<Utc>) {
+        if date > Utc::now() + chrono::Duration::hours(QUEUE_HORIZON_HOURS) {
+            return;
+        }
+
+        let job = Job { date, kind, id };
+
+        let mut queue = self.queue.lock().await;
+        if queue.queued.insert(job) {
+            queue.jobs.push(Reverse(job));
+            drop(queue);
+
+            self.notify.notify_one();
+        }
+    }
+
+    // Removes the jobs that are due, and returns them with the date of the next job.
+    async fn pop_due(&self) -> (Vec<Job>, Option<DateTime<Utc>>) {
+        let now = Utc::now();
+        let mut queue = self.queue.lock().await;
+        let mut due = Vec::new();
+
+        while let Some(Reverse(job)) = queue.jobs.peek().copied() {
+            if job.date > now {
+                return (due, Some(job.date));
+            }
+
+            queue.jobs.pop();
+            queue.queued.remove(&job);
+            due.push(job);
+        }
+
+        (due, None)
+    }
+}
+
+/// Queues a job on the scheduler of the bot.
+pub async fn schedule_job(ctx: &Context, kind: JobKind, id: i32, date: DateTime<Utc>) {
+    let scheduler = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<JobScheduler>().unwrap().clone()
+    };
+
+    scheduler.schedule(kind, id, date).await;
+}
+
+/// Runs the jobs of the queue at their time, until the bot shuts down.
+pub async fn run_scheduler(ctx: Arc<Context>) {
+    let (scheduler, shutdown) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<JobScheduler>().unwrap().clone(),
+            data_read.get::<ShutdownSignal>().unwrap().clone(),
+        )
+    };
+
+    let shutdown_clone = shutdown.clone();
+    shutdown
+        .spawn("scheduler", async move {
+            // The jobs that are running, they are waited on before the bot shuts down.
+            let mut running = FuturesUnordered::new();
+
+            loop {
+                let (due, next) = scheduler.pop_due().await;
+
+                for job in due {
+                    let ctx = Arc::clone(&ctx);
+
+                    running.push(tokio::spawn(async move { run_job(&ctx, job).await }));
+                }
+
+                let sleep = next
+                    .map(|date| (date - Utc::now()).to_std().unwrap_or_default())
+                    .unwrap_or(MAX_SLEEP)
+                    .min(MAX_SLEEP);
+
+                tokio::select! {
+                    _ = tokio::time::sleep(sleep) => (),
+                    _ = scheduler.notify.notified() => (),
+                    Some(_) = running.next(), if !running.is_empty() => (),
+                    _ = shutdown_clone.wait() => break,
+                }
+            }
+
+            while running.next().await.is_some() {}
+        })
+        .await;
+}
+
+async fn run_job(ctx: &Context, job: Job) {
+    let result = match job.kind {
+        JobKind::Reminder => fire_reminder(ctx, job.id).await,
+        JobKind::Unmute => fire_unmute(ctx, job.id).await,
+    };
+
+    if let Err(why) = result {
+        error!("Error running the {:?} job {}: {}", job.kind, job.id, why);
+    }
+}
+
+/// Queues the jobs that are due soon, including the ones that were missed.
+pub async fn load_upcoming_jobs(ctx: Arc<Context>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (pool, scheduler) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<DatabasePool>().unwrap().clone(),
+            data_read.get::<JobScheduler>().unwrap().clone(),
+        )
+    };
+
+    let until = Utc::now() + chrono::Duration::hours(QUEUE_HORIZON_HOURS);
+
+    // The jobs that failed are queued for their retry, which is always after their date.
+    let reminders = sqlx::query!(
+        "SELECT id, date, retry_at FROM reminders WHERE date < $1",
+        until
+    )
+    .fetch_all(&pool)
+    .await?;
+    for row in reminders {
+        let date = row.retry_at.unwrap_or(row.date);
+        scheduler.schedule(JobKind::Reminder, row.id, date).await;
+    }
+
+    let muted_members = sqlx::query!(
+        "SELECT id, date, retry_at FROM muted_members WHERE date < $1",
+        until
+    )
+    .fetch_all(&pool)
+    .await?;
+    for row in muted_members {
+        let date = row.retry_at.unwrap_or(row.date);
+        scheduler.schedule(JobKind::Unmute, row.id, date).await;
+    }
+
+    Ok(())
+}
+
+// A note of how late a job fired, when it was missed.
+fn late_note(date: DateTime<Utc>) -> Option<String> {
+    let late = (Utc::now() - date).num_seconds();
+
+    if late > LATE_NOTE_THRESHOLD {
+        let late = humantime::format_duration(Duration::from_secs(late as u64));
+        Some(format!("Late by {}.", late))
+    } else {
+        None
+    }
+}
+
+// When a job that failed for the nth time is tried again.
+fn retry_date(attempts: i32) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(RETRY_DELAY << (attempts - 1))
+}
+
+fn jump_url(guild_id: i64, channel_id: i64, message_id: i64) -> String {
+    format!(
+        "[Jump](https://discord.com/channels/{}/{}/{})",
+        if guild_id == 0 {
+            "@me".to_string()
+        } else {
+            guild_id.to_string()
+        },
+        channel_id,
+        message_id,
+    )
+}
+
+// The row is locked until the reminder is sent, so a job that got queued twice only fires once,
+// and it's only deleted, or moved to its next date when it recurs, after it was delivered.
+// Rows which date was moved later are left for their new date.
+async fn fire_reminder(ctx: &Context, id: i32) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query!(
+        "SELECT date, message_id, channel_id, guild_id, user_id, message, recurrence, failed_attempts FROM reminders WHERE id = $1 AND date <= $2 AND (retry_at IS NULL OR retry_at <= $2) FOR UPDATE SKIP LOCKED",
+        id,
+        Utc::now()
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let row = if let Some(x) = row {
+        x
+    } else {
+        return Ok(());
+    };
+
+    let recurrence = row.recurrence.as_deref().and_then(Recurrence::from_db);
+
+    let footer = recurrence
+        .as_ref()
+        .map(|r| format!("Repeats {}. ID: {}", r, id))
+        .into_iter()
+        .chain(late_note(row.date))
+        .collect::<Vec<_>>();
+
+    let sent = ChannelId(row.channel_id as u64)
+        .send_message(ctx, |m| {
+            m.content(format!("<@!{}>: Reminder!", row.user_id));
+            m.embed(|e| {
+                e.description(row.message.as_deref().unwrap_or("No Message."));
+                e.field(
+                    "Original Message",
+                    jump_url(row.guild_id, row.channel_id, row.message_id),
+                    true,
+                );
+                if !footer.is_empty() {
+                    e.footer(|f| f.text(footer.join("\n")));
+                }
+                e
+            });
+            m.components(|c| snooze_buttons(c, row.user_id))
+        })
+        .await;
+
+    if let Err(why) = sent {
+        let attempts = row.failed_attempts + 1;
+
+        if attempts < MAX_ATTEMPTS {
+            let retry_at = retry_date(attempts);
+
+            sqlx::query!(
+                "UPDATE reminders SET failed_attempts = $2, retry_at = $3 WHERE id = $1",
+                id,
+                attempts,
+                retry_at
+            )
+            .execute(&mut tx)
+            .await?;
+            tx.commit().await?;
+
+            schedule_job(ctx, JobKind::Reminder, id, retry_at).await;
+            return Err(why.into());
+        }
+
+        error!(
+            "Giving up on the reminder {} after {} attempts: {}",
+            id, attempts, why
+        );
+    }
+
+    if let Some(recurrence) = &recurrence {
+        let next = recurrence.next_from_now(row.date);
+
+        sqlx::query!(
+            "UPDATE reminders SET date = $2, failed_attempts = 0, retry_at = NULL WHERE id = $1",
+            id,
+            next
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        schedule_job(ctx, JobKind::Reminder, id, next).await;
+    } else {
+        sqlx::query!("DELETE FROM reminders WHERE id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+    }
+
+    Ok(())
+}
+
+// Like the reminders, the row is locked until the role is removed, and only deleted after.
+async fn fire_unmute(ctx: &Context, id: i32) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let pool = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<DatabasePool>().unwrap().clone()
+    };
+
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query!(
+        "SELECT date, message_id, channel_id, guild_id, user_id, message, failed_attempts FROM muted_members WHERE id = $1 AND date <= $2 AND (retry_at IS NULL OR retry_at <= $2) FOR UPDATE SKIP LOCKED",
+        id,
+        Utc::now()
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let row = if let Some(x) = row {
+        x
+    } else {
+        return Ok(());
+    };
+
+    let channel_id = ChannelId(row.channel_id as u64);
+
+    let role_row = sqlx::query!(
+        "SELECT role_id FROM muted_roles WHERE guild_id = $1",
+        row.guild_id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    // Retrying wouldn't help without a role, so the mute is dropped.
+    let role_id = if let Some(role_row) = role_row {
+        role_row.role_id as u64
+    } else {
+        sqlx::query!("DELETE FROM muted_members WHERE id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        channel_id
+            .say(
+                ctx,
+                format!(
+                    "Unable to unmute <@{}> from temporal mute because there's no configured role.",
+                    row.user_id
+                ),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let unmuted = match ctx
+        .http
+        .get_member(row.guild_id as u64, row.user_id as u64)
+        .await
+    {
+        Ok(mut member) => member.remove_role(ctx, role_id).await,
+        Err(why) => Err(why),
+    };
+
+    if let Err(why) = unmuted {
+        let attempts = row.failed_attempts + 1;
+
+        if attempts < MAX_ATTEMPTS {
+            let retry_at = retry_date(attempts);
+
+            sqlx::query!(
+                "UPDATE muted_members SET failed_attempts = $2, retry_at = $3 WHERE id = $1",
+                id,
+                attempts,
+                retry_at
+            )
+            .execute(&mut tx)
+            .await?;
+            tx.commit().await?;
+
+            schedule_job(ctx, JobKind::Unmute, id, retry_at).await;
+            return Err(why.into());
+        }
+
+        error!(
+            "Giving up on the unmute {} after {} attempts: {}",
+            id, attempts, why
+        );
+
+        sqlx::query!("DELETE FROM muted_members WHERE id = $1", id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        channel_id
+            .say(
+                ctx,
+                format!("Unable to unmute <@{}> from temporal mute.", row.user_id),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    sqlx::query!("DELETE FROM muted_members WHERE id = $1", id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    channel_id
+        .send_message(ctx, |m| {
+            m.content(format!("<@!{}> has been unmuted.", row.user_id));
+            m.embed(|e| {
+                e.description(if let Some(x) = &row.message {
+                    format!("Mute Reason: {}", x)
+                } else {
+                    "No Message.".to_string()
+                });
+                e.field(
+                    "Original Message",
+                    jump_url(row.guild_id, row.channel_id, row.message_id),
+                    true,
+                );
+                if let Some(note) = late_note(row.date) {
+                    e.footer(|f| f.text(note));
+                }
+                e
+            })
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! History of the posts that were already sent by every booru subscription.
+use std::collections::HashSet;
+
+use sqlx::PgPool;
+
+// The posts that haven't been on the latest posts of their booru for this long get pruned.
+pub const RETENTION_DAYS: i32 = 30;
+
+/// Obtains which of the posts were already sent by a subscription.
+pub async fn already_sent(
+    pool: &PgPool,
+    subscription_id: i32,
+    post_hashes: &[String],
+) -> Result<HashSet<String>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT post_hash FROM sent_posts WHERE subscription_id = $1 AND post_hash = ANY($2)",
+        subscription_id,
+        post_hashes
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.post_hash).collect())
+}
+
+/// Records the posts as sent, in a single query.
+/// The posts that were already sent get marked as seen, so they are not pruned while they are
+/// still on the latest posts of the booru.
+pub async fn mark_as_sent(
+    pool: &PgPool,
+    subscription_id: i32,
+    post_hashes: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut post_hashes = post_hashes.to_vec();
+    post_hashes.sort();
+    post_hashes.dedup();
+
+    if post_hashes.is_empty() {
+        return Ok(());
+    }
+
+    // Refreshing last_seen_at once a day is enough, and avoids rewriting the rows on every poll.
+    sqlx::query!(
+        "INSERT INTO sent_posts (subscription_id, post_hash) SELECT $1, unnest($2::text[]) ON CONFLICT (subscription_id, post_hash) DO UPDATE SET last_seen_at = now() WHERE sent_posts.last_seen_at < now() - interval '1 day'",
+        subscription_id,
+        &post_hashes
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes the posts that were not seen for longer than the retention.
+pub async fn prune_sent_posts(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM sent_posts WHERE last_seen_at < now() - make_interval(days => $1)",
+        RETENTION_DAYS
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
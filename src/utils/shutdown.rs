@@ -0,0 +1,167 @@
+use crate::global_data::{Lavalink, ShutdownSignal};
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use serenity::{
+    client::bridge::gateway::ShardManager,
+    model::id::GuildId,
+    prelude::{Mutex, RwLock, TypeMap},
+};
+use songbird::serenity::SongbirdKey;
+use tokio::{
+    sync::watch,
+    task::JoinHandle,
+    time::{timeout_at, Instant},
+};
+
+// How long the background tasks get to finish what they are doing, in total.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Keeps track of the background tasks, and tells them when the bot is shutting down.
+///
+/// Each task takes a clone from the global data, so it has its own receiver of the signal.
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+    tasks: Arc<Mutex<Vec<(&'static str, JoinHandle<()>)>>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let (sender, receiver) = watch::channel(false);
+
+        Self {
+            sender: Arc::new(sender),
+            receiver,
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn is_shutting_down(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Completes once the shutdown has been started.
+    /// Background loops should use this instead of a plain sleep, so they stop right away.
+    pub async fn wait(&self) {
+        let mut receiver = self.receiver.clone();
+
+        while !*receiver.borrow() {
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Sleeps for the duration, unless the bot starts shutting down first.
+    /// Returns false if the sleep was interrupted by the shutdown.
+    pub async fn sleep(&self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => true,
+            _ = self.wait() => false,
+        }
+    }
+
+    /// Spawns a background task that will be waited on when the bot shuts down.
+    pub async fn spawn<F>(&self, name: &'static str, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(future);
+
+        self.tasks.lock().await.push((name, handle));
+    }
+
+    /// Tells every task to stop, and waits for them to finish.
+    pub async fn drain(&self) {
+        let _ = self.sender.send(true);
+
+        let tasks = std::mem::take(&mut *self.tasks.lock().await);
+        let deadline = Instant::now() + DRAIN_TIMEOUT;
+
+        for (name, handle) in tasks {
+            match timeout_at(deadline, handle).await {
+                Ok(Ok(())) => debug!("Background task {} finished.", name),
+                Ok(Err(why)) => error!("Background task {} failed: {}", name, why),
+                Err(_) => warn!("Background task {} didn't finish in time.", name),
+            }
+        }
+    }
+}
+
+/// Completes when the process is asked to stop, with SIGTERM or Ctrl+C.
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {},
+                    _ = tokio::signal::ctrl_c() => {},
+                }
+            }
+            Err(why) => {
+                error!("Could not listen for SIGTERM: {}", why);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Stops the background tasks, leaves every voice channel and disconnects every shard.
+pub async fn graceful_shutdown(
+    data: Arc<RwLock<TypeMap>>,
+    shard_manager: Arc<Mutex<ShardManager>>,
+) {
+    info!("Shutting down.");
+
+    let (shutdown, lavalink, songbird) = {
+        let data_read = data.read().await;
+        (
+            data_read.get::<ShutdownSignal>().cloned(),
+            data_read.get::<Lavalink>().cloned(),
+            data_read.get::<SongbirdKey>().cloned(),
+        )
+    };
+
+    if let Some(shutdown) = shutdown {
+        shutdown.drain().await;
+    }
+
+    if let Some(lavalink) = lavalink {
+        let guilds = lavalink
+            .nodes()
+            .await
+            .iter()
+            .map(|node| *node.key())
+            .collect::<Vec<_>>();
+
+        for guild_id in guilds {
+            let guild_id = GuildId(guild_id);
+
+            if let Err(why) = lavalink.destroy(guild_id).await {
+                error!("Error when destroying session on shutdown: {}", why);
+            }
+
+            if let Some(songbird) = &songbird {
+                if let Err(why) = songbird.remove(guild_id).await {
+                    error!("Error when leaving a call on shutdown: {}", why);
+                }
+            }
+        }
+    }
+
+    shard_manager.lock().await.shutdown_all().await;
+
+    info!("Shutdown complete.");
+}
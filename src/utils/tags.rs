@@ -0,0 +1,91 @@
+use crate::utils::command_rules::is_command_name;
+
+use std::collections::HashSet;
+
+use serenity::model::channel::Message;
+
+// Limits to keep the tags and aliases of a guild small enough to be cached.
+pub const MAX_NAME_LENGTH: usize = 32;
+pub const MAX_TAG_CONTENT_LENGTH: usize = 1900;
+pub const MAX_TAGS_PER_GUILD: usize = 100;
+// Discord rejects messages longer than this.
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// Checks if a name can be used for a tag or a command alias.
+/// They can't shadow commands, as those always get resolved first.
+pub fn validate_custom_command_name(
+    name: &str,
+    booru_commands: &HashSet<String>,
+) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "The name must be between 1 and {} characters long.",
+            MAX_NAME_LENGTH
+        ));
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("The name can only contain letters, numbers, `_` and `-`.".to_string());
+    }
+
+    if is_command_name(name, booru_commands) {
+        return Err("There's already a command with that name.".to_string());
+    }
+
+    Ok(())
+}
+
+/// Replaces the placeholders of a tag.
+///
+/// `{user}`: Mention of the user that used the tag.
+/// `{channel}`: Mention of the channel the tag was used on.
+/// `{args}`: Everything that was written after the name of the tag.
+///
+/// The placeholders can make a tag longer than a message can be, so the result is cut to fit.
+pub fn render_tag(content: &str, msg: &Message, args: &str) -> String {
+    let rendered = content
+        .replace("{user}", &format!("<@{}>", msg.author.id))
+        .replace("{channel}", &format!("<#{}>", msg.channel_id))
+        .replace("{args}", args.trim());
+
+    if rendered.chars().count() > MAX_MESSAGE_LENGTH {
+        format!(
+            "{}…",
+            rendered
+                .chars()
+                .take(MAX_MESSAGE_LENGTH - 1)
+                .collect::<String>()
+        )
+    } else {
+        rendered
+    }
+}
+
+// Strips a prefix from the start of a text, ignoring the case.
+// The text is never lowercased, so the rest keeps it's original case and byte offsets.
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+
+    if head.to_lowercase() == prefix.to_lowercase() {
+        text.get(prefix.len()..)
+    } else {
+        None
+    }
+}
+
+/// Obtains what was written after the name of a tag or alias on a message.
+/// The message has to start with one of the prefixes, followed by the name.
+pub fn arguments_after<'a>(content: &'a str, prefixes: &[String], name: &str) -> &'a str {
+    // The longest prefix that matches is the one used, so `a!!` isn't mistaken for `a!`
+    let rest = prefixes
+        .iter()
+        .filter(|p| !p.is_empty())
+        .filter_map(|p| strip_prefix_ignore_case(content, p))
+        .min_by_key(|rest| rest.len());
+
+    rest.and_then(|rest| strip_prefix_ignore_case(rest.trim_start(), name))
+        .unwrap_or_default()
+}
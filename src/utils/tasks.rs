@@ -0,0 +1,173 @@
+//! Supervisor of the periodic background tasks.
+//!
+//! Every run of a task is spawned on it's own, so a panic only ends that run. The task is then
+//! restarted with an increasing backoff, and the status of every task can be seen with the
+//! `tasks` command.
+use crate::global_data::{BackgroundTasks, ShutdownSignal, Tokens};
+
+use std::{collections::BTreeMap, future::Future, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serenity::prelude::{Context, RwLock};
+use tokio::time::Instant;
+
+// The backoff after a panic doubles with every consecutive panic, up to the maximum.
+const MIN_PANIC_BACKOFF: Duration = Duration::from_secs(10);
+const MAX_PANIC_BACKOFF: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Default)]
+pub struct TaskStatus {
+    pub interval: Duration,
+    pub runs: u64,
+    pub failures: u64,
+    pub panics: u64,
+    pub running_since: Option<DateTime<Utc>>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_duration: Option<Duration>,
+    pub last_error: Option<String>,
+}
+
+impl TaskStatus {
+    /// A task is considered stuck when a single run takes longer than twice it's interval.
+    pub fn is_stuck(&self) -> bool {
+        self.running_since.map_or(false, |since| {
+            let running_for = (Utc::now() - since).to_std().unwrap_or_default();
+            running_for > self.interval * 2
+        })
+    }
+}
+
+/// Keeps the status of every periodic task.
+#[derive(Debug, Clone, Default)]
+pub struct TaskSupervisor {
+    statuses: Arc<RwLock<BTreeMap<&'static str, TaskStatus>>>,
+}
+
+impl TaskSupervisor {
+    /// A snapshot of the status of every task, sorted by name.
+    pub async fn statuses(&self) -> Vec<(&'static str, TaskStatus)> {
+        self.statuses
+            .read()
+            .await
+            .iter()
+            .map(|(name, status)| (*name, status.clone()))
+            .collect()
+    }
+
+    async fn update(&self, name: &'static str, f: impl FnOnce(&mut TaskStatus)) {
+        f(self.statuses.write().await.entry(name).or_default());
+    }
+}
+
+// The interval can be changed on the configuration, and it's read again before every run,
+// so it can be changed with a reload.
+async fn task_interval(ctx: &Context, name: &str, default: Duration) -> Duration {
+    let data_read = ctx.data.read().await;
+    let config = data_read.get::<Tokens>().unwrap();
+
+    config
+        .task_intervals
+        .get(name)
+        .map_or(default, Duration::from_secs)
+}
+
+/// Runs a job every `default_interval`, or the interval configured on `task_intervals`.
+/// The job is supervised, so it's status is recorded and it's restarted if it panics.
+pub async fn spawn_periodic<F, Fut>(
+    ctx: Arc<Context>,
+    name: &'static str,
+    default_interval: Duration,
+    job: F,
+) where
+    F: Fn(Arc<Context>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    let (shutdown, supervisor) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<ShutdownSignal>().unwrap().clone(),
+            data_read.get::<BackgroundTasks>().unwrap().clone(),
+        )
+    };
+
+    let interval = task_interval(&ctx, name, default_interval).await;
+    supervisor
+        .update(name, |s| {
+            s.interval = interval;
+        })
+        .await;
+
+    let shutdown_clone = shutdown.clone();
+    shutdown
+        .spawn(name, async move {
+            let mut consecutive_panics = 0;
+
+            loop {
+                let interval = task_interval(&ctx, name, default_interval).await;
+                supervisor
+                    .update(name, |s| {
+                        s.interval = interval;
+                        s.running_since = Some(Utc::now());
+                    })
+                    .await;
+
+                let start = Instant::now();
+                // The run is awaited even when shutting down, so it doesn't get cut half way.
+                let result = tokio::spawn(job(Arc::clone(&ctx))).await;
+                let duration = start.elapsed();
+
+                let panicked = result.is_err();
+                let error = match result {
+                    Ok(Ok(())) => None,
+                    Ok(Err(why)) => {
+                        error!("Background task {} failed: {}", name, why);
+                        Some(why)
+                    }
+                    Err(why) => {
+                        error!("Background task {} panicked: {}", name, why);
+                        Some(format!("Panicked: {}", why))
+                    }
+                };
+
+                supervisor
+                    .update(name, |s| {
+                        let now = Utc::now();
+
+                        s.runs += 1;
+                        s.running_since = None;
+                        s.last_run = Some(now);
+                        s.last_duration = Some(duration);
+
+                        if panicked {
+                            s.panics += 1;
+                        }
+
+                        if error.is_some() {
+                            s.failures += 1;
+                            s.last_error = error;
+                        } else {
+                            s.last_success = Some(now);
+                        }
+                    })
+                    .await;
+
+                let wait = if panicked {
+                    consecutive_panics += 1;
+                    let backoff = MIN_PANIC_BACKOFF * 2_u32.pow((consecutive_panics - 1).min(6));
+                    let backoff = backoff.min(MAX_PANIC_BACKOFF);
+
+                    warn!("Restarting background task {} in {:?}.", name, backoff);
+                    backoff
+                } else {
+                    consecutive_panics = 0;
+                    interval
+                };
+
+                if !shutdown_clone.sleep(wait).await {
+                    break;
+                }
+            }
+        })
+        .await;
+}
@@ -0,0 +1,166 @@
+//! Parser of the times given to the commands.
+//!
+//! Times are either relative, like `2h 30m`, or absolute, like `2026-12-24 18:00`,
+//! `tomorrow 9am` or `friday`. The absolute ones are read in the time zone the user set with
+//! `config user timezone`, UTC by default.
+use crate::utils::basic_functions::string_to_seconds;
+
+use std::str::FromStr;
+
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+
+// The time of the dates that don't say one.
+pub const DEFAULT_TIME: (u32, u32) = (9, 0);
+
+pub const INVALID_TIME: &str =
+    "That's not a valid time, use something like `2h 30m`, `tomorrow 9am`, `friday 18:00` or `2026-12-24 18:00`.";
+
+/// Parses a time of the day, like `9:00`, `21:30`, `9am` or `9:30pm`.
+pub fn parse_time_of_day(text: &str) -> Option<NaiveTime> {
+    let text = text.trim().to_lowercase();
+
+    let (text, offset) = if let Some(x) = text.strip_suffix("am") {
+        (x, Some(0))
+    } else if let Some(x) = text.strip_suffix("pm") {
+        (x, Some(12))
+    } else {
+        (text.as_str(), None)
+    };
+
+    let mut split = text.trim().splitn(2, ':');
+    let mut hour = split.next()?.parse::<u32>().ok()?;
+    let minute = split.next().map_or(Some(0), |m| m.parse::<u32>().ok())?;
+
+    // A plain number without am or pm is not a time, it could be part of something else.
+    if offset.is_none() && !text.contains(':') {
+        return None;
+    }
+
+    if let Some(offset) = offset {
+        if hour == 0 || hour > 12 {
+            return None;
+        }
+        hour = hour % 12 + offset;
+    }
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Parses a day of the week, like `mon`, `monday` or `mondays`.
+pub fn parse_weekday(text: &str) -> Option<Weekday> {
+    Weekday::from_str(text)
+        .or_else(|_| Weekday::from_str(text.strip_suffix('s').unwrap_or(text)))
+        .ok()
+}
+
+/// Converts a date of a time zone to UTC.
+/// Ambiguous dates take the earliest one, and the ones skipped by a DST change the hour after.
+pub fn local_to_utc(tz: Tz, date: NaiveDateTime) -> DateTime<Utc> {
+    tz.from_local_datetime(&date)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(date + Duration::hours(1)))
+                .earliest()
+        })
+        .map_or_else(
+            || DateTime::<Utc>::from_utc(date, Utc),
+            |d| d.with_timezone(&Utc),
+        )
+}
+
+// Parses the absolute times, the ones without a date are on the next time that hour comes.
+fn parse_absolute(text: &str, now: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+    let text = text.trim().to_lowercase();
+
+    for format in &["%Y-%m-%d %H:%M", "%Y-%m-%dt%H:%M", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(&text, format) {
+            return Some(local_to_utc(tz, date));
+        }
+    }
+
+    let mut words = text
+        .split_whitespace()
+        .filter(|w| !["at", "on", "next"].contains(w))
+        .collect::<Vec<_>>();
+
+    let time = words.last().and_then(|w| parse_time_of_day(w));
+    if time.is_some() {
+        words.pop();
+    }
+
+    let today = now.with_timezone(&tz).naive_local().date();
+    let default_time = NaiveTime::from_hms(DEFAULT_TIME.0, DEFAULT_TIME.1, 0);
+
+    let date = match words.as_slice() {
+        [] => {
+            let time = time?;
+            let date = local_to_utc(tz, today.and_time(time));
+
+            return Some(if date > now {
+                date
+            } else {
+                local_to_utc(tz, (today + Duration::days(1)).and_time(time))
+            });
+        }
+        ["today"] => today,
+        ["tomorrow"] => today + Duration::days(1),
+        [word] => {
+            if let Some(weekday) = parse_weekday(word) {
+                let time = time.unwrap_or(default_time);
+
+                // Today counts when the time didn't pass yet.
+                return (0..=7)
+                    .map(|i| today + Duration::days(i))
+                    .filter(|d| d.weekday() == weekday)
+                    .map(|d| local_to_utc(tz, d.and_time(time)))
+                    .find(|d| *d > now);
+            }
+
+            NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()?
+        }
+        _ => return None,
+    };
+
+    Some(local_to_utc(
+        tz,
+        date.and_time(time.unwrap_or(default_time)),
+    ))
+}
+
+/// Parses a relative or an absolute time, and returns the date it points to.
+pub fn parse_time(text: &str, now: DateTime<Utc>, tz: Tz) -> Result<DateTime<Utc>, &'static str> {
+    if let Some(date) = parse_absolute(text, now, tz) {
+        if date <= now {
+            return Err("That time is in the past.");
+        }
+
+        return Ok(date);
+    }
+
+    // The units are case sensitive, `1M` is a month and `1m` a minute.
+    let seconds = string_to_seconds(text.split_whitespace().collect::<String>());
+
+    if seconds == 0 {
+        return Err(INVALID_TIME);
+    }
+
+    Ok(now + Duration::seconds(seconds as i64))
+}
+
+/// Obtains the time zone of a user, UTC when they didn't set one.
+pub async fn user_timezone(pool: &PgPool, user_id: u64) -> Result<Tz, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT timezone FROM user_timezones WHERE user_id = $1",
+        user_id as i64
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row
+        .and_then(|r| r.timezone.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC))
+}
@@ -0,0 +1,447 @@
+//! Client of the Twitch Helix API, used by the livestream notifications.
+//!
+//! Logins and ids are batched up to the limit of Helix, the games and profile pictures are
+//! cached, and the app token is obtained and refreshed with the client credentials.
+use crate::global_data::{Tokens, TwitchApiCache};
+
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{Client as ReqwestClient, Method, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use serenity::prelude::{Context, Mutex, RwLock};
+
+// Helix accepts up to 100 logins or ids on a single request.
+const MAX_IDS_PER_REQUEST: usize = 100;
+// The app token is refreshed when it's going to expire in less than this many seconds.
+const TOKEN_REFRESH_MARGIN: i64 = 24 * 60 * 60;
+// Profile pictures can change, so they are obtained again after this many seconds.
+const PROFILE_IMAGE_TTL: i64 = 24 * 60 * 60;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TwitchStreamData {
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+    pub game_id: String,
+    pub title: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TwitchUser {
+    id: String,
+    login: String,
+    profile_image_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TwitchGame {
+    id: String,
+    name: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct EventSubCondition {
+    pub broadcaster_user_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct EventSubTransport {
+    pub method: String,
+    pub callback: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EventSubSubscription {
+    pub id: String,
+    pub status: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub condition: EventSubCondition,
+    #[serde(default)]
+    pub transport: EventSubTransport,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Pagination {
+    cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HelixResponse<T> {
+    data: Vec<T>,
+    #[serde(default)]
+    pagination: Pagination,
+}
+
+#[derive(Deserialize, Debug)]
+struct AppTokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+#[derive(Debug, Clone)]
+struct AppToken {
+    client_id: String,
+    access_token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// The state of the Twitch API that's kept between runs of the notifications.
+#[derive(Debug, Default)]
+pub struct TwitchCache {
+    http: ReqwestClient,
+    token: Option<AppToken>,
+    game_names: HashMap<String, String>,
+    // The profile pictures by user id, with the date they were obtained.
+    profile_images: HashMap<String, (DateTime<Utc>, String)>,
+    // Polling and EventSub check the same streams, so they take turns.
+    notifications_lock: Arc<Mutex<()>>,
+}
+
+pub struct Helix {
+    api_url: String,
+    auth_url: String,
+    client_id: String,
+    client_secret: Option<String>,
+    static_token: Option<String>,
+    cache: Arc<RwLock<TwitchCache>>,
+}
+
+impl Helix {
+    /// Obtains a client with the current configuration.
+    /// Returns None when twitch is not configured.
+    pub async fn new(ctx: &Context) -> Option<Self> {
+        let data_read = ctx.data.read().await;
+        let config = data_read.get::<Tokens>().unwrap();
+
+        Some(Self {
+            api_url: config.twitch_api_url.trim_end_matches('/').to_string(),
+            auth_url: config.twitch_auth_url.trim_end_matches('/').to_string(),
+            client_id: config.twitch_client_id.clone()?,
+            client_secret: config.twitch_client_secret.clone(),
+            static_token: config.twitch.clone(),
+            cache: data_read.get::<TwitchApiCache>().unwrap().clone(),
+        })
+    }
+
+    /// Obtains a new app token with the client credentials, if it's close to expire.
+    pub async fn refresh_token_if_needed(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.client_secret.is_some() {
+            self.token(false).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn token(&self, force_refresh: bool) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let client_secret = if let Some(x) = &self.client_secret {
+            x
+        } else {
+            return self
+                .static_token
+                .clone()
+                .ok_or_else(|| "There's no twitch token configured.".into());
+        };
+
+        if !force_refresh {
+            let cache = self.cache.read().await;
+
+            if let Some(token) = &cache.token {
+                let expires_soon =
+                    token.expires_at - Duration::seconds(TOKEN_REFRESH_MARGIN) < Utc::now();
+
+                if token.client_id == self.client_id && !expires_soon {
+                    return Ok(token.access_token.clone());
+                }
+            }
+        }
+
+        let http = self.cache.read().await.http.clone();
+        let res = http
+            .post(format!("{}/token", self.auth_url))
+            .query(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", client_secret.as_str()),
+                ("grant_type", "client_credentials"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<AppTokenResponse>()
+            .await?;
+
+        info!("Obtained a new twitch app token.");
+
+        self.cache.write().await.token = Some(AppToken {
+            client_id: self.client_id.clone(),
+            access_token: res.access_token.clone(),
+            expires_at: Utc::now() + Duration::seconds(res.expires_in),
+        });
+
+        Ok(res.access_token)
+    }
+
+    // The token gets refreshed once if Helix rejects it, as it could have been revoked.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
+    ) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        let http = self.cache.read().await.http.clone();
+        let mut refreshed = false;
+
+        loop {
+            let token = self.token(refreshed).await?;
+
+            let mut request = http
+                .request(method.clone(), format!("{}/{}", self.api_url, path))
+                .query(query)
+                .bearer_auth(token)
+                .header("Client-ID", &self.client_id);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+
+            let res = request.send().await?;
+
+            match res.status() {
+                StatusCode::UNAUTHORIZED if !refreshed && self.client_secret.is_some() => {
+                    refreshed = true;
+                }
+                StatusCode::TOO_MANY_REQUESTS => {
+                    let reset = res
+                        .headers()
+                        .get("Ratelimit-Reset")
+                        .and_then(|x| x.to_str().ok())
+                        .unwrap_or("unknown")
+                        .to_string();
+
+                    return Err(format!("Rate limited by Helix until {}.", reset).into());
+                }
+                _ => return Ok(res.error_for_status()?),
+            }
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .send(Method::GET, path, query, None)
+            .await?
+            .json::<HelixResponse<T>>()
+            .await?
+            .data)
+    }
+
+    /// The lock that has to be held while checking and notifying streams.
+    pub async fn notifications_lock(&self) -> Arc<Mutex<()>> {
+        Arc::clone(&self.cache.read().await.notifications_lock)
+    }
+
+    /// Obtains the streams that are live from a list of logins.
+    pub async fn get_streams(
+        &self,
+        logins: &[String],
+    ) -> Result<Vec<TwitchStreamData>, Box<dyn Error + Send + Sync>> {
+        let mut streams = Vec::new();
+
+        for chunk in logins.chunks(MAX_IDS_PER_REQUEST) {
+            let first = chunk.len().to_string();
+            let mut query = vec![("first", first.as_str())];
+            query.extend(chunk.iter().map(|login| ("user_login", login.as_str())));
+
+            streams.extend(self.get::<TwitchStreamData>("streams", &query).await?);
+        }
+
+        Ok(streams)
+    }
+
+    /// Obtains the names of a list of games, by their ids.
+    pub async fn game_names(
+        &self,
+        ids: &[String],
+    ) -> Result<HashMap<String, String>, Box<dyn Error + Send + Sync>> {
+        let missing = self
+            .missing_ids(ids, |cache, id| cache.game_names.contains_key(id))
+            .await;
+
+        for chunk in missing.chunks(MAX_IDS_PER_REQUEST) {
+            let query = chunk
+                .iter()
+                .map(|id| ("id", id.as_str()))
+                .collect::<Vec<_>>();
+            let games = self.get::<TwitchGame>("games", &query).await?;
+
+            let mut cache = self.cache.write().await;
+            for game in games {
+                let name = game.name.unwrap_or_else(|| "No Game".to_string());
+                cache.game_names.insert(game.id, name);
+            }
+        }
+
+        let cache = self.cache.read().await;
+        Ok(ids
+            .iter()
+            .filter_map(|id| Some((id.clone(), cache.game_names.get(id)?.clone())))
+            .collect())
+    }
+
+    /// Obtains the profile pictures of a list of users, by their ids.
+    pub async fn profile_images(
+        &self,
+        ids: &[String],
+    ) -> Result<HashMap<String, String>, Box<dyn Error + Send + Sync>> {
+        let now = Utc::now();
+        let missing = self
+            .missing_ids(ids, |cache, id| {
+                cache
+                    .profile_images
+                    .get(id)
+                    .map_or(false, |(obtained_at, _)| {
+                        now - *obtained_at < Duration::seconds(PROFILE_IMAGE_TTL)
+                    })
+            })
+            .await;
+
+        for chunk in missing.chunks(MAX_IDS_PER_REQUEST) {
+            let query = chunk
+                .iter()
+                .map(|id| ("id", id.as_str()))
+                .collect::<Vec<_>>();
+            let users = self.get::<TwitchUser>("users", &query).await?;
+
+            let mut cache = self.cache.write().await;
+            for user in users {
+                cache
+                    .profile_images
+                    .insert(user.id, (now, user.profile_image_url));
+            }
+        }
+
+        let cache = self.cache.read().await;
+        Ok(ids
+            .iter()
+            .filter_map(|id| Some((id.clone(), cache.profile_images.get(id)?.1.clone())))
+            .collect())
+    }
+
+    /// Obtains the ids of a list of logins, by their lowercase login.
+    pub async fn user_ids(
+        &self,
+        logins: &[String],
+    ) -> Result<HashMap<String, String>, Box<dyn Error + Send + Sync>> {
+        let mut ids = HashMap::new();
+
+        for chunk in logins.chunks(MAX_IDS_PER_REQUEST) {
+            let query = chunk
+                .iter()
+                .map(|login| ("login", login.as_str()))
+                .collect::<Vec<_>>();
+
+            for user in self.get::<TwitchUser>("users", &query).await? {
+                ids.insert(user.login.to_lowercase(), user.id);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Obtains every EventSub subscription of the app, following the pagination.
+    pub async fn eventsub_subscriptions(
+        &self,
+    ) -> Result<Vec<EventSubSubscription>, Box<dyn Error + Send + Sync>> {
+        let mut subscriptions = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let mut query = Vec::new();
+            if let Some(cursor) = &cursor {
+                query.push(("after", cursor.as_str()));
+            }
+
+            let page = self
+                .send(Method::GET, "eventsub/subscriptions", &query, None)
+                .await?
+                .json::<HelixResponse<EventSubSubscription>>()
+                .await?;
+
+            subscriptions.extend(page.data);
+
+            cursor = page.pagination.cursor.filter(|c| !c.is_empty());
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(subscriptions)
+    }
+
+    /// Subscribes to an event of a broadcaster, sent to the callback with a webhook.
+    /// Twitch verifies the callback before it's enabled.
+    pub async fn create_eventsub_subscription(
+        &self,
+        kind: &str,
+        broadcaster_user_id: &str,
+        callback: &str,
+        secret: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let body = serde_json::json!({
+            "type": kind,
+            "version": "1",
+            "condition": {
+                "broadcaster_user_id": broadcaster_user_id,
+            },
+            "transport": {
+                "method": "webhook",
+                "callback": callback,
+                "secret": secret,
+            },
+        });
+
+        self.send(Method::POST, "eventsub/subscriptions", &[], Some(&body))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_eventsub_subscription(
+        &self,
+        id: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.send(
+            Method::DELETE,
+            "eventsub/subscriptions",
+            &[("id", id)],
+            None,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    // The ids that are not cached yet or expired, without duplicates.
+    async fn missing_ids(
+        &self,
+        ids: &[String],
+        is_cached: impl Fn(&TwitchCache, &str) -> bool,
+    ) -> Vec<String> {
+        let cache = self.cache.read().await;
+
+        let mut missing = ids
+            .iter()
+            .filter(|id| !id.is_empty() && !is_cached(&cache, id))
+            .cloned()
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+
+        missing
+    }
+}